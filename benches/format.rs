@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+
+use syntect::{highlighting::ThemeSet, parsing::SyntaxSet};
+
+/// Run `f` until a second has passed, at least three times, and print the
+/// fastest run.
+fn bench(name: &str, mut f: impl FnMut()) {
+    let started = Instant::now();
+    let mut runs = 0;
+    let mut best = Duration::MAX;
+    while runs < 3 || started.elapsed() < Duration::from_secs(1) {
+        let at = Instant::now();
+        f();
+        best = best.min(at.elapsed());
+        runs += 1;
+    }
+    println!("{:<24}{:>10.2?}  (best of {})", name, best, runs);
+}
+
+/// A pretty-printed JSON body of about `n` items.
+fn body(n: usize) -> String {
+    let items: Vec<_> = (0..n)
+        .map(|i| serde_json::json!({"id": i, "name": format!("item {}", i), "tags": ["a", "b"], "nested": {"deep": [i, null]}}))
+        .collect();
+    serde_json::to_string_pretty(&serde_json::json!({"items": items})).unwrap()
+}
+
+fn main() {
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let theme = &ts.themes["base16-ocean.light"];
+    let json = ps.find_syntax_by_extension("json").unwrap();
+
+    for n in [100, 5_000] {
+        let body = body(n);
+        let size = format!("{}KiB", body.len() / 1024);
+        bench(&format!("highlight {}", size), || {
+            let mut len = 0;
+            httpie::highlight::highlight(&body, json, &ps, theme, |chunk| len += chunk.len());
+            assert!(len > 0);
+        });
+        bench(&format!("sort_json {}", size), || {
+            assert!(!httpie::sort_json(body.clone()).is_empty());
+        });
+        bench(&format!("to_vec_pretty {}", size), || {
+            let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert!(!serde_json::to_vec_pretty(&value).unwrap().is_empty());
+        });
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use flate2::read::DeflateDecoder;
+
+use crate::body;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Method {
+    Stored,
+    Deflate,
+    Other(u16),
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub name: String,
+    /// unpacked
+    pub size: u64,
+    pub dir: bool,
+    /// links and devices are listed but not extracted
+    special: bool,
+    method: Method,
+    data: Vec<u8>,
+}
+
+impl Entry {
+    pub fn contents(&self) -> Result<Vec<u8>> {
+        match self.method {
+            Method::Stored => Ok(self.data.clone()),
+            Method::Deflate => {
+                let mut out = Vec::new();
+                DeflateDecoder::new(&self.data[..])
+                    .read_to_end(&mut out)
+                    .with_context(|| format!("Failed to inflate {}", self.name))?;
+                Ok(out)
+            }
+            Method::Other(m) => Err(anyhow!(format!(
+                "Failed to extract {}: compression method {} is not supported",
+                self.name, m
+            ))),
+        }
+    }
+}
+
+pub struct Archive {
+    pub kind: Kind,
+    pub entries: Vec<Entry>,
+}
+
+/// The archive in `bytes`, recognized by its magic number: zip, tar, or a
+/// gzipped tar.
+pub fn open(bytes: &[u8]) -> Option<Archive> {
+    if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        return Some(Archive {
+            kind: Kind::Zip,
+            entries: zip(bytes)?,
+        });
+    }
+    if is_tar(bytes) {
+        return Some(Archive {
+            kind: Kind::Tar,
+            entries: tar(bytes)?,
+        });
+    }
+    if body::is_gzip(bytes) {
+        let tarball = body::gunzip(bytes).ok()?;
+        if is_tar(&tarball) {
+            return Some(Archive {
+                kind: Kind::TarGz,
+                entries: tar(&tarball)?,
+            });
+        }
+    }
+    None
+}
+
+fn u16_at(b: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(b.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn u32_at(b: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(b.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Entries from the central directory at the end of a zip file.
+fn zip(bytes: &[u8]) -> Option<Vec<Entry>> {
+    // the end record is 22 bytes, followed by a comment of up to 64KiB
+    let earliest = bytes.len().saturating_sub(22 + 0xffff);
+    let end = (earliest..=bytes.len().checked_sub(22)?)
+        .rev()
+        .find(|&i| u32_at(bytes, i) == Some(0x06054b50))?;
+    let count = u16_at(bytes, end + 10)?;
+    let mut at = u32_at(bytes, end + 16)? as usize;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        if u32_at(bytes, at)? != 0x02014b50 {
+            return None;
+        }
+        let method = match u16_at(bytes, at + 10)? {
+            0 => Method::Stored,
+            8 => Method::Deflate,
+            m => Method::Other(m),
+        };
+        let packed = u32_at(bytes, at + 20)? as usize;
+        let size = u64::from(u32_at(bytes, at + 24)?);
+        let name_len = usize::from(u16_at(bytes, at + 28)?);
+        let skip = usize::from(u16_at(bytes, at + 30)?) + usize::from(u16_at(bytes, at + 32)?);
+        let local = u32_at(bytes, at + 42)? as usize;
+        let name = String::from_utf8_lossy(bytes.get(at + 46..at + 46 + name_len)?).into_owned();
+        at += 46 + name_len + skip;
+
+        if u32_at(bytes, local)? != 0x04034b50 {
+            return None;
+        }
+        let start = local
+            + 30
+            + usize::from(u16_at(bytes, local + 26)?)
+            + usize::from(u16_at(bytes, local + 28)?);
+        entries.push(Entry {
+            dir: name.ends_with('/'),
+            special: false,
+            data: bytes.get(start..start.checked_add(packed)?)?.to_vec(),
+            name,
+            size,
+            method,
+        });
+    }
+    Some(entries)
+}
+
+fn is_tar(bytes: &[u8]) -> bool {
+    bytes.get(257..262) == Some(b"ustar")
+}
+
+/// A NUL padded header field as text.
+fn field(b: &[u8]) -> String {
+    let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..end]).into_owned()
+}
+
+fn octal(b: &[u8]) -> Option<u64> {
+    let s = field(b);
+    let s = s.trim();
+    if s.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(s, 8).ok()
+}
+
+/// Entries of a ustar archive, with GNU long names and pax paths.
+fn tar(bytes: &[u8]) -> Option<Vec<Entry>> {
+    let mut entries = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut at = 0;
+    while let Some(header) = bytes.get(at..at + 512) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = octal(&header[124..136])?;
+        let kind = header[156];
+        let start = at + 512;
+        let data = bytes.get(start..start.checked_add(size as usize)?)?;
+        at = start + (size as usize).div_ceil(512) * 512;
+        match kind {
+            b'L' => {
+                long_name = Some(field(data));
+                continue;
+            }
+            b'x' => {
+                // records of `<len> key=value\n`
+                let pax = String::from_utf8_lossy(data);
+                long_name = pax
+                    .lines()
+                    .find_map(|l| l.split_once(' ')?.1.strip_prefix("path="))
+                    .map(String::from)
+                    .or(long_name);
+                continue;
+            }
+            b'g' => continue,
+            _ => {}
+        }
+        let name = long_name.take().unwrap_or_else(|| {
+            let prefix = field(&header[345..500]);
+            let name = field(&header[..100]);
+            if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            }
+        });
+        entries.push(Entry {
+            name,
+            size,
+            dir: kind == b'5',
+            special: !matches!(kind, b'0' | 0 | b'5'),
+            method: Method::Stored,
+            data: data.to_vec(),
+        });
+    }
+    Some(entries)
+}
+
+/// `name` under `dir`, unless it would land outside it.
+fn target(dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = Path::new(name);
+    if !path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(anyhow!(format!(
+            "Failed to extract {}: the path leaves the target directory",
+            name
+        )));
+    }
+    Ok(dir.join(path))
+}
+
+/// Unpack the files of `archive` into `dir`, returning how many were written.
+pub fn extract(archive: &Archive, dir: &Path) -> Result<usize> {
+    let mut written = 0;
+    for entry in archive.entries.iter().filter(|e| !e.special) {
+        let path = target(dir, &entry.name)?;
+        if entry.dir {
+            std::fs::create_dir_all(&path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&path, entry.contents()?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+/// One line per entry with its unpacked size.
+pub fn print(archive: &Archive) {
+    let files: Vec<_> = archive.entries.iter().filter(|e| !e.dir).collect();
+    let total: u64 = files.iter().map(|e| e.size).sum();
+    let kind = match archive.kind {
+        Kind::Zip => "zip",
+        Kind::Tar => "tar",
+        Kind::TarGz => "tar.gz",
+    };
+    println!(
+        "{} {} archive, {} files, {} bytes unpacked",
+        "Archive:".bold(),
+        kind,
+        files.len(),
+        total
+    );
+    let width = total.to_string().len();
+    for entry in archive.entries.iter() {
+        let name = if entry.dir {
+            entry.name.blue().bold().to_string()
+        } else {
+            entry.name.clone()
+        };
+        println!("{:>width$}  {}", entry.size, name, width = width);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tar_header(name: &str, kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut h = vec![0u8; 512];
+        h[..name.len()].copy_from_slice(name.as_bytes());
+        h[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        h[156] = kind;
+        h[257..262].copy_from_slice(b"ustar");
+        h.extend_from_slice(data);
+        h.resize(512 + data.len().div_ceil(512) * 512, 0);
+        h
+    }
+
+    #[test]
+    fn tar_works() {
+        let mut bytes = tar_header("report/", b'5', b"");
+        bytes.extend(tar_header("report/a.csv", b'0', b"a,b\n1,2\n"));
+        bytes.extend(tar_header("././@LongLink", b'L', b"report/long.txt\0"));
+        bytes.extend(tar_header("report/lon", b'0', b"x"));
+        bytes.extend(vec![0; 1024]);
+        let archive = open(&bytes).unwrap();
+        assert_eq!(archive.kind, Kind::Tar);
+        let names: Vec<_> = archive
+            .entries
+            .iter()
+            .map(|e| (e.name.as_str(), e.size))
+            .collect();
+        assert_eq!(
+            names,
+            [("report/", 0), ("report/a.csv", 8), ("report/long.txt", 1)]
+        );
+
+        let dir = std::env::temp_dir().join(format!("httpie-archive-{}", std::process::id()));
+        assert_eq!(extract(&archive, &dir).unwrap(), 2);
+        assert_eq!(
+            std::fs::read(dir.join("report/a.csv")).unwrap(),
+            b"a,b\n1,2\n"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(target(&dir, "../etc/passwd").is_err());
+        assert!(target(&dir, "/etc/passwd").is_err());
+    }
+
+    #[test]
+    fn zip_works() {
+        // `printf hi > a.txt && zip -0 t.zip a.txt`, without extra fields
+        let mut zip = Vec::new();
+        zip.extend(b"PK\x03\x04\x0a\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        zip.extend(b"\x00\x00\x00\x00\x02\x00\x00\x00\x02\x00\x00\x00\x05\x00\x00\x00");
+        zip.extend(b"a.txthi");
+        let central = zip.len() as u32;
+        zip.extend(b"PK\x01\x02\x14\x00\x0a\x00\x00\x00\x00\x00\x00\x00\x00\x00");
+        zip.extend(b"\x00\x00\x00\x00\x02\x00\x00\x00\x02\x00\x00\x00\x05\x00\x00\x00");
+        zip.extend(b"\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00a.txt");
+        let size = zip.len() as u32 - central;
+        zip.extend(b"PK\x05\x06\x00\x00\x00\x00\x01\x00\x01\x00");
+        zip.extend(size.to_le_bytes());
+        zip.extend(central.to_le_bytes());
+        zip.extend(b"\x00\x00");
+        let archive = open(&zip).unwrap();
+        assert_eq!(archive.kind, Kind::Zip);
+        assert_eq!(archive.entries[0].name, "a.txt");
+        assert_eq!(archive.entries[0].contents().unwrap(), b"hi");
+    }
+}
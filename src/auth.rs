@@ -0,0 +1,720 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use md5::{Digest as _, Md5};
+use reqwest::{
+    header::{self, HeaderValue},
+    Client, Request, Response, StatusCode, Url,
+};
+use ring::{digest, hmac};
+
+use crate::{
+    compat::{self, AuthType},
+    limit,
+};
+
+/// A way of authenticating requests, chosen with `--auth-type`.
+pub trait Auth: fmt::Debug + Send + Sync {
+    /// An Authorization header that is the same for every request, for
+    /// schemes that have one; it is then sent by every subcommand.
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Sign or otherwise prepare `req` just before it is sent.
+    fn apply(&self, _req: &mut Request) -> Result<()> {
+        Ok(())
+    }
+
+    /// Prepare `req` to be sent again in answer to the challenge in `resp`,
+    /// a 401; false when the scheme does not retry.
+    fn challenge(&self, _req: &mut Request, _resp: &Response) -> Result<bool> {
+        Ok(false)
+    }
+}
+
+/// Basic and bearer credentials, the same header on every request.
+#[derive(Debug)]
+struct Static(String);
+
+impl Auth for Static {
+    fn header(&self) -> Option<String> {
+        Some(self.0.clone())
+    }
+}
+
+/// The scheme for `--auth` and `--auth-type`, asking for a password only
+/// when `prompt` allows it. `--aws-profile` signs with aws4, so another
+/// type along with it is an error.
+pub fn from_args(
+    auth: Option<&str>,
+    auth_type: Option<AuthType>,
+    aws_profile: Option<&str>,
+    show_canonical: bool,
+    prompt: bool,
+) -> Result<Option<Arc<dyn Auth>>> {
+    let auth_type = match (auth_type, aws_profile) {
+        (Some(AuthType::Aws4) | None, Some(_)) => AuthType::Aws4,
+        (Some(other), Some(_)) => {
+            let name = other.to_possible_value().map(|v| v.get_name().to_string());
+            return Err(anyhow!(format!(
+                "Failed to set up auth: --aws-profile signs with aws4, not --auth-type {}",
+                name.unwrap_or_default()
+            )));
+        }
+        (auth_type, None) => auth_type.unwrap_or_default(),
+    };
+    let scheme: Arc<dyn Auth> = match (auth_type, auth) {
+        (AuthType::Aws4, _) => Arc::new(Aws4::load(auth, aws_profile, show_canonical)?),
+        (_, None) => return Ok(None),
+        (AuthType::Digest, Some(auth)) => {
+            let (user, password) = compat::credentials(auth, prompt)?;
+            Arc::new(Digest::new(user, password))
+        }
+        (AuthType::Basic | AuthType::Bearer, Some(auth)) => {
+            Arc::new(Static(compat::authorization(auth, auth_type, prompt)?))
+        }
+    };
+    Ok(Some(scheme))
+}
+
+/// Send `req` when the host's shared rate limit allows.
+async fn execute(client: &Client, req: Request) -> Result<Response> {
+    limit::acquire(req.url()).await?;
+    Ok(client.execute(req).await?)
+}
+
+/// Send `req`, and once more if the scheme answers a 401 challenge.
+pub async fn send(client: &Client, req: Request, auth: Option<&dyn Auth>) -> Result<Response> {
+    let Some(auth) = auth else {
+        return execute(client, req).await;
+    };
+    let retry = req.try_clone();
+    let resp = execute(client, req).await?;
+    if resp.status() != StatusCode::UNAUTHORIZED {
+        return Ok(resp);
+    }
+    // bodies streamed from files cannot be sent twice
+    let Some(mut retry) = retry else {
+        return Ok(resp);
+    };
+    if !auth.challenge(&mut retry, &resp)? {
+        return Ok(resp);
+    }
+    execute(client, retry).await
+}
+
+/// Prepare `req` with the scheme and [`send`] it, for requests that are not
+/// printed on the way.
+pub async fn sign_and_send(
+    client: &Client,
+    mut req: Request,
+    auth: Option<&dyn Auth>,
+) -> Result<Response> {
+    if let Some(auth) = auth {
+        auth.apply(&mut req)?;
+    }
+    send(client, req, auth).await
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut s, b| {
+        write!(s, "{:02x}", b).ok();
+        s
+    })
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, bytes).as_ref())
+}
+
+// digest
+
+/// The parameters of a `WWW-Authenticate: Digest ...` challenge.
+#[derive(Debug, Clone, PartialEq)]
+struct Challenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    qop: Option<String>,
+}
+
+/// `key=value` and `key="quoted, value"` pairs, keys lowercased.
+fn auth_params(s: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    let mut rest = s.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key
+            .trim()
+            .trim_start_matches(',')
+            .trim()
+            .to_ascii_lowercase();
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => {
+                let end = after.find(',').unwrap_or(after.len());
+                (after[..end].trim().to_string(), &after[end..])
+            }
+        };
+        params.insert(key, value);
+        rest = next.trim_start().trim_start_matches(',');
+    }
+    params
+}
+
+impl Challenge {
+    fn parse(header: &str) -> Option<Self> {
+        let (scheme, rest) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let mut params = auth_params(rest);
+        let qop = params.remove("qop").map(|q| {
+            // auth-int is only used when it is all the server takes
+            let offered: Vec<_> = q.split(',').map(str::trim).collect();
+            if offered.contains(&"auth") {
+                "auth"
+            } else {
+                offered[0]
+            }
+            .to_string()
+        });
+        Some(Self {
+            realm: params.remove("realm").unwrap_or_default(),
+            nonce: params.remove("nonce")?,
+            opaque: params.remove("opaque"),
+            algorithm: params.remove("algorithm").unwrap_or_else(|| "MD5".into()),
+            qop,
+        })
+    }
+
+    fn hash(&self, s: &str) -> Result<String> {
+        let algorithm = self.algorithm.to_ascii_uppercase();
+        match algorithm.trim_end_matches("-SESS") {
+            "MD5" => Ok(hex(&Md5::digest(s.as_bytes()))),
+            "SHA-256" => Ok(sha256(s.as_bytes())),
+            _ => Err(anyhow!(format!(
+                "Failed to answer the digest challenge: unsupported algorithm {}",
+                self.algorithm
+            ))),
+        }
+    }
+
+    /// The Authorization header for a request, per RFC 7616.
+    #[allow(clippy::too_many_arguments)]
+    fn authorization(
+        &self,
+        user: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        body: &[u8],
+        nc: u32,
+        cnonce: &str,
+    ) -> Result<String> {
+        let mut ha1 = self.hash(&format!("{}:{}:{}", user, self.realm, password))?;
+        if self.algorithm.to_ascii_uppercase().ends_with("-SESS") {
+            ha1 = self.hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce))?;
+        }
+        let ha2 = match self.qop.as_deref() {
+            Some("auth-int") => {
+                let body = self.hash(&String::from_utf8_lossy(body))?;
+                self.hash(&format!("{}:{}:{}", method, uri, body))?
+            }
+            _ => self.hash(&format!("{}:{}", method, uri))?,
+        };
+        let nc = format!("{:08x}", nc);
+        let response = match &self.qop {
+            Some(qop) => self.hash(&format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, self.nonce, nc, cnonce, qop, ha2
+            ))?,
+            None => self.hash(&format!("{}:{}:{}", ha1, self.nonce, ha2))?,
+        };
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            user, self.realm, self.nonce, uri, self.algorithm, response
+        );
+        if let Some(qop) = &self.qop {
+            write!(header, ", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce)?;
+        }
+        if let Some(opaque) = &self.opaque {
+            write!(header, ", opaque=\"{}\"", opaque)?;
+        }
+        Ok(header)
+    }
+}
+
+/// HTTP Digest (RFC 7616): the first request draws a challenge that the
+/// retry answers; later requests reuse the nonce.
+#[derive(Debug)]
+struct Digest {
+    user: String,
+    password: String,
+    challenge: Mutex<Option<Challenge>>,
+    nc: AtomicU32,
+}
+
+impl Digest {
+    fn new(user: String, password: String) -> Self {
+        Self {
+            user,
+            password,
+            challenge: Mutex::new(None),
+            nc: AtomicU32::new(1),
+        }
+    }
+
+    fn sign(&self, req: &mut Request, challenge: &Challenge) -> Result<()> {
+        let url = req.url();
+        let uri = match url.query() {
+            Some(q) => format!("{}?{}", url.path(), q),
+            None => url.path().to_string(),
+        };
+        let body = req.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+        let mut cnonce = [0; 8];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut cnonce)
+            .map_err(|_| anyhow!("Failed to generate a client nonce"))?;
+        let header = challenge.authorization(
+            &self.user,
+            &self.password,
+            req.method().as_str(),
+            &uri,
+            body,
+            self.nc.fetch_add(1, Ordering::Relaxed),
+            &hex(&cnonce),
+        )?;
+        req.headers_mut()
+            .insert(header::AUTHORIZATION, HeaderValue::from_str(&header)?);
+        Ok(())
+    }
+}
+
+impl Auth for Digest {
+    fn apply(&self, req: &mut Request) -> Result<()> {
+        let known = self.challenge.lock().unwrap().clone();
+        match known {
+            Some(challenge) => self.sign(req, &challenge),
+            None => Ok(()),
+        }
+    }
+
+    fn challenge(&self, req: &mut Request, resp: &Response) -> Result<bool> {
+        // several challenges may be offered; prefer SHA-256
+        let mut challenges: Vec<_> = resp
+            .headers()
+            .get_all(header::WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| Challenge::parse(v.to_str().ok()?))
+            .collect();
+        challenges.sort_by_key(|c| !c.algorithm.to_ascii_uppercase().starts_with("SHA-256"));
+        let Some(challenge) = challenges.into_iter().next() else {
+            return Ok(false);
+        };
+        self.nc.store(1, Ordering::Relaxed);
+        self.sign(req, &challenge)?;
+        *self.challenge.lock().unwrap() = Some(challenge);
+        Ok(true)
+    }
+}
+
+// aws4
+
+/// `[section]` of an INI file like `~/.aws/credentials`.
+fn ini_section(text: &str, section: &str) -> Option<HashMap<String, String>> {
+    let mut found = None;
+    for line in text.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            if found.is_some() {
+                break;
+            }
+            if line[1..line.len() - 1].trim() == section {
+                found = Some(HashMap::new());
+            }
+        } else if let (Some(values), Some((k, v))) = (&mut found, line.split_once('=')) {
+            values.insert(k.trim().to_string(), v.trim().to_string());
+        }
+    }
+    found
+}
+
+fn aws_file(name: &str, env: &str) -> PathBuf {
+    std::env::var_os(env).map(PathBuf::from).unwrap_or_else(|| {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".aws")
+            .join(name)
+    })
+}
+
+/// `us-east-1` and `s3` from hosts like `s3.us-east-1.amazonaws.com` or
+/// `bucket.s3.eu-west-1.amazonaws.com`.
+fn region_and_service(host: &str) -> (Option<String>, Option<String>) {
+    let Some(prefix) = host.strip_suffix(".amazonaws.com") else {
+        return (None, None);
+    };
+    let labels: Vec<_> = prefix.split('.').collect();
+    let service = labels
+        .iter()
+        .position(|l| l.starts_with("s3"))
+        .map_or(labels[0], |i| labels[i]);
+    let service = if service.starts_with("s3") {
+        "s3"
+    } else {
+        service
+    };
+    let region = labels
+        .iter()
+        .rev()
+        .find(|l| l.contains('-') && l.chars().any(|c| c.is_ascii_digit()));
+    (region.map(|r| r.to_string()), Some(service.to_string()))
+}
+
+/// AWS Signature Version 4, for S3 and compatible APIs such as MinIO.
+#[derive(Debug)]
+struct Aws4 {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    region: Option<String>,
+    show_canonical: bool,
+}
+
+/// What is signed and the resulting header.
+#[derive(Debug)]
+struct Signed {
+    canonical_request: String,
+    string_to_sign: String,
+    authorization: String,
+}
+
+/// Percent-encode all but the characters SigV4 leaves unreserved.
+fn uri_encode(s: &str) -> String {
+    s.bytes().fold(String::new(), |mut out, b| {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            write!(out, "%{:02X}", b).ok();
+        }
+        out
+    })
+}
+
+impl Aws4 {
+    /// Credentials from `--auth KEY:SECRET`, else the environment when no
+    /// profile is named, else the profile in `~/.aws/credentials`.
+    fn load(auth: Option<&str>, profile: Option<&str>, show_canonical: bool) -> Result<Self> {
+        let env = |name| std::env::var(name).ok().filter(|v: &String| !v.is_empty());
+        let name = profile
+            .map(String::from)
+            .or_else(|| env("AWS_PROFILE"))
+            .unwrap_or_else(|| "default".into());
+        let config_path = aws_file("config", "AWS_CONFIG_FILE");
+        let config = std::fs::read_to_string(&config_path).unwrap_or_default();
+        let section = if name == "default" {
+            name.clone()
+        } else {
+            format!("profile {}", name)
+        };
+        let region = env("AWS_REGION")
+            .or_else(|| env("AWS_DEFAULT_REGION"))
+            .or_else(|| ini_section(&config, &section)?.remove("region"));
+        if let Some((key, secret)) = auth.and_then(|a| a.split_once(':')) {
+            return Ok(Self {
+                access_key: key.into(),
+                secret_key: secret.into(),
+                session_token: None,
+                region,
+                show_canonical,
+            });
+        }
+        if let (None, Some(key), Some(secret)) = (
+            profile,
+            env("AWS_ACCESS_KEY_ID"),
+            env("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Ok(Self {
+                access_key: key,
+                secret_key: secret,
+                session_token: env("AWS_SESSION_TOKEN"),
+                region,
+                show_canonical,
+            });
+        }
+        let path = aws_file("credentials", "AWS_SHARED_CREDENTIALS_FILE");
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read AWS credentials {}", path.display()))?;
+        let mut values = ini_section(&text, &name).ok_or_else(|| {
+            anyhow!(format!(
+                "Failed to find AWS profile {} in {}",
+                name,
+                path.display()
+            ))
+        })?;
+        let mut take = |key: &str| {
+            values
+                .remove(key)
+                .ok_or_else(|| anyhow!(format!("Failed to load AWS profile {}: no {}", name, key)))
+        };
+        Ok(Self {
+            access_key: take("aws_access_key_id")?,
+            secret_key: take("aws_secret_access_key")?,
+            session_token: take("aws_session_token").ok(),
+            region,
+            show_canonical,
+        })
+    }
+
+    /// Sign a request to `url`; `headers` are the lowercased headers to
+    /// sign, including host and x-amz-date.
+    #[allow(clippy::too_many_arguments)]
+    fn sign(
+        &self,
+        method: &str,
+        url: &Url,
+        headers: &BTreeMap<String, String>,
+        payload_hash: &str,
+        amz_date: &str,
+        region: &str,
+        service: &str,
+    ) -> Signed {
+        let mut query: Vec<_> = url
+            .query_pairs()
+            .map(|(k, v)| (uri_encode(&k), uri_encode(&v)))
+            .collect();
+        query.sort();
+        let query: Vec<_> = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| {
+                format!(
+                    "{}:{}\n",
+                    k,
+                    v.split_whitespace().collect::<Vec<_>>().join(" ")
+                )
+            })
+            .collect();
+        let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            url.path(),
+            query.join("&"),
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            sha256(canonical_request.as_bytes())
+        );
+        let mac = |key: &[u8], data: &str| {
+            hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data.as_bytes())
+        };
+        let mut key = mac(format!("AWS4{}", self.secret_key).as_bytes(), date);
+        for part in [region, service, "aws4_request"] {
+            key = mac(key.as_ref(), part);
+        }
+        let signature = hex(mac(key.as_ref(), &string_to_sign).as_ref());
+        Signed {
+            authorization: format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key, scope, signed_headers, signature
+            ),
+            canonical_request,
+            string_to_sign,
+        }
+    }
+}
+
+/// `t` as SigV4 wants it, e.g. `20150830T123600Z`.
+fn amz_date(t: SystemTime) -> String {
+    let iso = crate::har::iso8601(t);
+    iso[..19]
+        .chars()
+        .filter(|c| !matches!(c, '-' | ':'))
+        .collect::<String>()
+        + "Z"
+}
+
+impl Auth for Aws4 {
+    fn apply(&self, req: &mut Request) -> Result<()> {
+        let host = crate::conn::authority(req.url());
+        let (host_region, host_service) =
+            region_and_service(req.url().host_str().unwrap_or_default());
+        let region = self
+            .region
+            .clone()
+            .or(host_region)
+            .unwrap_or_else(|| "us-east-1".into());
+        let service = host_service.unwrap_or_else(|| "s3".into());
+        // streamed bodies cannot be hashed up front, which S3 allows
+        let payload_hash = match req.body() {
+            Some(body) => body.as_bytes().map_or("UNSIGNED-PAYLOAD".into(), sha256),
+            None => sha256(b""),
+        };
+        let amz_date = amz_date(SystemTime::now());
+        let headers = req.headers_mut();
+        headers.insert("x-amz-date", amz_date.parse()?);
+        headers.insert("x-amz-content-sha256", payload_hash.parse()?);
+        if let Some(token) = &self.session_token {
+            headers.insert("x-amz-security-token", token.parse()?);
+        }
+        let mut signed: BTreeMap<String, String> = req
+            .headers()
+            .iter()
+            .filter(|(name, _)| *name != header::AUTHORIZATION)
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).trim().to_string(),
+                )
+            })
+            .collect();
+        signed.insert("host".into(), host);
+        let method = req.method().to_string();
+        let signed = self.sign(
+            &method,
+            req.url(),
+            &signed,
+            &payload_hash,
+            &amz_date,
+            &region,
+            &service,
+        );
+        if self.show_canonical {
+            eprintln!(
+                "{}\n{}\n",
+                "Canonical request:".bold(),
+                signed.canonical_request
+            );
+            eprintln!("{}\n{}\n", "String to sign:".bold(), signed.string_to_sign);
+            eprintln!("{} {}\n", "Authorization:".bold(), signed.authorization);
+        }
+        req.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&signed.authorization)?,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_works() {
+        // the example of RFC 7616, section 3.9.1
+        let header = "Digest realm=\"http-auth@example.org\", qop=\"auth, auth-int\", \
+            algorithm=SHA-256, nonce=\"7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v\", \
+            opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\"";
+        let mut challenge = Challenge::parse(header).unwrap();
+        assert_eq!(challenge.qop.as_deref(), Some("auth"));
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+        let answer = |c: &Challenge| {
+            c.authorization(
+                "Mufasa",
+                "Circle of Life",
+                "GET",
+                "/dir/index.html",
+                b"",
+                1,
+                cnonce,
+            )
+            .unwrap()
+        };
+        assert!(answer(&challenge).contains(
+            "response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""
+        ));
+        challenge.algorithm = "MD5".into();
+        let md5 = answer(&challenge);
+        assert!(md5.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+        assert!(md5.contains("nc=00000001"));
+        assert!(md5.ends_with("opaque=\"FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS\""));
+        assert!(Challenge::parse("Basic realm=\"x\"").is_none());
+    }
+
+    #[test]
+    fn aws4_works() {
+        // get-vanilla from the AWS SigV4 test suite
+        let aws = Aws4 {
+            access_key: "AKIDEXAMPLE".into(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+            region: None,
+            show_canonical: false,
+        };
+        let headers = BTreeMap::from([
+            ("host".to_string(), "example.amazonaws.com".to_string()),
+            ("x-amz-date".to_string(), "20150830T123600Z".to_string()),
+        ]);
+        let url = "https://example.amazonaws.com/".parse().unwrap();
+        let signed = aws.sign(
+            "GET",
+            &url,
+            &headers,
+            &sha256(b""),
+            "20150830T123600Z",
+            "us-east-1",
+            "service",
+        );
+        assert_eq!(
+            signed.authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(amz_date(SystemTime::UNIX_EPOCH), "19700101T000000Z");
+        assert_eq!(
+            region_and_service("bucket.s3.eu-west-1.amazonaws.com"),
+            (Some("eu-west-1".into()), Some("s3".into()))
+        );
+        assert_eq!(region_and_service("localhost"), (None, None));
+        let err = from_args(
+            Some("u:p"),
+            Some(AuthType::Digest),
+            Some("dev"),
+            false,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to set up auth: --aws-profile signs with aws4, not --auth-type digest"
+        );
+    }
+}
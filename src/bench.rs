@@ -0,0 +1,295 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use reqwest::{Client, Request};
+use tokio::{sync::Notify, task::JoinSet};
+
+use crate::{
+    auth::{self, Auth},
+    limit::Pacer,
+    stats::{self, format_duration},
+};
+
+/// Outcome of a benchmark run.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub latencies: Vec<Duration>,
+    pub statuses: BTreeMap<u16, usize>,
+    pub errors: usize,
+    pub elapsed: Duration,
+    /// The most requests in flight and where it settled, when adaptive
+    pub adaptive: Option<(usize, usize)>,
+}
+
+/// A response this many times slower than the typical one means the server
+/// is queueing.
+const SLOW: u32 = 2;
+/// Below this much slower, a response counts as fast whatever the ratio, so
+/// that sub-millisecond jitter on a local server is not taken as queueing.
+const SLACK: Duration = Duration::from_millis(5);
+/// What the limit is multiplied by on a sign of trouble.
+const BACKOFF: f64 = 0.5;
+/// Weight of each healthy response in the typical latency.
+const SMOOTHING: f64 = 0.1;
+
+#[derive(Debug)]
+struct Window {
+    limit: f64,
+    in_flight: usize,
+    /// Moving average of the healthy responses' latency
+    typical: Option<Duration>,
+    /// Doubling until the first sign of trouble, like TCP slow start
+    slow_start: bool,
+    /// Responses to requests sent before this were already backed off for
+    backed_off: Option<Instant>,
+    peak: usize,
+}
+
+/// Requests allowed in flight, grown by one per round trip while responses
+/// come back fast and successful, and halved on errors, 429 or 5xx
+/// statuses and slow responses (AIMD), between 1 and `max`.
+#[derive(Debug)]
+pub struct Aimd {
+    max: usize,
+    window: Mutex<Window>,
+    released: Notify,
+}
+
+impl Aimd {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            window: Mutex::new(Window {
+                limit: 1.0,
+                in_flight: 0,
+                typical: None,
+                slow_start: true,
+                backed_off: None,
+                peak: 1,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Wait for room to send a request.
+    pub async fn acquire(&self) {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut w = self.window.lock().unwrap();
+                if w.in_flight < w.limit as usize {
+                    w.in_flight += 1;
+                    return;
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Account for the response to a request sent at `sent`: `ok` when it
+    /// succeeded with a status other than 429 or 5xx.
+    pub fn release(&self, sent: Instant, ok: bool, latency: Duration) {
+        let mut w = self.window.lock().unwrap();
+        w.in_flight -= 1;
+        let typical = *w.typical.get_or_insert(latency);
+        let slow = latency > typical * SLOW && latency > typical + SLACK;
+        // slow ones too, or a fast first response would stay the norm
+        if ok {
+            w.typical = Some(typical.mul_f64(1.0 - SMOOTHING) + latency.mul_f64(SMOOTHING));
+        }
+        if !ok || slow {
+            // once per round trip: the others in flight saw the same load
+            if w.backed_off.is_none_or(|at| sent > at) {
+                w.limit = (w.limit * BACKOFF).max(1.0);
+                w.slow_start = false;
+                w.backed_off = Some(Instant::now());
+            }
+        } else if w.slow_start {
+            w.limit += 1.0;
+        } else {
+            w.limit += 1.0 / w.limit;
+        }
+        w.limit = w.limit.min(self.max as f64);
+        w.peak = w.peak.max(w.limit as usize);
+        drop(w);
+        self.released.notify_waiters();
+    }
+
+    /// The highest limit reached and the current one.
+    pub fn limits(&self) -> (usize, usize) {
+        let w = self.window.lock().unwrap();
+        (w.peak, w.limit as usize)
+    }
+}
+
+/// Send `request` `total` times from `concurrency` workers, paced by
+/// `pacer` when given, and with only as many in flight as `adaptive` allows.
+pub async fn run(
+    client: Client,
+    auth: Option<Arc<dyn Auth>>,
+    request: Request,
+    total: usize,
+    concurrency: usize,
+    pacer: Option<Arc<Pacer>>,
+    adaptive: Option<Arc<Aimd>>,
+) -> Result<Report> {
+    if request.try_clone().is_none() {
+        return Err(anyhow!("Streaming request bodies cannot be repeated"));
+    }
+    let request = Arc::new(request);
+    let next = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let mut workers = JoinSet::new();
+    for _ in 0..concurrency.clamp(1, total.max(1)) {
+        let (client, auth, request, next) =
+            (client.clone(), auth.clone(), request.clone(), next.clone());
+        let (pacer, adaptive) = (pacer.clone(), adaptive.clone());
+        workers.spawn(async move {
+            let mut report = Report::default();
+            while next.fetch_add(1, Ordering::Relaxed) < total {
+                let req = request.try_clone().unwrap();
+                if let Some(adaptive) = &adaptive {
+                    adaptive.acquire().await;
+                }
+                if let Some(pacer) = &pacer {
+                    pacer.wait().await;
+                }
+                let sent = Instant::now();
+                let healthy = match auth::sign_and_send(&client, req, auth.as_deref()).await {
+                    Ok(resp) => {
+                        let status = resp.status().as_u16();
+                        // latency covers the whole body, not just the headers
+                        let ok = resp.bytes().await.is_ok();
+                        report.latencies.push(sent.elapsed());
+                        if ok {
+                            *report.statuses.entry(status).or_default() += 1;
+                        } else {
+                            report.errors += 1;
+                        }
+                        ok && status != 429 && status < 500
+                    }
+                    Err(_) => {
+                        report.errors += 1;
+                        false
+                    }
+                };
+                if let Some(adaptive) = &adaptive {
+                    adaptive.release(sent, healthy, sent.elapsed());
+                }
+            }
+            report
+        });
+    }
+
+    let mut report = Report::default();
+    while let Some(part) = workers.join_next().await {
+        let part = part?;
+        report.latencies.extend(part.latencies);
+        report.errors += part.errors;
+        for (status, n) in part.statuses {
+            *report.statuses.entry(status).or_default() += n;
+        }
+    }
+    report.elapsed = start.elapsed();
+    report.adaptive = adaptive.map(|a| a.limits());
+    Ok(report)
+}
+
+pub fn print_report(report: &Report, concurrency: usize) {
+    let total = report.statuses.values().sum::<usize>() + report.errors;
+    println!("{:<14}{} ({} errors)", "Requests:", total, report.errors);
+    match report.adaptive {
+        Some((peak, last)) => println!(
+            "{:<14}adaptive up to {}, peaked at {}, settled at {}",
+            "Concurrency:", concurrency, peak, last
+        ),
+        None => println!("{:<14}{}", "Concurrency:", concurrency),
+    }
+    println!("{:<14}{}", "Total time:", format_duration(report.elapsed));
+    println!(
+        "{:<14}{:.1} req/s",
+        "Throughput:",
+        total as f64 / report.elapsed.as_secs_f64()
+    );
+    if let Some(s) = stats::summarize(&report.latencies) {
+        println!(
+            "{:<14}min {}  mean {}  p50 {}  p95 {}  p99 {}  max {}",
+            "Latency:",
+            format_duration(s.min),
+            format_duration(s.mean),
+            format_duration(s.p50),
+            format_duration(s.p95),
+            format_duration(s.p99),
+            format_duration(s.max)
+        );
+    }
+    println!("Status codes:");
+    for (status, n) in report.statuses.iter() {
+        let code = match status {
+            200..=299 => status.to_string().green(),
+            300..=399 => status.to_string().yellow(),
+            _ => status.to_string().red(),
+        };
+        println!("  {}  {}", code, n);
+    }
+    if report.errors > 0 {
+        println!("  {}  {}", "ERR".red(), report.errors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn aimd_works() {
+        let aimd = Aimd::new(8);
+        let fast = Duration::from_millis(10);
+        // slow start doubles each round trip
+        for round in [1, 2, 4] {
+            assert_eq!(aimd.limits().1, round);
+            let sent = Instant::now();
+            for _ in 0..round {
+                aimd.acquire().await;
+            }
+            for _ in 0..round {
+                aimd.release(sent, true, fast);
+            }
+        }
+        assert_eq!(aimd.limits(), (8, 8));
+
+        // a burst of 429s halves the limit once, not once per response
+        let sent = Instant::now();
+        for _ in 0..8 {
+            aimd.acquire().await;
+        }
+        for _ in 0..8 {
+            aimd.release(sent, false, fast);
+        }
+        assert_eq!(aimd.limits(), (8, 4));
+
+        // then it grows by about one per round trip
+        for _ in 0..4 {
+            aimd.acquire().await;
+            aimd.release(Instant::now(), true, fast);
+        }
+        assert_eq!(aimd.limits().1, 4);
+        aimd.acquire().await;
+        aimd.release(Instant::now(), true, fast);
+        assert_eq!(aimd.limits().1, 5);
+
+        // a response far slower than the typical one is trouble too
+        aimd.acquire().await;
+        aimd.release(Instant::now(), true, fast * 10);
+        assert_eq!(aimd.limits().1, 2);
+    }
+}
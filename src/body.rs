@@ -0,0 +1,130 @@
+use std::{borrow::Cow, io::Read, sync::OnceLock};
+
+use anyhow::{Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::MultiGzDecoder;
+use mime::Mime;
+
+/// Whether `bytes` start with the gzip magic number.
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0x1f, 0x8b])
+}
+
+pub fn gunzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    MultiGzDecoder::new(bytes)
+        .read_to_end(&mut out)
+        .context("Failed to decompress gzip body")?;
+    Ok(out)
+}
+
+/// Set by `--response-charset`, for servers that give the wrong charset or
+/// none at all.
+static CHARSET: OnceLock<&'static Encoding> = OnceLock::new();
+
+pub fn override_charset(encoding: &'static Encoding) {
+    CHARSET.set(encoding).ok();
+}
+
+/// The charset given with `--response-charset`.
+pub fn charset_override() -> Option<&'static Encoding> {
+    CHARSET.get().copied()
+}
+
+/// Decode `bytes` with `--response-charset`, else the charset from the
+/// Content-Type, UTF-8 by default. Valid UTF-8 is borrowed, not copied.
+pub fn decode_text<'a>(bytes: &'a [u8], m: Option<&Mime>) -> Cow<'a, str> {
+    let encoding = charset_override()
+        .or_else(|| {
+            m.and_then(|m| m.get_param(mime::CHARSET))
+                .and_then(|c| Encoding::for_label(c.as_str().as_bytes()))
+        })
+        .unwrap_or(UTF_8);
+    encoding.decode(bytes).0
+}
+
+/// Cut `s` down to at most `max` bytes, on a character boundary, returning
+/// how many bytes were cut.
+pub fn truncate(s: &mut Cow<str>, max: usize) -> usize {
+    if s.len() <= max {
+        return 0;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let cut = s.len() - end;
+    s.to_mut().truncate(end);
+    cut
+}
+
+/// Classic 16 bytes per row hex dump with offsets and printable ASCII.
+pub fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<_> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out += &format!("{:08x}  {:<47}  |{}|\n", row * 16, hex.join(" "), ascii);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    #[test]
+    fn gunzip_works() {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(b"hello").unwrap();
+        let gz = enc.finish().unwrap();
+        assert!(is_gzip(&gz));
+        assert!(!is_gzip(b"hello"));
+        assert_eq!(gunzip(&gz).unwrap(), b"hello");
+        assert!(gunzip(&[0x1f, 0x8b, 0]).is_err());
+    }
+
+    #[test]
+    fn hexdump_works() {
+        assert_eq!(hexdump(b""), "");
+        assert_eq!(
+            hexdump(b"hi\x00"),
+            "00000000  68 69 00                                         |hi.|
+"
+        );
+        assert_eq!(hexdump(&[0; 17]).lines().count(), 2);
+    }
+
+    #[test]
+    fn truncate_works() {
+        let mut s = Cow::Borrowed("héllo");
+        assert_eq!(truncate(&mut s, 10), 0);
+        assert!(matches!(s, Cow::Borrowed(_)));
+        // the second byte is inside é
+        assert_eq!(truncate(&mut s, 2), 5);
+        assert_eq!(s, "h");
+    }
+
+    #[test]
+    fn decode_text_works() {
+        let latin1: Mime = "text/plain; charset=ISO-8859-1".parse().unwrap();
+        assert_eq!(decode_text(b"caf\xe9", Some(&latin1)), "café");
+        assert_eq!(decode_text("café".as_bytes(), None), "café");
+        assert!(matches!(decode_text(b"{}", None), Cow::Borrowed(_)));
+        let sjis: Mime = "text/plain; charset=Shift_JIS".parse().unwrap();
+        assert_eq!(decode_text(b"\x93\xfa\x96\x7b", Some(&sjis)), "日本");
+        let gb2312: Mime = "text/html; charset=GB2312".parse().unwrap();
+        assert_eq!(decode_text(b"\xd6\xd0\xce\xc4", Some(&gb2312)), "中文");
+    }
+}
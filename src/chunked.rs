@@ -0,0 +1,351 @@
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use md5::{Digest, Md5};
+use reqwest::{header, Client, Response, Url};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::Semaphore,
+    task::JoinSet,
+};
+
+use crate::{
+    auth::{self, Auth},
+    upload::{file_size, progress_bar},
+};
+
+/// Progress of a multipart upload, persisted after every finished part so an
+/// interrupted upload can pick up where it stopped.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct UploadState {
+    url: String,
+    upload_id: String,
+    file_len: u64,
+    /// modification time of the file, in nanoseconds since the epoch
+    #[serde(default)]
+    modified: u64,
+    part_size: u64,
+    /// part number -> ETag returned by the server
+    parts: BTreeMap<u32, String>,
+}
+
+impl UploadState {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Ok(Some(serde_json::from_str(&s).with_context(|| {
+                format!("Failed to parse upload state {}", path.display())
+            })?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write upload state {}", path.display()))
+    }
+
+    /// A saved state is only reusable for the same target, file and layout;
+    /// a file rewritten since, even to the same size, starts over.
+    fn matches(&self, url: &Url, file_len: u64, modified: u64, part_size: u64) -> bool {
+        self.url == url.as_str()
+            && self.file_len == file_len
+            && self.modified == modified
+            && self.part_size == part_size
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct PartRange {
+    number: u32,
+    offset: u64,
+    len: u64,
+}
+
+/// Split `file_len` bytes into parts of `part_size`, numbered from 1.
+fn plan_parts(file_len: u64, part_size: u64) -> Vec<PartRange> {
+    let count = file_len.div_ceil(part_size).max(1);
+    (0..count)
+        .map(|i| {
+            let offset = i * part_size;
+            PartRange {
+                number: i as u32 + 1,
+                offset,
+                len: part_size.min(file_len - offset),
+            }
+        })
+        .collect()
+}
+
+async fn file_modified(file: &str) -> Result<u64> {
+    let modified = tokio::fs::metadata(file)
+        .await
+        .and_then(|meta| meta.modified())
+        .with_context(|| format!("Failed to read {}", file))?;
+    Ok(modified.duration_since(UNIX_EPOCH)?.as_nanos() as u64)
+}
+
+/// Default state file location: next to the uploaded file.
+pub fn default_state_path(file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.upload-state", file))
+}
+
+/// Upload `file` to `url` with the S3 multipart protocol, sending up to
+/// `parallel` parts at once. Returns the response of the completion request.
+pub async fn upload(
+    client: Client,
+    auth: Option<Arc<dyn Auth>>,
+    url: Url,
+    file: &str,
+    part_size: u64,
+    parallel: usize,
+    state_path: &Path,
+) -> Result<Response> {
+    if part_size == 0 {
+        return Err(anyhow!("Part size must be greater than zero"));
+    }
+    let file_len = file_size(file).await?;
+    let modified = file_modified(file).await?;
+    let mut state = match UploadState::load(state_path)? {
+        Some(state) if state.matches(&url, file_len, modified, part_size) => {
+            eprintln!(
+                "Resuming upload {} ({} parts done)",
+                state.upload_id,
+                state.parts.len()
+            );
+            state
+        }
+        _ => UploadState {
+            url: url.to_string(),
+            upload_id: initiate(&client, auth.as_deref(), &url).await?,
+            file_len,
+            modified,
+            part_size,
+            parts: BTreeMap::new(),
+        },
+    };
+    state.save(state_path)?;
+
+    let (done, pending): (Vec<_>, Vec<_>) = plan_parts(file_len, part_size)
+        .into_iter()
+        .partition(|p| state.parts.contains_key(&p.number));
+    let pb = progress_bar(file_len);
+    pb.inc(done.iter().map(|p| p.len).sum());
+
+    let permits = Arc::new(Semaphore::new(parallel));
+    let mut tasks = JoinSet::new();
+    for part in pending {
+        let permit = permits.clone().acquire_owned().await?;
+        let (client, url, file, pb) = (client.clone(), url.clone(), file.to_string(), pb.clone());
+        let (auth, upload_id) = (auth.clone(), state.upload_id.clone());
+        tasks.spawn(async move {
+            let _permit = permit;
+            let etag = upload_part(&client, auth.as_deref(), &url, &upload_id, &file, part).await?;
+            pb.inc(part.len);
+            Ok::<_, anyhow::Error>((part.number, etag))
+        });
+        // record parts as they finish so a crash loses as little work as possible
+        while let Some(res) = tasks.try_join_next() {
+            let (number, etag) = res??;
+            state.parts.insert(number, etag);
+            state.save(state_path)?;
+        }
+    }
+    while let Some(res) = tasks.join_next().await {
+        let (number, etag) = res??;
+        state.parts.insert(number, etag);
+        state.save(state_path)?;
+    }
+    pb.finish();
+
+    let resp = complete(&client, auth.as_deref(), &url, &state).await?;
+    if resp.status().is_success() {
+        std::fs::remove_file(state_path)?;
+    }
+    Ok(resp)
+}
+
+async fn initiate(client: &Client, auth: Option<&dyn Auth>, url: &Url) -> Result<String> {
+    let mut url = url.clone();
+    url.query_pairs_mut().append_key_only("uploads");
+    let resp = auth::sign_and_send(client, client.post(url).build()?, auth)
+        .await?
+        .error_for_status()?;
+    let body = resp.text().await?;
+    xml_text(&body, "UploadId")
+        .map(|s| s.to_string())
+        .ok_or_else(|| anyhow!("Failed to find UploadId in response: {}", body))
+}
+
+async fn upload_part(
+    client: &Client,
+    auth: Option<&dyn Auth>,
+    url: &Url,
+    upload_id: &str,
+    file: &str,
+    part: PartRange,
+) -> Result<String> {
+    let mut f = File::open(file)
+        .await
+        .with_context(|| format!("Failed to open {}", file))?;
+    f.seek(SeekFrom::Start(part.offset)).await?;
+    let mut buf = vec![0; part.len as usize];
+    f.read_exact(&mut buf).await?;
+    let digest = Md5::digest(&buf);
+
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("partNumber", &part.number.to_string())
+        .append_pair("uploadId", upload_id);
+    let req = client
+        .put(url)
+        .header("Content-MD5", STANDARD.encode(digest))
+        .body(buf)
+        .build()?;
+    let resp = auth::sign_and_send(client, req, auth)
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to upload part {}", part.number))?;
+    let etag = resp
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| anyhow!("Part {} response has no ETag", part.number))?
+        .to_string();
+    verify_etag(&etag, &digest)
+        .with_context(|| format!("Integrity check failed for part {}", part.number))?;
+    Ok(etag)
+}
+
+/// Plain S3 ETags are the hex MD5 of the part; other forms (e.g. with
+/// SSE-KMS) are opaque and rely on the server's Content-MD5 check instead.
+fn verify_etag(etag: &str, digest: &[u8]) -> Result<()> {
+    let etag = etag.trim_matches('"');
+    if etag.len() != 32 || !etag.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Ok(());
+    }
+    let expected: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    if etag.eq_ignore_ascii_case(&expected) {
+        Ok(())
+    } else {
+        Err(anyhow!("expected MD5 {}, server has {}", expected, etag))
+    }
+}
+
+async fn complete(
+    client: &Client,
+    auth: Option<&dyn Auth>,
+    url: &Url,
+    state: &UploadState,
+) -> Result<Response> {
+    let mut url = url.clone();
+    url.query_pairs_mut()
+        .append_pair("uploadId", &state.upload_id);
+    let req = client.post(url).body(complete_body(&state.parts)).build()?;
+    auth::sign_and_send(client, req, auth).await
+}
+
+fn complete_body(parts: &BTreeMap<u32, String>) -> String {
+    let mut body = String::from("<CompleteMultipartUpload>");
+    for (number, etag) in parts {
+        body += &format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            number, etag
+        );
+    }
+    body += "</CompleteMultipartUpload>";
+    body
+}
+
+/// Text of the first `<tag>...</tag>` element in `xml`.
+pub fn xml_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&format!("</{}>", tag))?;
+    Some(&xml[start..start + end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_parts_works() {
+        assert_eq!(
+            plan_parts(10, 4),
+            vec![
+                PartRange {
+                    number: 1,
+                    offset: 0,
+                    len: 4
+                },
+                PartRange {
+                    number: 2,
+                    offset: 4,
+                    len: 4
+                },
+                PartRange {
+                    number: 3,
+                    offset: 8,
+                    len: 2
+                },
+            ]
+        );
+        assert_eq!(
+            plan_parts(0, 4),
+            vec![PartRange {
+                number: 1,
+                offset: 0,
+                len: 0
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn matches_works() {
+        let path = std::env::temp_dir().join("httpie-chunked-matches");
+        std::fs::write(&path, b"first").unwrap();
+        let file = path.to_str().unwrap();
+        let url: Url = "http://bucket/key".parse().unwrap();
+        let state = UploadState {
+            url: url.to_string(),
+            upload_id: "id".into(),
+            file_len: 5,
+            modified: file_modified(file).await.unwrap(),
+            part_size: 4,
+            parts: BTreeMap::new(),
+        };
+        assert!(state.matches(&url, 5, file_modified(file).await.unwrap(), 4));
+        assert!(!state.matches(&url, 5, file_modified(file).await.unwrap(), 8));
+
+        // another file of the same size must not resume the old upload
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        std::fs::write(&path, b"other").unwrap();
+        assert!(!state.matches(&url, 5, file_modified(file).await.unwrap(), 4));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn verify_etag_works() {
+        let digest = Md5::digest(b"hello");
+        assert!(verify_etag("\"5d41402abc4b2a76b9719d911017c592\"", &digest).is_ok());
+        assert!(verify_etag("\"00000000000000000000000000000000\"", &digest).is_err());
+        assert!(verify_etag("\"opaque-etag-2\"", &digest).is_ok());
+    }
+
+    #[test]
+    fn xml_text_works() {
+        let xml = "<InitiateMultipartUploadResult><UploadId>abc</UploadId></InitiateMultipartUploadResult>";
+        assert_eq!(xml_text(xml, "UploadId"), Some("abc"));
+        assert_eq!(xml_text(xml, "Key"), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{template, BodyItem, KvPair};
+
+/// A request definition, as stored in a collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Body items in command line syntax: `k=v`, `k@path` or `@path`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body: Vec<String>,
+}
+
+impl Request {
+    /// Parsed body items.
+    pub fn items(&self) -> Result<Vec<BodyItem>> {
+        self.body.iter().map(|s| s.parse()).collect()
+    }
+
+    /// Apply `key=value` overrides: `url` and `method` replace those, a key
+    /// naming a saved header replaces its value, anything else sets a body
+    /// field (or the path of a file item with that key).
+    pub fn apply(&mut self, overrides: &[KvPair]) -> Result<()> {
+        let mut items = self.items()?;
+        for KvPair { k, v } in overrides {
+            if k == "url" {
+                self.url = v.clone();
+            } else if k == "method" {
+                self.method = v.to_ascii_uppercase();
+            } else if let Some(name) = self.headers.keys().find(|h| h.eq_ignore_ascii_case(k)) {
+                let name = name.clone();
+                self.headers.insert(name, v.clone());
+            } else {
+                let existing = items.iter_mut().find_map(|item| match item {
+                    BodyItem::Field(pair) | BodyItem::File(pair) if pair.k == *k => Some(pair),
+                    _ => None,
+                });
+                match existing {
+                    Some(pair) => pair.v = v.clone(),
+                    None => items.push(BodyItem::Field(KvPair {
+                        k: k.clone(),
+                        v: v.clone(),
+                    })),
+                }
+            }
+        }
+        self.body = items.iter().map(|i| i.to_string()).collect();
+        Ok(())
+    }
+
+    /// The request with `{{VAR}}` placeholders in the URL, header values and
+    /// body items resolved.
+    pub fn render(&self) -> Result<Self> {
+        Ok(Self {
+            method: self.method.clone(),
+            url: template::render(&self.url)?,
+            headers: self
+                .headers
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), template::render(v)?)))
+                .collect::<Result<_>>()?,
+            body: self
+                .body
+                .iter()
+                .map(|item| template::render(item))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+/// Named requests, kept in a JSON file.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Collection {
+    pub requests: BTreeMap<String, Request>,
+}
+
+impl Collection {
+    /// Load the collection at `path`, or an empty one when it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s)
+                .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Request> {
+        self.requests.get(name).ok_or_else(|| {
+            let known: Vec<_> = self.requests.keys().map(String::as_str).collect();
+            anyhow!(format!(
+                "Failed to find request {} (saved: {})",
+                name,
+                if known.is_empty() {
+                    "none".into()
+                } else {
+                    known.join(", ")
+                }
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kv(k: &str, v: &str) -> KvPair {
+        KvPair {
+            k: k.into(),
+            v: v.into(),
+        }
+    }
+
+    #[test]
+    fn apply_works() {
+        let mut req = Request {
+            method: "POST".into(),
+            url: "http://localhost/login".into(),
+            headers: BTreeMap::from([("X-Token".into(), "a".into())]),
+            body: vec!["user=bob".into(), "avatar@me.png".into()],
+        };
+        req.apply(&[
+            kv("url", "http://staging/login"),
+            kv("x-token", "b"),
+            kv("user", "alice"),
+            kv("avatar", "you.png"),
+            kv("remember", "true"),
+        ])
+        .unwrap();
+        assert_eq!(req.url, "http://staging/login");
+        assert_eq!(req.headers["X-Token"], "b");
+        assert_eq!(req.body, ["user=alice", "avatar@you.png", "remember=true"]);
+
+        // as given with `run --set`, `=` in values and all
+        let set = ["url=http://h/x?a=b", "token=YWJj=="].map(|s| crate::parse_kv_pair(s).unwrap());
+        req.apply(&set).unwrap();
+        assert_eq!(req.url, "http://h/x?a=b");
+        assert_eq!(req.body[3], "token=YWJj==");
+        req.apply(&[]).unwrap();
+        assert_eq!(req.body[3], "token=YWJj==");
+    }
+
+    #[test]
+    fn collection_round_trips() {
+        let path =
+            std::env::temp_dir().join(format!("httpie-collection-{}.json", std::process::id()));
+        assert!(Collection::load(&path).unwrap().requests.is_empty());
+        let mut c = Collection::default();
+        c.requests.insert(
+            "ping".into(),
+            Request {
+                method: "GET".into(),
+                url: "http://localhost/ping".into(),
+                headers: BTreeMap::new(),
+                body: vec![],
+            },
+        );
+        c.save(&path).unwrap();
+        let loaded = Collection::load(&path).unwrap();
+        assert_eq!(loaded.get("ping").unwrap(), c.get("ping").unwrap());
+        assert!(loaded.get("pong").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
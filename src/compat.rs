@@ -0,0 +1,255 @@
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::atomic::{AtomicU16, Ordering},
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use colored::Colorize;
+use reqwest::{
+    header::{self, HeaderMap},
+    Certificate, ClientBuilder, Request, StatusCode,
+};
+
+/// Which parts of the exchange `--print` shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Print {
+    pub request_headers: bool,
+    pub request_body: bool,
+    pub response_headers: bool,
+    pub response_body: bool,
+}
+
+impl Default for Print {
+    fn default() -> Self {
+        Self {
+            request_headers: false,
+            request_body: false,
+            response_headers: true,
+            response_body: true,
+        }
+    }
+}
+
+impl Print {
+    pub const ALL: Self = Self {
+        request_headers: true,
+        request_body: true,
+        response_headers: true,
+        response_body: true,
+    };
+
+    pub fn request(&self) -> bool {
+        self.request_headers || self.request_body
+    }
+}
+
+/// `--print` letters: `H` request headers, `B` request body, `h` response
+/// headers, `b` response body.
+pub fn parse_print(s: &str) -> Result<Print> {
+    let mut print = Print {
+        request_headers: false,
+        request_body: false,
+        response_headers: false,
+        response_body: false,
+    };
+    for c in s.chars() {
+        match c {
+            'H' => print.request_headers = true,
+            'B' => print.request_body = true,
+            'h' => print.response_headers = true,
+            'b' => print.response_body = true,
+            other => {
+                return Err(anyhow!(format!(
+                    "Failed to parse {}: unknown part {}, expected some of HBhb",
+                    s, other
+                )))
+            }
+        }
+    }
+    Ok(print)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum AuthType {
+    /// `user:password`, sent as Basic credentials
+    #[default]
+    Basic,
+    /// a token, sent as `Authorization: Bearer <token>`
+    Bearer,
+    /// `user:password`, answering the server's Digest challenge (RFC 7616)
+    Digest,
+    /// AWS Signature Version 4 signing, with the keys of `--aws-profile` or
+    /// `--auth KEY:SECRET`
+    Aws4,
+}
+
+/// User and password from `user:password`, asking for the password when
+/// `user` comes without one, as HTTPie does, unless `prompt` is off.
+pub fn credentials(auth: &str, prompt: bool) -> Result<(String, String)> {
+    match auth.split_once(':') {
+        Some((user, password)) => Ok((user.to_string(), password.to_string())),
+        None if !prompt => Err(anyhow!(format!(
+            "Failed to read the password for {}: prompts are off, give `{}:password`",
+            auth, auth
+        ))),
+        None => {
+            let prompt = format!("http: password for {}: ", auth);
+            let password =
+                rpassword::prompt_password(prompt).context("Failed to read the password")?;
+            Ok((auth.to_string(), password))
+        }
+    }
+}
+
+/// The Authorization header for `--auth` with the basic and bearer types,
+/// which send the same one with every request.
+pub fn authorization(auth: &str, auth_type: AuthType, prompt: bool) -> Result<String> {
+    match auth_type {
+        AuthType::Bearer => Ok(format!("Bearer {}", auth)),
+        _ => {
+            let (user, password) = credentials(auth, prompt)?;
+            Ok(format!("Basic {}", STANDARD.encode(format!("{}:{}", user, password))))
+        }
+    }
+}
+
+/// `--verify`: check certificates against the system roots, not at all, or
+/// against a CA bundle.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Verify {
+    #[default]
+    Yes,
+    No,
+    CaBundle(PathBuf),
+}
+
+pub fn parse_verify(s: &str) -> Result<Verify> {
+    Ok(match s.to_ascii_lowercase().as_str() {
+        "yes" | "true" => Verify::Yes,
+        "no" | "false" => Verify::No,
+        _ => Verify::CaBundle(s.into()),
+    })
+}
+
+pub fn configure(builder: ClientBuilder, verify: &Verify) -> Result<ClientBuilder> {
+    Ok(match verify {
+        Verify::Yes => builder,
+        Verify::No => builder.danger_accept_invalid_certs(true),
+        Verify::CaBundle(path) => {
+            let pem = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+            let end = "-----END CERTIFICATE-----";
+            let mut builder = builder;
+            let mut found = false;
+            for block in pem.split_inclusive(end).filter(|b| b.contains(end)) {
+                let cert = Certificate::from_pem(block.trim().as_bytes()).map_err(|e| {
+                    anyhow!(format!("Failed to parse CA bundle {}: {}", path.display(), e))
+                })?;
+                builder = builder.add_root_certificate(cert);
+                found = true;
+            }
+            if !found {
+                return Err(anyhow!(format!(
+                    "Failed to parse CA bundle {}: no PEM certificates in it",
+                    path.display()
+                )));
+            }
+            builder
+        }
+    })
+}
+
+/// The least successful response status seen so far, for `--check-status`.
+static WORST_STATUS: AtomicU16 = AtomicU16::new(0);
+
+pub fn observe(status: StatusCode) {
+    WORST_STATUS.fetch_max(status.as_u16(), Ordering::Relaxed);
+}
+
+/// A redirect or error response under `--check-status`.
+#[derive(Debug)]
+pub struct StatusError(pub StatusCode);
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP {}", self.0)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+impl StatusError {
+    /// 3, 4 or 5 by the status class, as HTTPie exits.
+    pub fn exit_code(&self) -> u8 {
+        (self.0.as_u16() / 100) as u8
+    }
+}
+
+/// An error when any response seen was a 3xx (not followed), 4xx or 5xx.
+pub fn check_status() -> Result<()> {
+    let worst = WORST_STATUS.load(Ordering::Relaxed);
+    match StatusCode::from_u16(worst) {
+        Ok(status) if (300..600).contains(&worst) => Err(StatusError(status).into()),
+        _ => Ok(()),
+    }
+}
+
+/// Print the request as it goes out, with `defaults` for the headers the
+/// client adds.
+pub fn print_request(req: &Request, defaults: &HeaderMap, print: Print) {
+    if print.request_headers {
+        let target = crate::conn::target(req.url());
+        println!("{} {} {:?}", req.method().as_str().blue(), target, req.version());
+        let mut headers = defaults.clone();
+        headers.extend(req.headers().clone());
+        if let Ok(host) = crate::conn::authority(req.url()).parse() {
+            headers.entry(header::HOST).or_insert(host);
+        }
+        crate::print_header_table(&headers, false);
+        println!();
+    }
+    if print.request_body {
+        match req.body().map(|b| b.as_bytes()) {
+            Some(Some(body)) => println!("{}\n", String::from_utf8_lossy(body)),
+            Some(None) => println!("{}\n", "(streamed body)".yellow()),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_print_works() {
+        assert_eq!(parse_print("hb").unwrap(), Print::default());
+        assert_eq!(parse_print("HBhb").unwrap(), Print::ALL);
+        let headers = parse_print("Hh").unwrap();
+        assert!(headers.request() && !headers.response_body);
+        assert!(parse_print("x").is_err());
+    }
+
+    #[test]
+    fn authorization_works() {
+        assert_eq!(
+            authorization("user:pa:ss", AuthType::Basic, true).unwrap(),
+            "Basic dXNlcjpwYTpzcw=="
+        );
+        assert_eq!(authorization("t0k", AuthType::Bearer, true).unwrap(), "Bearer t0k");
+        assert!(authorization("user", AuthType::Basic, false).is_err());
+    }
+
+    #[test]
+    fn parse_verify_works() {
+        assert_eq!(parse_verify("no").unwrap(), Verify::No);
+        assert_eq!(parse_verify("YES").unwrap(), Verify::Yes);
+        assert_eq!(
+            parse_verify("/etc/ca.pem").unwrap(),
+            Verify::CaBundle("/etc/ca.pem".into())
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use std::io::Read;
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use flate2::{
+    read::{MultiGzDecoder, ZlibDecoder},
+    write::GzEncoder,
+    Compression,
+};
+use hyper::body::Bytes;
+use reqwest::{header, Request};
+
+/// What `--compressed` offers, best first.
+pub const OFFERED: &str = "br, zstd, gzip, deflate";
+
+/// Content codings of a Content-Encoding value, in the order they were applied.
+pub fn codings(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(|c| c.trim().to_ascii_lowercase())
+        .filter(|c| !c.is_empty() && c != "identity")
+        .collect()
+}
+
+fn decode_one(coding: &str, bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let read = match coding {
+        "gzip" | "x-gzip" => MultiGzDecoder::new(bytes).read_to_end(&mut out),
+        "deflate" => ZlibDecoder::new(bytes).read_to_end(&mut out),
+        "br" => brotli::Decompressor::new(bytes, 4096).read_to_end(&mut out),
+        "zstd" => ruzstd::decoding::StreamingDecoder::new(bytes)
+            .map_err(|e| anyhow!(format!("Failed to decompress zstd body: {}", e)))?
+            .read_to_end(&mut out),
+        other => {
+            return Err(anyhow!(format!(
+                "Failed to decompress body: unsupported coding {}",
+                other
+            )))
+        }
+    };
+    read.with_context(|| format!("Failed to decompress {} body", coding))?;
+    Ok(out)
+}
+
+/// Undo `codings`, last applied first; with none, `bytes` are handed back
+/// as they are rather than copied.
+pub fn decode(codings: &[String], bytes: Bytes) -> Result<Bytes> {
+    codings.iter().rev().try_fold(bytes, |bytes, coding| {
+        decode_one(coding, &bytes).map(Bytes::from)
+    })
+}
+
+/// Gzip the body of `req` in place, returning its size before and after.
+pub fn compress_body(req: &mut Request) -> Result<Option<(usize, usize)>> {
+    let Some(body) = req.body() else {
+        return Ok(None);
+    };
+    let raw = body.as_bytes().ok_or_else(|| {
+        anyhow!("Failed to compress request body: streamed file bodies are not supported")
+    })?;
+    let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+    std::io::Write::write_all(&mut gz, raw)?;
+    let gz = gz.finish()?;
+    let sizes = (raw.len(), gz.len());
+    req.headers_mut()
+        .insert(header::CONTENT_ENCODING, "gzip".parse()?);
+    *req.body_mut() = Some(gz.into());
+    Ok(Some(sizes))
+}
+
+/// One line about the response encoding: what was offered (with
+/// `--compressed`), what the server chose, and the sizes on the wire and
+/// decompressed, when it was.
+pub fn report(
+    offered: Option<&str>,
+    chosen: &[String],
+    wire: usize,
+    decoded: Option<usize>,
+) -> String {
+    let choice = if chosen.is_empty() {
+        "identity (uncompressed)".yellow().to_string()
+    } else {
+        chosen.join(", ").green().to_string()
+    };
+    let mut line = match offered {
+        Some(offered) => format!(
+            "{} offered {}, server chose {}",
+            "Encoding:".bold(),
+            offered,
+            choice
+        ),
+        None => format!("{} {}", "Encoding:".bold(), choice),
+    };
+    if chosen.is_empty() {
+        return line;
+    }
+    match decoded {
+        Some(decoded) if decoded > 0 => {
+            line += &format!(
+                "; {} -> {} bytes, {:.1}x ({:.0}% smaller)",
+                wire,
+                decoded,
+                decoded as f64 / wire.max(1) as f64,
+                100.0 - wire as f64 * 100.0 / decoded as f64
+            );
+        }
+        Some(_) => {}
+        None => line += &format!("; {} bytes, not decompressed", wire),
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn decode_works() {
+        let text = b"hello hello hello hello hello".repeat(10);
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&text).unwrap();
+        let gz = gz.finish().unwrap();
+        let mut br = Vec::new();
+        brotli::CompressorWriter::new(&mut br, 4096, 5, 22)
+            .write_all(&gz)
+            .unwrap();
+        // gzip applied first, then br
+        let codings = codings("gzip, br");
+        assert_eq!(decode(&codings, br.into()).unwrap(), text);
+
+        let mut zst = Vec::new();
+        ruzstd::encoding::compress(
+            &text[..],
+            &mut zst,
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        assert_eq!(decode(&["zstd".into()], zst.into()).unwrap(), text);
+        assert!(decode(&["compress".into()], Bytes::from_static(b"x")).is_err());
+        assert!(decode(&["gzip".into()], Bytes::from_static(b"not gzip")).is_err());
+        // nothing to undo, nothing copied
+        let plain = Bytes::from_static(b"plain");
+        assert_eq!(decode(&[], plain.clone()).unwrap().as_ptr(), plain.as_ptr());
+    }
+
+    #[test]
+    fn report_works() {
+        colored::control::set_override(false);
+        assert_eq!(
+            report(Some(OFFERED), &["br".into()], 250, Some(1000)),
+            "Encoding: offered br, zstd, gzip, deflate, server chose br; 250 -> 1000 bytes, 4.0x (75% smaller)"
+        );
+        assert_eq!(
+            report(Some("gzip"), &[], 10, Some(10)),
+            "Encoding: offered gzip, server chose identity (uncompressed)"
+        );
+        assert_eq!(
+            report(None, &["gzip".into()], 250, None),
+            "Encoding: gzip; 250 bytes, not decompressed"
+        );
+    }
+
+    #[test]
+    fn compress_body_works() {
+        let client = reqwest::Client::new();
+        let mut req = client
+            .post("http://a/")
+            .body("hello ".repeat(100))
+            .build()
+            .unwrap();
+        let (raw, gz) = compress_body(&mut req).unwrap().unwrap();
+        assert_eq!(raw, 600);
+        assert!(gz < raw);
+        assert_eq!(req.headers()[header::CONTENT_ENCODING], "gzip");
+        let body = req.body().unwrap().as_bytes().unwrap();
+        assert_eq!(
+            decode(&["gzip".into()], Bytes::copy_from_slice(body)).unwrap(),
+            "hello ".repeat(100).as_bytes()
+        );
+        let mut empty = client.get("http://a/").build().unwrap();
+        assert_eq!(compress_body(&mut empty).unwrap(), None);
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use mime::Mime;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{collection, curl::split_words};
+
+/// Name of the project config, looked for from the current directory up.
+pub const PROJECT_FILE: &str = ".httpie.toml";
+
+/// The user configuration, `~/.httpie/config.toml`, with the project one
+/// merged over it.
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    /// Base URL that URLs given as a path, like `/users`, are relative to
+    pub base: Option<String>,
+    /// Words that stand for a command line, e.g.
+    /// `whoami = "get {{base}}/me --cookie session={{SESSION}}"`
+    #[serde(default)]
+    pub alias: BTreeMap<String, String>,
+    /// Settings selected with `--profile`
+    #[serde(default)]
+    pub profile: BTreeMap<String, Profile>,
+    /// Saved requests for `run`, alongside those in the collection file
+    #[serde(default)]
+    pub requests: BTreeMap<String, collection::Request>,
+    /// Requests per second allowed to a host, counted across every run, e.g.
+    /// `"api.example.com" = 2`
+    #[serde(default)]
+    pub rate_limit: BTreeMap<String, f64>,
+    /// Template variables fetched at run time instead of kept in the file,
+    /// e.g. `API_TOKEN = "vault:secret/staging#token"`, also `sops:FILE#a.b`
+    /// and `env-file:FILE#KEY`
+    #[serde(default)]
+    pub secrets: BTreeMap<String, String>,
+    /// Count the subcommands and flags used in `~/.httpie/usage.json`, for
+    /// `httpie usage`
+    pub usage_stats: Option<bool>,
+    /// How to print bodies from URLs matching a pattern, whatever their
+    /// Content-Type says, e.g. `"*.internal/api/*" = "json"` or
+    /// `"*.log" = "plain"`; the longest matching pattern wins
+    #[serde(default)]
+    pub format: BTreeMap<String, Syntax>,
+    /// The files this was loaded from
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+/// A format forced on a response body by `format`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    Json,
+    Xml,
+    Yaml,
+    Html,
+    Css,
+    Js,
+    Plain,
+}
+
+impl Syntax {
+    /// The type bodies are then taken to be, keeping the charset of the
+    /// original one.
+    pub fn mime(self, original: Option<&Mime>) -> Mime {
+        let essence = match self {
+            Self::Json => "application/json",
+            Self::Xml => "application/xml",
+            Self::Yaml => "application/yaml",
+            Self::Html => "text/html",
+            Self::Css => "text/css",
+            Self::Js => "application/javascript",
+            Self::Plain => "text/plain",
+        };
+        let charset = original.and_then(|m| m.get_param(mime::CHARSET));
+        match charset {
+            Some(charset) => format!("{}; charset={}", essence, charset).parse(),
+            None => essence.parse(),
+        }
+        .unwrap_or(mime::TEXT_PLAIN)
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for any one.
+fn glob(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    // the last `*` seen, and where in `text` it was last tried up to
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            star = Some((sp, st + 1));
+            pi = sp + 1;
+            ti = st + 1;
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// A named set of settings, e.g. one per deployment.
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Profile {
+    /// Replaces the top level `base`
+    pub base: Option<String>,
+    /// Headers sent with every request; values may use `{{VAR}}`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+}
+
+pub fn default_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".httpie")
+        .join("config.toml")
+}
+
+/// The nearest project config in `dir` or one of its parents.
+pub fn discover(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(PROJECT_FILE))
+        .find(|p| p.is_file())
+}
+
+impl Config {
+    /// Load the config at `path`, or an empty one when it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut config: Self = match std::fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s)
+                .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        config.sources.push(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// The user config with the project config found from the current
+    /// directory merged over it.
+    pub fn load_all() -> Result<Self> {
+        let mut config = Self::load(&default_path())?;
+        let project = std::env::current_dir().ok().and_then(|d| discover(&d));
+        if let Some(path) = project {
+            config.merge(Self::load(&path)?);
+        }
+        Ok(config)
+    }
+
+    /// The format forced on bodies from `url`. Patterns are matched against
+    /// the host and path, with and without the port, or the whole URL up to
+    /// the query when they have a scheme.
+    pub fn format_for(&self, url: &Url) -> Option<Syntax> {
+        let host = url.host_str()?;
+        let mut targets = vec![format!("{}{}", host, url.path())];
+        if let Some(port) = url.port() {
+            targets.push(format!("{}:{}{}", host, port, url.path()));
+        }
+        let full = url.origin().ascii_serialization() + url.path();
+        self.format
+            .iter()
+            .filter(|(pattern, _)| match pattern.contains("://") {
+                true => glob(pattern, &full),
+                false => targets.iter().any(|t| glob(pattern, t)),
+            })
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, syntax)| *syntax)
+    }
+
+    /// Take `base` and every alias, profile and request of `other` over ours.
+    pub fn merge(&mut self, other: Self) {
+        if other.base.is_some() {
+            self.base = other.base;
+        }
+        if other.usage_stats.is_some() {
+            self.usage_stats = other.usage_stats;
+        }
+        self.alias.extend(other.alias);
+        self.profile.extend(other.profile);
+        self.requests.extend(other.requests);
+        self.rate_limit.extend(other.rate_limit);
+        self.secrets.extend(other.secrets);
+        self.format.extend(other.format);
+        self.sources.extend(other.sources);
+    }
+}
+
+/// The config in effect and the selected profile, set once before the
+/// command line is parsed so that value parsers can see the base URL.
+static CURRENT: OnceLock<(Config, Profile)> = OnceLock::new();
+
+/// Make `config` current, with the profile named by `--profile`.
+pub fn init(config: Config, profile: Option<&str>) -> Result<()> {
+    let selected = match profile {
+        Some(name) => config.profile.get(name).cloned().ok_or_else(|| {
+            let known: Vec<_> = config.profile.keys().map(String::as_str).collect();
+            anyhow!(format!(
+                "Failed to select profile {}: not defined (defined: {})",
+                name,
+                if known.is_empty() {
+                    "none".into()
+                } else {
+                    known.join(", ")
+                }
+            ))
+        })?,
+        None => Profile::default(),
+    };
+    CURRENT.set((config, selected)).ok();
+    Ok(())
+}
+
+pub fn current() -> &'static Config {
+    static EMPTY: OnceLock<Config> = OnceLock::new();
+    CURRENT
+        .get()
+        .map(|(c, _)| c)
+        .unwrap_or_else(|| EMPTY.get_or_init(Config::default))
+}
+
+/// The selected profile, empty without `--profile`.
+pub fn profile() -> Option<&'static Profile> {
+    CURRENT.get().map(|(_, p)| p)
+}
+
+/// The base URL of the selected profile, or the top level one.
+pub fn base() -> Option<&'static str> {
+    profile()
+        .and_then(|p| p.base.as_deref())
+        .or(current().base.as_deref())
+}
+
+/// Index of the subcommand in `args`, skipping the global options before it
+/// along with their values.
+fn subcommand_index(args: &[String], cmd: &clap::Command) -> Option<usize> {
+    let takes_value = |arg: &clap::Arg| arg.get_action().takes_values();
+    let mut i = 1;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" {
+            return None;
+        }
+        let flag = if let Some(long) = arg.strip_prefix("--") {
+            (!long.contains('='))
+                .then(|| cmd.get_arguments().find(|a| a.get_long() == Some(long)))
+                .flatten()
+        } else if let Some(short) = arg.strip_prefix('-').filter(|s| s.chars().count() == 1) {
+            let c = short.chars().next()?;
+            cmd.get_arguments().find(|a| a.get_short() == Some(c))
+        } else if arg.starts_with('-') {
+            None
+        } else {
+            return Some(i);
+        };
+        i += if flag.is_some_and(takes_value) { 2 } else { 1 };
+    }
+    None
+}
+
+/// Replace an alias standing where the subcommand goes with the words it
+/// expands to. Built-in subcommands win over aliases of the same name, and
+/// an expansion is not expanded again.
+pub fn expand_alias(
+    args: Vec<String>,
+    config: &Config,
+    cmd: &clap::Command,
+) -> Result<Vec<String>> {
+    let Some(i) = subcommand_index(&args, cmd) else {
+        return Ok(args);
+    };
+    let name = &args[i];
+    if cmd.find_subcommand(name).is_some() {
+        return Ok(args);
+    }
+    let Some(expansion) = config.alias.get(name) else {
+        return Ok(args);
+    };
+    let words = split_words(expansion)
+        .map_err(|e| anyhow!(format!("Failed to expand alias {}: {}", name, e)))?;
+    let mut expanded = args[..i].to_vec();
+    expanded.extend(words);
+    expanded.extend_from_slice(&args[i + 1..]);
+    Ok(expanded)
+}
+
+/// Print the aliases, one `name = expansion` per line.
+pub fn list_aliases(config: &Config, cmd: &clap::Command) {
+    if config.alias.is_empty() {
+        let sources: Vec<_> = config
+            .sources
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        println!("No aliases defined in {}", sources.join(" or "));
+        return;
+    }
+    let width = config.alias.keys().map(|k| k.len()).max().unwrap_or(0);
+    for (name, expansion) in config.alias.iter() {
+        let shadowed = if cmd.find_subcommand(name).is_some() {
+            " (shadowed by the built-in command)".yellow().to_string()
+        } else {
+            String::new()
+        };
+        println!(
+            "{} = {}{}",
+            format!("{:<width$}", name, width = width).bold(),
+            expansion,
+            shadowed
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn format_for_works() {
+        assert!(glob("*.internal/api/*", "a.b.internal/api/users"));
+        assert!(glob("a?c*", "abc"));
+        assert!(!glob("*.log", "x/app.log/more"));
+        let config: Config = toml::from_str(
+            r#"
+            [format]
+            "*.internal/api/*" = "json"
+            "*.internal/api/logs/*" = "plain"
+            "*.log" = "plain"
+            "http://legacy.test/*" = "xml"
+            "#,
+        )
+        .unwrap();
+        let format = |url: &str| config.format_for(&url.parse().unwrap());
+        assert_eq!(
+            format("https://x.internal/api/users?page=2"),
+            Some(Syntax::Json)
+        );
+        assert_eq!(
+            format("https://x.internal:8443/api/logs/today"),
+            Some(Syntax::Plain)
+        );
+        assert_eq!(format("https://files.test/app.log"), Some(Syntax::Plain));
+        assert_eq!(format("http://legacy.test/soap"), Some(Syntax::Xml));
+        assert_eq!(format("https://legacy.test/soap"), None);
+        let latin1 = "text/html; charset=latin1".parse().unwrap();
+        assert_eq!(
+            Syntax::Json.mime(Some(&latin1)).to_string(),
+            "application/json; charset=latin1"
+        );
+        assert!(toml::from_str::<Config>("[format]\n\"*\" = \"jsonish\"").is_err());
+    }
+
+    #[test]
+    fn expand_alias_works() {
+        let config: Config = toml::from_str(
+            r#"
+            [alias]
+            whoami = "get '{{base}}/me' --sorted"
+            get = "post http://a.b/"
+            "#,
+        )
+        .unwrap();
+        let cmd = crate::command();
+        assert_eq!(
+            expand_alias(args("httpie --cookie a=b whoami -r"), &config, &cmd).unwrap(),
+            args("httpie --cookie a=b get {{base}}/me --sorted -r")
+        );
+        // the built-in wins
+        assert_eq!(
+            expand_alias(args("httpie get http://x.y/"), &config, &cmd).unwrap(),
+            args("httpie get http://x.y/")
+        );
+        assert_eq!(
+            expand_alias(args("httpie --cookie=whoami unknown"), &config, &cmd).unwrap(),
+            args("httpie --cookie=whoami unknown")
+        );
+        assert!(toml::from_str::<Config>("[alias]\nx = 1").is_err());
+    }
+
+    #[test]
+    fn merge_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-config-{}", std::process::id()));
+        let nested = dir.join("a").join("b");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(
+            dir.join(PROJECT_FILE),
+            r#"
+            base = "localhost:3000"
+            [alias]
+            me = "get /me"
+            [profile.staging]
+            base = "https://staging.example.com"
+            [requests.health]
+            method = "GET"
+            url = "/health"
+            "#,
+        )
+        .unwrap();
+        let found = discover(&nested).unwrap();
+        assert_eq!(found, dir.join(PROJECT_FILE));
+
+        let mut config: Config = toml::from_str(
+            r#"
+            base = "https://api.example.com"
+            [alias]
+            me = "get /users/me"
+            up = "get /status"
+            "#,
+        )
+        .unwrap();
+        config.merge(Config::load(&found).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.base.as_deref(), Some("localhost:3000"));
+        assert_eq!(config.alias["me"], "get /me");
+        assert_eq!(config.alias["up"], "get /status");
+        assert!(config.profile.contains_key("staging"));
+        assert_eq!(config.requests["health"].url, "/health");
+        assert_eq!(config.sources, vec![found]);
+    }
+}
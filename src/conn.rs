@@ -0,0 +1,76 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{body, client::conn, Body};
+use reqwest::{
+    header::{self, HeaderMap},
+    Request, Response, Url,
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::UnixStream,
+};
+
+/// Path and query of `url`, as sent in the request line.
+pub fn target(url: &Url) -> String {
+    match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    }
+}
+
+/// The Host header for `url`.
+pub fn authority(url: &Url) -> String {
+    let host = url.host_str().unwrap_or("localhost");
+    match url.port() {
+        Some(p) => format!("{}:{}", host, p),
+        None => host.to_string(),
+    }
+}
+
+/// One HTTP/1.1 request over `io`, a connection reqwest does not manage. The
+/// connection is handed back so that callers can inspect it.
+pub async fn exchange<T>(io: T, req: hyper::Request<Body>) -> Result<(hyper::Response<Vec<u8>>, T)>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut send, connection) = conn::handshake(io).await?;
+    let connection = tokio::spawn(connection.without_shutdown());
+    let (parts, body) = send.send_request(req).await?.into_parts();
+    let bytes = body::to_bytes(body).await?.to_vec();
+    drop(send);
+    let io = connection.await??.io;
+    Ok((hyper::Response::from_parts(parts, bytes), io))
+}
+
+/// Send `req` over the Unix socket at `path`, with `defaults` for the headers
+/// the client would otherwise add.
+pub async fn unix(path: &Path, req: Request, defaults: &HeaderMap) -> Result<Response> {
+    let body = match req.body() {
+        Some(b) => b
+            .as_bytes()
+            .ok_or_else(|| {
+                anyhow!("Failed to send over a Unix socket: streamed bodies are not supported")
+            })?
+            .to_vec(),
+        None => Vec::new(),
+    };
+    let mut headers = defaults.clone();
+    headers.extend(req.headers().clone());
+    // as reqwest does
+    if !headers.contains_key(header::ACCEPT) {
+        headers.insert(header::ACCEPT, "*/*".parse()?);
+    }
+    if !headers.contains_key(header::HOST) {
+        headers.insert(header::HOST, authority(req.url()).parse()?);
+    }
+    let mut builder = hyper::Request::builder()
+        .method(req.method().clone())
+        .uri(target(req.url()));
+    *builder.headers_mut().unwrap() = headers;
+    let stream = UnixStream::connect(path)
+        .await
+        .with_context(|| format!("Failed to connect to {}", path.display()))?;
+    let (resp, _) = exchange(stream, builder.body(Body::from(body))?).await?;
+    Ok(resp.into())
+}
@@ -0,0 +1,358 @@
+use std::{
+    path::Path,
+    sync::{OnceLock, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use reqwest::{cookie::CookieStore, header::HeaderValue, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::KvPair;
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// set without a Domain attribute, so only sent to exactly `domain`
+    host_only: bool,
+    path: String,
+    secure: bool,
+    /// unix timestamp; `None` for session cookies
+    expires: Option<u64>,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header received from `url`, following RFC 6265.
+    fn parse(header: &str, url: &Url) -> Option<Self> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut attrs = header.split(';');
+        let (name, value) = attrs.next()?.split_once('=')?;
+        let mut cookie = Self {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_path(url),
+            secure: false,
+            expires: None,
+        };
+        if cookie.name.is_empty() {
+            return None;
+        }
+
+        let mut max_age = None;
+        for attr in attrs {
+            let (k, v) = attr.split_once('=').unwrap_or((attr, ""));
+            let v = v.trim();
+            match k.trim().to_ascii_lowercase().as_str() {
+                "domain" if !v.is_empty() => {
+                    let domain = v.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_match(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if v.starts_with('/') => cookie.path = v.to_string(),
+                "secure" => cookie.secure = true,
+                "expires" => {
+                    if let Ok(t) = httpdate::parse_http_date(v) {
+                        cookie.expires = Some(unix_secs(t));
+                    }
+                }
+                "max-age" => max_age = v.parse::<i64>().ok(),
+                _ => {}
+            }
+        }
+        // Max-Age takes precedence over Expires
+        if let Some(age) = max_age {
+            cookie.expires = Some((unix_secs(SystemTime::now()) as i64 + age).max(0) as u64);
+        }
+        Some(cookie)
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires, Some(t) if t <= now)
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(h) => h.to_ascii_lowercase(),
+            None => return false,
+        };
+        let host_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        host_ok && path_match(url.path(), &self.path) && (!self.secure || url.scheme() == "https")
+    }
+
+    /// Same cookie per RFC 6265: a new one with this identity replaces the old.
+    fn same_as(&self, other: &Self) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    /// One line of a Netscape (curl/wget) cookies.txt file.
+    fn to_netscape(&self) -> String {
+        let domain = if self.host_only {
+            self.domain.clone()
+        } else {
+            format!(".{}", self.domain)
+        };
+        let flag = |b| if b { "TRUE" } else { "FALSE" };
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            domain,
+            flag(!self.host_only),
+            self.path,
+            flag(self.secure),
+            self.expires.unwrap_or(0),
+            self.name,
+            self.value
+        )
+    }
+
+    fn from_netscape(line: &str) -> Option<Self> {
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let fields: Vec<_> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return None;
+        }
+        let expires: u64 = fields[4].parse().ok()?;
+        Some(Self {
+            domain: fields[0].trim_start_matches('.').to_ascii_lowercase(),
+            host_only: fields[1] != "TRUE",
+            path: fields[2].to_string(),
+            secure: fields[3] == "TRUE",
+            expires: (expires != 0).then_some(expires),
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+        })
+    }
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of the request path, used when Set-Cookie has no Path.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".into(),
+        Some(i) => url.path()[..i].into(),
+    }
+}
+
+/// Cookie store used by the client: cookies from `--cookie` are sent to the
+/// host of the first request, and so not along redirects to other hosts,
+/// while cookies set by servers follow the usual domain/path rules and can
+/// be persisted to a `--cookie-jar` file.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    extra: Vec<KvPair>,
+    /// the host `extra` is for, once the first request is sent
+    extra_host: OnceLock<String>,
+    cookies: RwLock<Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Jar holding `extra` cookies plus whatever was saved in `path`, if any.
+    pub fn load(path: Option<&Path>, extra: &[KvPair]) -> Result<Self> {
+        let mut cookies = Vec::new();
+        if let Some(path) = path {
+            match std::fs::read_to_string(path) {
+                Ok(s) => cookies = parse_jar(path, &s)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to read {}", path.display()))
+                }
+            }
+        }
+        let now = unix_secs(SystemTime::now());
+        cookies.retain(|c: &Cookie| !c.is_expired(now));
+        Ok(Self {
+            extra: extra.to_vec(),
+            extra_host: OnceLock::new(),
+            cookies: RwLock::new(cookies),
+        })
+    }
+
+    /// Write all stored cookies to `path`: JSON for `*.json`, Netscape otherwise.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let cookies = self.cookies.read().unwrap();
+        let content = if is_json(path) {
+            serde_json::to_string_pretty(&*cookies)?
+        } else {
+            let mut s = String::from("# Netscape HTTP Cookie File\n");
+            for c in cookies.iter() {
+                s += &c.to_netscape();
+                s.push('\n');
+            }
+            s
+        };
+        std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e == "json")
+}
+
+fn parse_jar(path: &Path, s: &str) -> Result<Vec<Cookie>> {
+    if is_json(path) {
+        serde_json::from_str(s).with_context(|| format!("Failed to parse {}", path.display()))
+    } else {
+        Ok(s.lines().filter_map(Cookie::from_netscape).collect())
+    }
+}
+
+impl CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let now = unix_secs(SystemTime::now());
+        let mut cookies = self.cookies.write().unwrap();
+        for header in cookie_headers {
+            let Some(cookie) = header.to_str().ok().and_then(|h| Cookie::parse(h, url)) else {
+                continue;
+            };
+            cookies.retain(|c| !c.same_as(&cookie));
+            // an already-expired cookie is how servers delete one
+            if !cookie.is_expired(now) {
+                cookies.push(cookie);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let now = unix_secs(SystemTime::now());
+        let cookies = self.cookies.read().unwrap();
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let extra = match self.extra_host.get_or_init(|| host.clone()) == &host {
+            true => &self.extra[..],
+            false => &[],
+        };
+        let pairs: Vec<_> = extra
+            .iter()
+            .map(|p| format!("{}={}", p.k, p.v))
+            .chain(
+                cookies
+                    .iter()
+                    .filter(|c| !c.is_expired(now) && c.matches(url))
+                    .map(|c| format!("{}={}", c.name, c.value)),
+            )
+            .collect();
+        if pairs.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&pairs.join("; ")).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_cookie_works() {
+        let c = Cookie::parse("sid=abc; Path=/api; Secure", &url("https://x.org/login")).unwrap();
+        assert_eq!((c.name.as_str(), c.value.as_str()), ("sid", "abc"));
+        assert_eq!((c.domain.as_str(), c.path.as_str()), ("x.org", "/api"));
+        assert!(c.host_only && c.secure && c.expires.is_none());
+
+        let c = Cookie::parse("a=1; Domain=.x.org", &url("http://www.x.org/a/b")).unwrap();
+        assert_eq!((c.domain.as_str(), c.path.as_str()), ("x.org", "/a"));
+        assert!(!c.host_only);
+
+        assert!(Cookie::parse("a=1; Domain=evil.com", &url("http://x.org/")).is_none());
+        assert!(Cookie::parse("novalue", &url("http://x.org/")).is_none());
+    }
+
+    #[test]
+    fn cookie_matching_works() {
+        let c = Cookie::parse("a=1; Domain=x.org; Path=/api", &url("http://x.org/")).unwrap();
+        assert!(c.matches(&url("http://x.org/api")));
+        assert!(c.matches(&url("http://www.x.org/api/users")));
+        assert!(!c.matches(&url("http://x.org/apix")));
+        assert!(!c.matches(&url("http://y.org/api")));
+
+        let c = Cookie::parse("a=1; Secure", &url("https://x.org/")).unwrap();
+        assert!(!c.matches(&url("http://x.org/")));
+        assert!(!c.matches(&url("https://www.x.org/")));
+    }
+
+    #[test]
+    fn jar_round_trips_and_deletes() {
+        let jar = CookieJar::load(
+            None,
+            &[KvPair {
+                k: "k".into(),
+                v: "v".into(),
+            }],
+        )
+        .unwrap();
+        let u = url("http://x.org/");
+        let set = [
+            HeaderValue::from_static("a=1"),
+            HeaderValue::from_static("b=2; Max-Age=3600"),
+        ];
+        jar.set_cookies(&mut set.iter(), &u);
+        assert_eq!(jar.cookies(&u).unwrap(), "k=v; a=1; b=2");
+
+        let delete = [HeaderValue::from_static("a=; Max-Age=0")];
+        jar.set_cookies(&mut delete.iter(), &u);
+        assert_eq!(jar.cookies(&u).unwrap(), "k=v; b=2");
+
+        let lines: Vec<_> = jar
+            .cookies
+            .read()
+            .unwrap()
+            .iter()
+            .map(|c| c.to_netscape())
+            .collect();
+        let parsed: Vec<_> = lines
+            .iter()
+            .filter_map(|l| Cookie::from_netscape(l))
+            .collect();
+        assert_eq!(parsed, *jar.cookies.read().unwrap());
+    }
+
+    #[test]
+    fn extra_cookies_stay_with_the_first_host() {
+        let jar = CookieJar::load(
+            None,
+            &[KvPair {
+                k: "sid".into(),
+                v: "abc==".into(),
+            }],
+        )
+        .unwrap();
+        assert_eq!(
+            jar.cookies(&url("http://x.org/login")).unwrap(),
+            "sid=abc=="
+        );
+        // redirected to another host, and back
+        assert!(jar.cookies(&url("http://evil.com/")).is_none());
+        assert_eq!(jar.cookies(&url("http://X.org/home")).unwrap(), "sid=abc==");
+    }
+}
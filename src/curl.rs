@@ -0,0 +1,536 @@
+use anyhow::{anyhow, Context, Result};
+use indicatif::ProgressBar;
+use reqwest::{header, Client, Method, RequestBuilder};
+
+use crate::upload::file_part;
+
+/// An equivalent `curl` invocation, built up flag by flag.
+#[derive(Debug)]
+pub struct Curl {
+    method: Method,
+    url: String,
+    args: Vec<(String, Option<String>)>,
+    has_body: bool,
+}
+
+impl Curl {
+    pub fn new(method: Method, url: &str) -> Self {
+        Self {
+            method,
+            url: url.into(),
+            args: Vec::new(),
+            has_body: false,
+        }
+    }
+
+    pub fn header(&mut self, name: &str, value: &str) -> &mut Self {
+        self.arg("-H", &format!("{}: {}", name, value))
+    }
+
+    pub fn arg(&mut self, flag: &str, value: &str) -> &mut Self {
+        self.args.push((flag.into(), Some(value.into())));
+        self
+    }
+
+    /// A flag without a value, like `--insecure`.
+    pub fn flag(&mut self, flag: &str) -> &mut Self {
+        self.args.push((flag.into(), None));
+        self
+    }
+
+    /// Literal request body.
+    pub fn data(&mut self, body: &[u8]) -> &mut Self {
+        self.has_body = true;
+        self.arg("--data-raw", &String::from_utf8_lossy(body))
+    }
+
+    /// Request body read from `path`.
+    pub fn data_file(&mut self, path: &str) -> &mut Self {
+        self.has_body = true;
+        self.arg("--data-binary", &format!("@{}", path))
+    }
+
+    /// Multipart text field.
+    pub fn form(&mut self, name: &str, value: &str) -> &mut Self {
+        self.has_body = true;
+        self.arg("--form-string", &format!("{}={}", name, value))
+    }
+
+    /// Multipart file field.
+    pub fn form_file(&mut self, name: &str, path: &str) -> &mut Self {
+        self.has_body = true;
+        self.arg("-F", &format!("{}=@{}", name, path))
+    }
+}
+
+impl std::fmt::Display for Curl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "curl")?;
+        // curl implies GET, or POST once there is a body
+        let implied = if self.has_body {
+            Method::POST
+        } else {
+            Method::GET
+        };
+        if self.method != implied {
+            write!(f, " -X {}", self.method)?;
+        }
+        write!(f, " {}", quote(&self.url))?;
+        for (flag, value) in self.args.iter() {
+            match value {
+                Some(value) => write!(f, " \\\n  {} {}", flag, quote(value))?,
+                None => write!(f, " \\\n  {}", flag)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quote `s` for a POSIX shell, leaving obviously safe words alone.
+pub fn quote(s: &str) -> String {
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c));
+    if safe {
+        s.into()
+    } else {
+        format!("'{}'", s.replace('\'', r"'\''"))
+    }
+}
+
+/// A request described by a curl command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct CurlRequest {
+    pub method: Option<Method>,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub data: Vec<String>,
+    pub form: Vec<(String, String)>,
+    pub user: Option<String>,
+    /// `-G`: send data in the query string
+    pub get: bool,
+    /// `--json`: data is JSON
+    pub json: bool,
+}
+
+/// Flags that take a value, in their short and long spellings; `""` where
+/// there is no short one. Their values must not be taken for the URL.
+const VALUE_FLAGS: &[(&str, &str)] = &[
+    ("-X", "--request"),
+    ("-H", "--header"),
+    ("-d", "--data"),
+    ("-u", "--user"),
+    ("-A", "--user-agent"),
+    ("-b", "--cookie"),
+    ("-e", "--referer"),
+    ("-F", "--form"),
+    ("-o", "--output"),
+    ("-m", "--max-time"),
+    ("-r", "--range"),
+    ("-T", "--upload-file"),
+    ("-x", "--proxy"),
+    ("-U", "--proxy-user"),
+    ("-w", "--write-out"),
+    ("-c", "--cookie-jar"),
+    ("-D", "--dump-header"),
+    ("-E", "--cert"),
+    ("-K", "--config"),
+    ("-Y", "--speed-limit"),
+    ("-y", "--speed-time"),
+    ("", "--oauth2-bearer"),
+    ("", "--connect-timeout"),
+    ("", "--connect-to"),
+    ("", "--retry"),
+    ("", "--retry-delay"),
+    ("", "--retry-max-time"),
+    ("", "--max-redirs"),
+    ("", "--cacert"),
+    ("", "--capath"),
+    ("", "--cert-type"),
+    ("", "--key"),
+    ("", "--key-type"),
+    ("", "--pass"),
+    ("", "--ciphers"),
+    ("", "--resolve"),
+    ("", "--dns-servers"),
+    ("", "--interface"),
+    ("", "--noproxy"),
+    ("", "--unix-socket"),
+    ("", "--limit-rate"),
+    ("", "--keepalive-time"),
+    ("", "--expect100-timeout"),
+    ("", "--stderr"),
+    ("", "--trace"),
+    ("", "--trace-ascii"),
+    ("", "--proto"),
+    ("", "--proto-redir"),
+    ("", "--aws-sigv4"),
+];
+
+impl CurlRequest {
+    /// Parse a curl command, either as one string or already split words.
+    pub fn parse(words: &[String]) -> Result<Self> {
+        let words = match words {
+            [one] => split_words(one)?,
+            many => many.to_vec(),
+        };
+        let mut args = words.into_iter().peekable();
+        if args.peek().map(String::as_str) == Some("curl") {
+            args.next();
+        }
+
+        let mut req = Self::default();
+        let mut head = false;
+        while let Some(arg) = args.next() {
+            let (flag, inline) = split_flag(&arg);
+            let takes_value = VALUE_FLAGS.iter().any(|(s, l)| flag == *s || flag == *l)
+                || (flag.starts_with("--data") || flag == "--json" || flag == "--url");
+            let mut value = || -> Result<String> {
+                match inline.clone() {
+                    Some(v) => Ok(v),
+                    None => args
+                        .next()
+                        .ok_or_else(|| anyhow!("Missing value for {}", flag)),
+                }
+            };
+            match flag.as_str() {
+                "-X" | "--request" => req.method = Some(value()?.to_ascii_uppercase().parse()?),
+                "-H" | "--header" => {
+                    let h = value()?;
+                    let (k, v) = h
+                        .split_once(':')
+                        .ok_or_else(|| anyhow!("Failed to parse header {}", h))?;
+                    req.headers.push((k.trim().into(), v.trim().into()));
+                }
+                "-A" | "--user-agent" => req.headers.push(("User-Agent".into(), value()?)),
+                "-e" | "--referer" => req.headers.push(("Referer".into(), value()?)),
+                "-b" | "--cookie" => req.headers.push(("Cookie".into(), value()?)),
+                "-r" | "--range" => req
+                    .headers
+                    .push(("Range".into(), format!("bytes={}", value()?))),
+                "--oauth2-bearer" => req
+                    .headers
+                    .push(("Authorization".into(), format!("Bearer {}", value()?))),
+                "-T" | "--upload-file" => {
+                    return Err(anyhow!(
+                        "Failed to import -T {}: use --data-binary @file instead",
+                        value()?
+                    ))
+                }
+                "-u" | "--user" => req.user = Some(value()?),
+                "-F" | "--form" => {
+                    let f = value()?;
+                    let (k, v) = f
+                        .split_once('=')
+                        .ok_or_else(|| anyhow!("Failed to parse form field {}", f))?;
+                    req.form.push((k.into(), v.into()));
+                }
+                "--json" => {
+                    req.json = true;
+                    req.data.push(value()?);
+                }
+                "--data-urlencode" => req.data.push(urlencode_data(&value()?)),
+                "--data-raw" => req.data.push(value()?),
+                "-d" | "--data" | "--data-ascii" => req.data.push(read_data(&value()?, true)?),
+                "--data-binary" => req.data.push(read_data(&value()?, false)?),
+                "--url" => req.url = value()?,
+                "-G" | "--get" => req.get = true,
+                "-I" | "--head" => head = true,
+                // output, transport and verbosity flags don't change the request
+                _ if takes_value => {
+                    value()?;
+                }
+                f if f.starts_with('-') && f.len() > 1 => {}
+                _ => {
+                    if !req.url.is_empty() {
+                        return Err(anyhow!("Unexpected argument {}", arg));
+                    }
+                    req.url = arg.clone();
+                }
+            }
+        }
+        if req.url.is_empty() {
+            return Err(anyhow!("No URL in curl command"));
+        }
+        if head {
+            req.method.get_or_insert(Method::HEAD);
+        }
+        Ok(req)
+    }
+
+    /// Method curl would use: explicit, or implied by the presence of a body.
+    pub fn method(&self) -> Method {
+        match &self.method {
+            Some(m) => m.clone(),
+            None if (!self.data.is_empty() && !self.get) || !self.form.is_empty() => Method::POST,
+            None => Method::GET,
+        }
+    }
+
+    fn has_header(&self, name: &str) -> bool {
+        self.headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+
+    /// Build the request on `client`.
+    pub async fn to_request(&self, client: &Client) -> Result<RequestBuilder> {
+        // like curl, assume http when no scheme is given
+        let url = if self.url.contains("://") {
+            self.url.clone()
+        } else {
+            format!("http://{}", self.url)
+        };
+        let mut url: reqwest::Url = url
+            .parse()
+            .with_context(|| format!("Failed to parse URL {}", self.url))?;
+        let data = self.data.join("&");
+        if self.get && !data.is_empty() {
+            let query = match url.query() {
+                Some(q) if !q.is_empty() => format!("{}&{}", q, data),
+                _ => data.clone(),
+            };
+            url.set_query(Some(&query));
+        }
+
+        let mut req = client.request(self.method(), url);
+        for (k, v) in self.headers.iter() {
+            req = req.header(k.as_str(), v.as_str());
+        }
+        if let Some(user) = &self.user {
+            let (name, password) = match user.split_once(':') {
+                Some((n, p)) => (n, Some(p)),
+                None => (user.as_str(), None),
+            };
+            req = req.basic_auth(name, password);
+        }
+        if !self.form.is_empty() {
+            let mut form = reqwest::multipart::Form::new();
+            for (k, v) in self.form.iter() {
+                form = match v.strip_prefix('@') {
+                    Some(path) => {
+                        form.part(k.clone(), file_part(path, &ProgressBar::hidden()).await?)
+                    }
+                    None => form.text(k.clone(), v.clone()),
+                };
+            }
+            req = req.multipart(form);
+        } else if !self.get && !self.data.is_empty() {
+            if self.json {
+                if !self.has_header("content-type") {
+                    req = req.header(header::CONTENT_TYPE, "application/json");
+                }
+                if !self.has_header("accept") {
+                    req = req.header(header::ACCEPT, "application/json");
+                }
+            } else if !self.has_header("content-type") {
+                req = req.header(header::CONTENT_TYPE, "application/x-www-form-urlencoded");
+            }
+            req = req.body(data);
+        }
+        Ok(req)
+    }
+}
+
+/// `--name=value` becomes (`--name`, `value`), `-XPOST` becomes (`-X`, `POST`).
+fn split_flag(arg: &str) -> (String, Option<String>) {
+    if let Some(long) = arg.strip_prefix("--") {
+        return match long.split_once('=') {
+            Some((k, v)) => (format!("--{}", k), Some(v.into())),
+            None => (arg.into(), None),
+        };
+    }
+    if arg.starts_with('-') && arg.len() > 2 {
+        let short = &arg[..2];
+        if VALUE_FLAGS.iter().any(|(s, _)| *s == short) {
+            return (short.into(), Some(arg[2..].into()));
+        }
+    }
+    (arg.into(), None)
+}
+
+/// `-d @file` reads the body from a file; plain `-d` drops its newlines.
+fn read_data(value: &str, strip_newlines: bool) -> Result<String> {
+    let data = match value.strip_prefix('@') {
+        Some(path) => {
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?
+        }
+        None => return Ok(value.into()),
+    };
+    Ok(if strip_newlines {
+        data.replace(['\r', '\n'], "")
+    } else {
+        data
+    })
+}
+
+/// `--data-urlencode name=value` encodes only the value part.
+fn urlencode_data(value: &str) -> String {
+    let encode = |s: &str| form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+    match value.split_once('=') {
+        Some((name, v)) => format!("{}={}", name, encode(v)),
+        None => encode(value),
+    }
+}
+
+/// Split a command line into words the way a POSIX shell would, honouring
+/// single and double quotes, backslash escapes and line continuations.
+pub fn split_words(s: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(anyhow!("Unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => return Err(anyhow!("Trailing backslash")),
+            },
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_works() {
+        assert_eq!(quote("https://x.org/a"), "https://x.org/a");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote(""), "''");
+    }
+
+    #[test]
+    fn curl_works() {
+        let mut curl = Curl::new(Method::GET, "http://x.org/?a=1&b=2");
+        curl.header("Accept", "*/*");
+        assert_eq!(
+            curl.to_string(),
+            "curl 'http://x.org/?a=1&b=2' \\\n  -H 'Accept: */*'"
+        );
+
+        let mut curl = Curl::new(Method::POST, "http://x.org/");
+        curl.data(br#"{"a":"1"}"#);
+        assert_eq!(
+            curl.to_string(),
+            "curl http://x.org/ \\\n  --data-raw '{\"a\":\"1\"}'"
+        );
+
+        let mut curl = Curl::new(Method::PUT, "http://x.org/");
+        curl.data_file("big.bin");
+        assert_eq!(
+            curl.to_string(),
+            "curl -X PUT http://x.org/ \\\n  --data-binary @big.bin"
+        );
+    }
+
+    fn words(s: &str) -> Vec<String> {
+        vec![s.to_string()]
+    }
+
+    #[test]
+    fn split_words_works() {
+        assert_eq!(
+            split_words(
+                r#"curl -H 'A: b c' "x\"y" a\ b \
+  -d z"#
+            )
+            .unwrap(),
+            vec!["curl", "-H", "A: b c", "x\"y", "a b", "-d", "z"]
+        );
+        assert_eq!(split_words("''").unwrap(), vec![""]);
+        assert!(split_words("'open").is_err());
+    }
+
+    #[test]
+    fn parse_curl_works() {
+        let req = CurlRequest::parse(&words(
+            "curl -sSL -XPUT 'https://x.org/api' -H 'Accept: application/json' --data-raw '{\"a\":1}' -u me:pw --compressed",
+        ))
+        .unwrap();
+        assert_eq!(req.method(), Method::PUT);
+        assert_eq!(req.url, "https://x.org/api");
+        assert_eq!(
+            req.headers,
+            vec![("Accept".to_string(), "application/json".to_string())]
+        );
+        assert_eq!(req.data, vec![r#"{"a":1}"#]);
+        assert_eq!(req.user.as_deref(), Some("me:pw"));
+
+        let req = CurlRequest::parse(&words("curl x.org -d a=1 --data-urlencode 'q=a b'")).unwrap();
+        assert_eq!(req.method(), Method::POST);
+        assert_eq!(req.data, vec!["a=1", "q=a+b"]);
+
+        let req = CurlRequest::parse(&words("curl -G x.org -d a=1")).unwrap();
+        assert_eq!(req.method(), Method::GET);
+
+        let req = CurlRequest::parse(&words("curl -I --url=x.org -o /dev/null")).unwrap();
+        assert_eq!((req.method(), req.url.as_str()), (Method::HEAD, "x.org"));
+
+        // values of transport flags are not the URL
+        let req = CurlRequest::parse(&words(
+            "curl --connect-timeout 5 -x http://proxy:3128 -w '%{http_code}' -c jar.txt --retry 3 \
+             --cacert ca.pem -E me.pem --resolve x.org:443:10.0.0.1 -r 0-99 https://x.org/f",
+        ))
+        .unwrap();
+        assert_eq!(req.url, "https://x.org/f");
+        assert_eq!(
+            req.headers,
+            vec![("Range".to_string(), "bytes=0-99".to_string())]
+        );
+        assert!(CurlRequest::parse(&words("curl -T f.txt https://x.org/")).is_err());
+
+        assert!(CurlRequest::parse(&words("curl -H")).is_err());
+        assert!(CurlRequest::parse(&words("curl -s")).is_err());
+    }
+}
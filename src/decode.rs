@@ -0,0 +1,550 @@
+use std::{collections::HashMap, path::PathBuf, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mime::Mime;
+use serde_json::{Map, Number, Value};
+
+/// A binary body format to show as JSON, chosen with `--decode`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Format {
+    Msgpack,
+    /// Without a schema fields are named by number and nested messages are
+    /// guessed at.
+    Protobuf(Option<Schema>),
+}
+
+/// `descriptor.bin:package.Message`, a `FileDescriptorSet` as written by
+/// `protoc --descriptor_set_out` and the message type of the body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub descriptors: PathBuf,
+    pub message: String,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "msgpack" => Ok(Self::Msgpack),
+            None if s == "protobuf" => Ok(Self::Protobuf(None)),
+            Some(("protobuf", rest)) => {
+                let (path, message) = rest.rsplit_once(':').ok_or_else(|| {
+                    anyhow!(format!(
+                        "Failed to parse {}: expected protobuf:<descriptor.bin>:<MessageType>",
+                        s
+                    ))
+                })?;
+                Ok(Self::Protobuf(Some(Schema {
+                    descriptors: path.into(),
+                    message: message.trim_start_matches('.').to_string(),
+                })))
+            }
+            _ => Err(anyhow!(format!(
+                "Failed to parse {}: expected msgpack, protobuf or protobuf:<descriptor.bin>:<MessageType>",
+                s
+            ))),
+        }
+    }
+}
+
+impl Format {
+    /// The format a Content-Type names, for bodies shown without `--decode`.
+    pub fn for_mime(m: &Mime) -> Option<Self> {
+        match m.subtype().as_str() {
+            "msgpack" | "x-msgpack" | "vnd.msgpack" => Some(Self::Msgpack),
+            "protobuf" | "x-protobuf" | "vnd.google.protobuf" => Some(Self::Protobuf(None)),
+            _ => None,
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value> {
+        match self {
+            Self::Msgpack => msgpack(bytes),
+            Self::Protobuf(None) => {
+                protobuf_raw(bytes).ok_or_else(|| anyhow!("Failed to decode protobuf body"))
+            }
+            Self::Protobuf(Some(schema)) => {
+                let set = std::fs::read(&schema.descriptors)
+                    .with_context(|| format!("Failed to read {}", schema.descriptors.display()))?;
+                let messages = descriptors(&set).ok_or_else(|| {
+                    anyhow!(format!(
+                        "Failed to parse {}: not a FileDescriptorSet",
+                        schema.descriptors.display()
+                    ))
+                })?;
+                let name = format!(".{}", schema.message);
+                if !messages.contains_key(&name) {
+                    return Err(anyhow!(format!(
+                        "Failed to decode protobuf body: {} is not in {}",
+                        schema.message,
+                        schema.descriptors.display()
+                    )));
+                }
+                protobuf(bytes, &name, &messages)
+                    .ok_or_else(|| anyhow!(format!("Failed to decode {} body", schema.message)))
+            }
+        }
+    }
+}
+
+fn binary(bytes: &[u8]) -> Value {
+    Value::String(STANDARD.encode(bytes))
+}
+
+// msgpack
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let end = self
+            .at
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("Failed to decode msgpack body: unexpected end"))?;
+        let taken = &self.bytes[self.at..end];
+        self.at = end;
+        Ok(taken)
+    }
+
+    fn uint(&mut self, n: usize) -> Result<u64> {
+        Ok(self
+            .take(n)?
+            .iter()
+            .fold(0, |acc, &b| (acc << 8) | u64::from(b)))
+    }
+
+    fn int(&mut self, n: usize) -> Result<i64> {
+        let bits = 64 - 8 * n as u32;
+        // sign extend from n bytes
+        Ok(((self.uint(n)? << bits) as i64) >> bits)
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let b = self.take(1)?[0];
+        Ok(match b {
+            0x00..=0x7f => Value::from(b),
+            0x80..=0x8f => self.map(usize::from(b & 0x0f))?,
+            0x90..=0x9f => self.array(usize::from(b & 0x0f))?,
+            0xa0..=0xbf => self.str(usize::from(b & 0x1f))?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let n = self.uint(1 << (b - 0xc4))? as usize;
+                binary(self.take(n)?)
+            }
+            0xc7..=0xc9 => {
+                let n = self.uint(1 << (b - 0xc7))? as usize;
+                self.ext(n)?
+            }
+            0xca => float(f64::from(f32::from_bits(self.uint(4)? as u32))),
+            0xcb => float(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Value::from(self.uint(1 << (b - 0xcc))?),
+            0xd0..=0xd3 => Value::from(self.int(1 << (b - 0xd0))?),
+            0xd4..=0xd8 => self.ext(1 << (b - 0xd4))?,
+            0xd9..=0xdb => {
+                let n = self.uint(1 << (b - 0xd9))? as usize;
+                self.str(n)?
+            }
+            0xdc | 0xdd => {
+                let n = self.uint(2 << (b - 0xdc))? as usize;
+                self.array(n)?
+            }
+            0xde | 0xdf => {
+                let n = self.uint(2 << (b - 0xde))? as usize;
+                self.map(n)?
+            }
+            0xe0..=0xff => Value::from(b as i8),
+            0xc1 => return Err(anyhow!("Failed to decode msgpack body: reserved byte 0xc1")),
+        })
+    }
+
+    fn str(&mut self, n: usize) -> Result<Value> {
+        let bytes = self.take(n)?;
+        Ok(std::str::from_utf8(bytes).map_or_else(|_| binary(bytes), Value::from))
+    }
+
+    fn ext(&mut self, n: usize) -> Result<Value> {
+        let kind = self.take(1)?[0] as i8;
+        let data = self.take(n)?;
+        Ok(serde_json::json!({"ext": kind, "data": binary(data)}))
+    }
+
+    fn array(&mut self, n: usize) -> Result<Value> {
+        (0..n).map(|_| self.value()).collect()
+    }
+
+    fn map(&mut self, n: usize) -> Result<Value> {
+        let mut map = Map::new();
+        for _ in 0..n {
+            let key = match self.value()? {
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            map.insert(key, self.value()?);
+        }
+        Ok(Value::Object(map))
+    }
+}
+
+fn float(f: f64) -> Value {
+    Number::from_f64(f).map_or_else(|| Value::String(f.to_string()), Value::Number)
+}
+
+/// A MessagePack body as JSON: binary as base64 and extension types as
+/// `{"ext": type, "data": base64}`. Bodies of several values give an array.
+pub fn msgpack(bytes: &[u8]) -> Result<Value> {
+    let mut reader = Reader { bytes, at: 0 };
+    let mut values = Vec::new();
+    while reader.at < bytes.len() {
+        values.push(reader.value()?);
+    }
+    Ok(match values.len() {
+        1 => values.remove(0),
+        _ => Value::Array(values),
+    })
+}
+
+// protobuf
+
+enum Wire<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn varint(bytes: &[u8], at: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *bytes.get(*at)?;
+        *at += 1;
+        value |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// The fields of a message in order, or `None` when it is not valid
+/// protobuf.
+fn fields(bytes: &[u8]) -> Option<Vec<(u64, Wire<'_>)>> {
+    let mut at = 0;
+    let mut fields = Vec::new();
+    while at < bytes.len() {
+        let tag = varint(bytes, &mut at)?;
+        let (number, wire) = (tag >> 3, tag & 7);
+        if number == 0 {
+            return None;
+        }
+        let value = match wire {
+            0 => Wire::Varint(varint(bytes, &mut at)?),
+            1 => {
+                let b = bytes.get(at..at + 8)?;
+                at += 8;
+                Wire::Fixed64(u64::from_le_bytes(b.try_into().ok()?))
+            }
+            2 => {
+                let n = usize::try_from(varint(bytes, &mut at)?).ok()?;
+                let b = bytes.get(at..at.checked_add(n)?)?;
+                at += n;
+                Wire::Bytes(b)
+            }
+            5 => {
+                let b = bytes.get(at..at + 4)?;
+                at += 4;
+                Wire::Fixed32(u32::from_le_bytes(b.try_into().ok()?))
+            }
+            // groups are long deprecated
+            _ => return None,
+        };
+        fields.push((number, value));
+    }
+    Some(fields)
+}
+
+/// Add `value` under `key`, turning repeated fields into arrays.
+fn insert(map: &mut Map<String, Value>, key: String, value: Value, repeated: bool) {
+    match map.get_mut(&key) {
+        Some(Value::Array(items)) if repeated => items.push(value),
+        Some(existing) if !repeated => {
+            let first = existing.take();
+            *existing = Value::Array(vec![first, value]);
+        }
+        _ if repeated => {
+            map.insert(key, Value::Array(vec![value]));
+        }
+        _ => {
+            map.insert(key, value);
+        }
+    }
+}
+
+/// A message without its schema: fields by number, and length delimited
+/// ones as a nested message if they parse as one, else text, else base64.
+fn protobuf_raw(bytes: &[u8]) -> Option<Value> {
+    let mut map = Map::new();
+    for (number, wire) in fields(bytes)? {
+        let value = match wire {
+            Wire::Varint(v) | Wire::Fixed64(v) => Value::from(v),
+            Wire::Fixed32(v) => Value::from(v),
+            Wire::Bytes(b) => match std::str::from_utf8(b) {
+                Ok(s) if !s.is_empty() && !s.chars().any(|c| c.is_control() && c != '\n') => {
+                    Value::from(s)
+                }
+                _ => protobuf_raw(b)
+                    .filter(|v| !b.is_empty() && v != &Value::Object(Map::new()))
+                    .unwrap_or_else(|| binary(b)),
+            },
+        };
+        insert(&mut map, number.to_string(), value, false);
+    }
+    Some(Value::Object(map))
+}
+
+#[derive(Debug, Default)]
+struct Field {
+    name: String,
+    kind: u64,
+    type_name: String,
+    repeated: bool,
+}
+
+/// Message types by full name (`.package.Outer.Inner`), with their fields
+/// by number.
+type Messages = HashMap<String, HashMap<u64, Field>>;
+
+fn text(wire: &Wire) -> String {
+    match wire {
+        Wire::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
+        _ => String::new(),
+    }
+}
+
+/// The message types in a `FileDescriptorSet`.
+fn descriptors(set: &[u8]) -> Option<Messages> {
+    let mut messages = Messages::new();
+    for (number, file) in fields(set)? {
+        let Wire::Bytes(file) = file else { continue };
+        if number != 1 {
+            continue;
+        }
+        let file = fields(file)?;
+        let package = file
+            .iter()
+            .find(|(n, _)| *n == 2)
+            .map(|(_, w)| text(w))
+            .unwrap_or_default();
+        let prefix = if package.is_empty() {
+            String::new()
+        } else {
+            format!(".{}", package)
+        };
+        for (n, message) in file.iter() {
+            if let (4, Wire::Bytes(message)) = (n, message) {
+                message_type(message, &prefix, &mut messages)?;
+            }
+        }
+    }
+    Some(messages)
+}
+
+/// Add a `DescriptorProto` and the types nested in it.
+fn message_type(bytes: &[u8], prefix: &str, messages: &mut Messages) -> Option<()> {
+    let message = fields(bytes)?;
+    let name = message
+        .iter()
+        .find(|(n, _)| *n == 1)
+        .map(|(_, w)| format!("{}.{}", prefix, text(w)))?;
+    let mut by_number = HashMap::new();
+    for (n, wire) in message.iter() {
+        match (n, wire) {
+            (2, Wire::Bytes(field)) => {
+                let mut f = Field::default();
+                let mut number = 0;
+                for (n, wire) in fields(field)? {
+                    match (n, &wire) {
+                        (1, _) => f.name = text(&wire),
+                        (3, Wire::Varint(v)) => number = *v,
+                        (4, Wire::Varint(v)) => f.repeated = *v == 3,
+                        (5, Wire::Varint(v)) => f.kind = *v,
+                        (6, _) => f.type_name = text(&wire),
+                        _ => {}
+                    }
+                }
+                by_number.insert(number, f);
+            }
+            (3, Wire::Bytes(nested)) => message_type(nested, &name, messages)?,
+            _ => {}
+        }
+    }
+    messages.insert(name, by_number);
+    Some(())
+}
+
+/// One scalar of a field declared as `kind`.
+fn scalar(kind: u64, wire: &Wire) -> Option<Value> {
+    Some(match (kind, wire) {
+        (1, Wire::Fixed64(v)) => float(f64::from_bits(*v)),
+        (2, Wire::Fixed32(v)) => float(f64::from(f32::from_bits(*v))),
+        (3, Wire::Varint(v)) => Value::from(*v as i64),
+        (4, Wire::Varint(v)) => Value::from(*v),
+        (5 | 14, Wire::Varint(v)) => Value::from(*v as i32),
+        (6, Wire::Fixed64(v)) => Value::from(*v),
+        (7, Wire::Fixed32(v)) => Value::from(*v),
+        (8, Wire::Varint(v)) => Value::Bool(*v != 0),
+        (13, Wire::Varint(v)) => Value::from(*v as u32),
+        (15, Wire::Fixed32(v)) => Value::from(*v as i32),
+        (16, Wire::Fixed64(v)) => Value::from(*v as i64),
+        // zigzag
+        (17, Wire::Varint(v)) => Value::from(((*v >> 1) as i32) ^ -((*v & 1) as i32)),
+        (18, Wire::Varint(v)) => Value::from(((*v >> 1) as i64) ^ -((*v & 1) as i64)),
+        _ => return None,
+    })
+}
+
+/// A value of a packed repeated scalar field.
+fn packed(kind: u64, bytes: &[u8]) -> Option<Vec<Value>> {
+    let mut values = Vec::new();
+    let mut at = 0;
+    while at < bytes.len() {
+        let wire = match kind {
+            1 | 6 | 16 => {
+                let b = bytes.get(at..at + 8)?;
+                at += 8;
+                Wire::Fixed64(u64::from_le_bytes(b.try_into().ok()?))
+            }
+            2 | 7 | 15 => {
+                let b = bytes.get(at..at + 4)?;
+                at += 4;
+                Wire::Fixed32(u32::from_le_bytes(b.try_into().ok()?))
+            }
+            _ => Wire::Varint(varint(bytes, &mut at)?),
+        };
+        values.push(scalar(kind, &wire)?);
+    }
+    Some(values)
+}
+
+/// A message of type `name` with fields by name.
+fn protobuf(bytes: &[u8], name: &str, messages: &Messages) -> Option<Value> {
+    let declared = messages.get(name)?;
+    let mut map = Map::new();
+    for (number, wire) in fields(bytes)? {
+        let Some(field) = declared.get(&number) else {
+            // unknown fields, e.g. from a newer schema
+            insert(
+                &mut map,
+                number.to_string(),
+                protobuf_raw_field(&wire),
+                false,
+            );
+            continue;
+        };
+        let key = field.name.clone();
+        match (field.kind, &wire) {
+            (9, Wire::Bytes(b)) => insert(
+                &mut map,
+                key,
+                Value::from(String::from_utf8_lossy(b)),
+                field.repeated,
+            ),
+            (12, Wire::Bytes(b)) => insert(&mut map, key, binary(b), field.repeated),
+            (11, Wire::Bytes(b)) => insert(
+                &mut map,
+                key,
+                protobuf(b, &field.type_name, messages)?,
+                field.repeated,
+            ),
+            (_, Wire::Bytes(b)) => {
+                for value in packed(field.kind, b)? {
+                    insert(&mut map, key.clone(), value, true);
+                }
+            }
+            (kind, wire) => insert(&mut map, key, scalar(kind, wire)?, field.repeated),
+        }
+    }
+    Some(Value::Object(map))
+}
+
+fn protobuf_raw_field(wire: &Wire) -> Value {
+    match wire {
+        Wire::Varint(v) | Wire::Fixed64(v) => Value::from(*v),
+        Wire::Fixed32(v) => Value::from(*v),
+        Wire::Bytes(b) => protobuf_raw(b).unwrap_or_else(|| binary(b)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn msgpack_works() {
+        // {"compact": true, "schema": 0, "n": -3, "f": 1.5, "list": [1, "a"]}
+        let bytes =
+            b"\x85\xa7compact\xc3\xa6schema\x00\xa1n\xfd\xa1f\xcb\x3f\xf8\x00\x00\x00\x00\x00\x00\
+            \xa4list\x92\x01\xa1a";
+        assert_eq!(
+            msgpack(bytes).unwrap(),
+            json!({"compact": true, "schema": 0, "n": -3, "f": 1.5, "list": [1, "a"]})
+        );
+        assert_eq!(msgpack(b"\xd1\xff\x00").unwrap(), json!(-256));
+        assert!(msgpack(b"\x92\x01").is_err());
+    }
+
+    #[test]
+    fn protobuf_works() {
+        // message Test { int32 id = 1; string name = 2; repeated sint32 d = 3 [packed]; }
+        let body = b"\x08\x96\x01\x12\x03abc\x1a\x02\x03\x04";
+        assert_eq!(
+            protobuf_raw(body).unwrap(),
+            json!({"1": 150, "2": "abc", "3": "AwQ="})
+        );
+
+        // a FileDescriptorSet with file "t.proto", package "p" and Test
+        let field = |name: &[u8], number: u8, label: u8, kind: u8| {
+            let mut f = vec![0x0a, name.len() as u8];
+            f.extend_from_slice(name);
+            f.extend_from_slice(&[0x18, number, 0x20, label, 0x28, kind]);
+            f
+        };
+        let mut message = b"\x0a\x04Test".to_vec();
+        for f in [
+            field(b"id", 1, 1, 5),
+            field(b"name", 2, 1, 9),
+            field(b"d", 3, 3, 17),
+        ] {
+            message.push(0x12);
+            message.push(f.len() as u8);
+            message.extend(f);
+        }
+        let mut file = b"\x0a\x07t.proto\x12\x01p\x22".to_vec();
+        file.push(message.len() as u8);
+        file.extend(message);
+        let mut set = vec![0x0a, file.len() as u8];
+        set.extend(file);
+
+        let messages = descriptors(&set).unwrap();
+        assert_eq!(
+            protobuf(body, ".p.Test", &messages).unwrap(),
+            json!({"id": 150, "name": "abc", "d": [-2, 2]})
+        );
+        assert_eq!(
+            "protobuf:t.bin:.p.Test".parse::<Format>().unwrap(),
+            Format::Protobuf(Some(Schema {
+                descriptors: "t.bin".into(),
+                message: "p.Test".into()
+            }))
+        );
+        assert!("avro".parse::<Format>().is_err());
+    }
+}
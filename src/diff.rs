@@ -0,0 +1,96 @@
+use colored::Colorize;
+use mime::Mime;
+use reqwest::{header::HeaderMap, StatusCode};
+use similar::TextDiff;
+
+use crate::body;
+
+/// A body as text for diffing: JSON pretty printed with sorted keys, so
+/// that neither key order nor formatting shows up as a change.
+pub fn normalize_body(bytes: &[u8], mime: Option<&Mime>) -> String {
+    let mut text = body::decode_text(bytes, mime).into_owned();
+    let json = mime.and_then(crate::syntax_for) == Some("json");
+    if json || (mime.is_none() && serde_json::from_str::<serde_json::Value>(&text).is_ok()) {
+        text = crate::sort_json(text);
+    }
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text
+}
+
+/// The status, the headers in `names` and the normalized body.
+pub fn comparable(status: StatusCode, headers: &HeaderMap, names: &[String], body: &str) -> String {
+    let mut text = format!("HTTP {}\n", status);
+    for name in names {
+        for value in headers.get_all(name.as_str()) {
+            text.push_str(&format!(
+                "{}: {}\n",
+                name.to_ascii_lowercase(),
+                String::from_utf8_lossy(value.as_bytes())
+            ));
+        }
+    }
+    text.push('\n');
+    text.push_str(body);
+    text
+}
+
+/// Unified line diff of `old` against `new`, or `None` when they are equal.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .context_radius(3)
+            .header(old_name, new_name)
+            .to_string(),
+    )
+}
+
+/// Print a unified diff with added lines in green and removed ones in red.
+pub fn print_unified(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_works() {
+        assert_eq!(unified("a\n", "a\n", "x", "y"), None);
+        let diff = unified("a\nb\n", "a\nc\n", "x", "y").unwrap();
+        assert_eq!(diff, "--- x\n+++ y\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n");
+    }
+
+    #[test]
+    fn normalize_body_works() {
+        let json: Mime = "application/json".parse().unwrap();
+        assert_eq!(
+            normalize_body(br#"{"b":1,"a":2}"#, Some(&json)),
+            normalize_body(b"{\n  \"a\": 2, \"b\": 1}", None)
+        );
+        assert_eq!(normalize_body(b"plain", Some(&mime::TEXT_PLAIN)), "plain\n");
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "text/plain".parse().unwrap());
+        assert_eq!(
+            comparable(StatusCode::OK, &headers, &["Content-Type".into()], "x\n"),
+            "HTTP 200 OK\ncontent-type: text/plain\n\nx\n"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use colored::Colorize;
+
+use crate::{archive, chunked::xml_text};
+
+/// What a document says about itself, enough to tell it from another.
+#[derive(Debug, PartialEq)]
+pub struct Metadata {
+    pub kind: &'static str,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Metadata of a PDF or an Office Open XML (docx, xlsx, pptx) body.
+pub fn inspect(bytes: &[u8]) -> Option<Metadata> {
+    if bytes.starts_with(b"%PDF-") {
+        return Some(pdf(bytes));
+    }
+    office(bytes)
+}
+
+/// The `/Key (value)` of a PDF dictionary: a literal string with escapes or
+/// a hex string, UTF-16 when it starts with a byte order mark.
+fn pdf_string(text: &str, key: &str) -> Option<String> {
+    let start = text.find(key)? + key.len();
+    let rest = text[start..].trim_start();
+    let bytes: Vec<u8> = if let Some(rest) = rest.strip_prefix('(') {
+        let mut out = Vec::new();
+        let mut depth = 0;
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next()? {
+                    'n' => out.push(b'\n'),
+                    'r' => out.push(b'\r'),
+                    't' => out.push(b'\t'),
+                    c => out.extend(c.to_string().bytes()),
+                },
+                '(' => {
+                    depth += 1;
+                    out.push(b'(');
+                }
+                ')' if depth == 0 => break,
+                ')' => {
+                    depth -= 1;
+                    out.push(b')');
+                }
+                // PDFDocEncoding matches Latin-1 where it matters
+                c => out.push(u8::try_from(u32::from(c)).unwrap_or(b'?')),
+            }
+        }
+        out
+    } else if let Some(rest) = rest.strip_prefix('<') {
+        let hex: String = rest
+            .chars()
+            .take_while(|&c| c != '>')
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        (0..hex.len() / 2)
+            .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16))
+            .collect::<Result<_, _>>()
+            .ok()?
+    } else {
+        return None;
+    };
+    let s = match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => bytes.iter().map(|&b| char::from(b)).collect(),
+    };
+    Some(s).filter(|s| !s.trim().is_empty())
+}
+
+fn pdf(bytes: &[u8]) -> Metadata {
+    // Latin-1, so that byte offsets and binary streams survive
+    let text: String = bytes.iter().map(|&b| char::from(b)).collect();
+    let version = text[5..].split_whitespace().next().unwrap_or_default();
+    let mut fields = vec![("Version", version.to_string())];
+    // the root of the page tree counts them all, so the largest count
+    let pages = text
+        .match_indices("/Type")
+        .filter(|(i, _)| text[i + 5..].trim_start().starts_with("/Pages"))
+        .filter_map(|(i, _)| {
+            let end = text[i..].find(">>").map_or(text.len(), |e| i + e);
+            let dict = &text[text[..i].rfind("<<").unwrap_or(i)..end];
+            let count = dict.split("/Count").nth(1)?;
+            count.split_whitespace().next()?.parse::<u64>().ok()
+        })
+        .max();
+    if let Some(pages) = pages {
+        fields.push(("Pages", pages.to_string()));
+    }
+    for (key, name) in [
+        ("/Title", "Title"),
+        ("/Author", "Author"),
+        ("/Producer", "Producer"),
+    ] {
+        if let Some(value) = pdf_string(&text, key) {
+            fields.push((name, value));
+        }
+    }
+    if text.contains("/Encrypt") {
+        fields.push(("Encrypted", "yes".into()));
+    }
+    Metadata {
+        kind: "PDF",
+        fields,
+    }
+}
+
+fn office(bytes: &[u8]) -> Option<Metadata> {
+    let archive = archive::open(bytes)?;
+    let file = |name: &str| {
+        let entry = archive.entries.iter().find(|e| e.name == name)?;
+        String::from_utf8(entry.contents().ok()?).ok()
+    };
+    file("[Content_Types].xml")?;
+    let (kind, count) = if archive
+        .entries
+        .iter()
+        .any(|e| e.name == "word/document.xml")
+    {
+        ("Word document", Some("Pages"))
+    } else if archive.entries.iter().any(|e| e.name == "xl/workbook.xml") {
+        ("Excel workbook", None)
+    } else if archive
+        .entries
+        .iter()
+        .any(|e| e.name == "ppt/presentation.xml")
+    {
+        ("PowerPoint presentation", Some("Slides"))
+    } else {
+        return None;
+    };
+    let mut fields = Vec::new();
+    let app = file("docProps/app.xml").unwrap_or_default();
+    if let Some(tag) = count {
+        if let Some(n) = xml_text(&app, tag) {
+            fields.push((tag, n.to_string()));
+        }
+    }
+    if let Some(workbook) = file("xl/workbook.xml") {
+        fields.push(("Sheets", workbook.matches("<sheet ").count().to_string()));
+    }
+    let core = file("docProps/core.xml").unwrap_or_default();
+    for (tag, name) in [("dc:title", "Title"), ("dc:creator", "Author")] {
+        if let Some(value) = xml_text(&core, tag).filter(|v| !v.trim().is_empty()) {
+            fields.push((name, value.to_string()));
+        }
+    }
+    if let Some(app_name) = xml_text(&app, "Application") {
+        fields.push(("Application", app_name.to_string()));
+    }
+    Some(Metadata { kind, fields })
+}
+
+/// The metadata in place of the body, `size` bytes of it.
+pub fn print(meta: &Metadata, size: usize) {
+    println!("{} {} bytes", format!("{},", meta.kind).bold(), size);
+    let width = meta
+        .fields
+        .iter()
+        .map(|(k, _)| k.len() + 1)
+        .max()
+        .unwrap_or(0);
+    for (key, value) in meta.fields.iter() {
+        println!("  {:<width$} {}", format!("{}:", key), value, width = width);
+    }
+    println!(
+        "{}",
+        "Binary body not shown; save it with --download [--output <file>]".dimmed()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pdf_works() {
+        let pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n\
+            1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj\n\
+            2 0 obj << /Type /Pages /Kids [3 0 R 4 0 R] /Count 2 >> endobj\n\
+            3 0 obj << /Type /Page /Parent 2 0 R >> endobj\n\
+            5 0 obj << /Title (Q3 \\(draft\\) report) /Author <FEFF004100640061> >> endobj\n\
+            trailer << /Root 1 0 R /Info 5 0 R >>\n%%EOF";
+        let meta = inspect(pdf).unwrap();
+        assert_eq!(
+            meta.fields,
+            [
+                ("Version", "1.7".to_string()),
+                ("Pages", "2".to_string()),
+                ("Title", "Q3 (draft) report".to_string()),
+                ("Author", "Ada".to_string()),
+            ]
+        );
+        assert!(inspect(b"PK\x03\x04 not really").is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use futures_util::StreamExt;
+use reqwest::{
+    header::{self, HeaderMap},
+    Response, Url,
+};
+
+use crate::{exchange, stats::format_duration, upload};
+
+/// File name for a download: from Content-Disposition, else the last path
+/// segment of the URL, else `index`. Directories are never taken over.
+pub fn file_name(url: &Url, headers: &HeaderMap) -> String {
+    let disposition = headers
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            v.split(';').find_map(|p| {
+                let value = p.trim().strip_prefix("filename=")?;
+                Some(value.trim_matches('"').to_string())
+            })
+        });
+    let from_url = url
+        .path_segments()
+        .and_then(|mut s| s.next_back())
+        .filter(|s| !s.is_empty())
+        .map(String::from);
+    let name = disposition.or(from_url).unwrap_or_else(|| "index".into());
+    // no paths from the server
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    match name {
+        "" | "." | ".." => "index".into(),
+        name => name.to_string(),
+    }
+}
+
+/// Create `name`, or `name-1`, `name-2`... when it exists, like HTTPie.
+fn create_unique(name: &str) -> Result<(File, PathBuf)> {
+    for n in 0.. {
+        let path = PathBuf::from(match n {
+            0 => name.to_string(),
+            n => format!("{}-{}", name, n),
+        });
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {}", path.display())),
+        }
+    }
+    unreachable!()
+}
+
+/// Stream the body of `resp` into `output`, or a new file named after the
+/// response. Returns where it went and how many bytes were written.
+pub async fn save(resp: Response, output: Option<&Path>, progress: bool) -> Result<(PathBuf, u64)> {
+    if !resp.status().is_success() {
+        return Err(anyhow!(format!(
+            "Failed to download {}: the server answered {}",
+            resp.url(),
+            resp.status()
+        )));
+    }
+    let url = resp.url().clone();
+    exchange::check_size(&url, resp.content_length().unwrap_or(0))?;
+    let (mut file, path) = match output {
+        Some(path) => (
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+            path.to_path_buf(),
+        ),
+        None => create_unique(&file_name(resp.url(), resp.headers()))?,
+    };
+    let pb = progress.then(|| upload::progress_bar(resp.content_length().unwrap_or(0)));
+    let mut written = 0;
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written += chunk.len() as u64;
+        if let Err(e) = exchange::check_size(&url, written) {
+            // a partial file would pass for the real thing
+            drop(file);
+            std::fs::remove_file(&path).ok();
+            return Err(e);
+        }
+        if let Some(pb) = &pb {
+            pb.set_position(written);
+        }
+    }
+    if let Some(pb) = pb {
+        pb.finish_and_clear();
+    }
+    Ok((path, written))
+}
+
+/// Download `resp` with a progress bar and say where it went.
+pub async fn run(resp: Response, output: Option<&Path>) -> Result<()> {
+    let start = Instant::now();
+    let (path, written) = save(resp, output, true).await?;
+    eprintln!(
+        "{} {} bytes to {} in {}",
+        "Downloaded".green().bold(),
+        written,
+        path.display(),
+        format_duration(start.elapsed())
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_name_works() {
+        let url: Url = "http://a.b/files/report.pdf?x=1".parse().unwrap();
+        assert_eq!(file_name(&url, &HeaderMap::new()), "report.pdf");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"../../etc/passwd\"".parse().unwrap(),
+        );
+        assert_eq!(file_name(&url, &headers), "passwd");
+        let root: Url = "http://a.b/".parse().unwrap();
+        assert_eq!(file_name(&root, &HeaderMap::new()), "index");
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use hyper::{Body, Request};
+use reqwest::{
+    header::{self, HeaderMap},
+    Response, Url,
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{self, pki_types::ServerName, RootCertStore},
+    TlsConnector,
+};
+
+use crate::{conn, stats::format_duration};
+
+/// What happened to the early data on the resumed connection.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EarlyData {
+    /// The server took the request from the first flight.
+    Accepted,
+    /// The server refused it, so it was sent again after the handshake.
+    Rejected,
+    /// The first connection left no ticket allowing early data.
+    NotOffered,
+}
+
+/// One request over its own TLS connection.
+struct Attempt {
+    response: hyper::Response<Vec<u8>>,
+    early: Option<bool>,
+    elapsed: Duration,
+}
+
+fn connector() -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_native_certs::load_native_certs().certs {
+        // unusable system certificates are skipped, as browsers do
+        roots.add(cert).ok();
+    }
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let mut config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_protocol_versions(&[&rustls::version::TLS13])?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    config.enable_early_data = true;
+    Ok(TlsConnector::from(Arc::new(config)).early_data(true))
+}
+
+async fn attempt(tls: &TlsConnector, url: &Url, headers: &HeaderMap) -> Result<Attempt> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!(format!("Failed to connect: no host in {}", url)))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let start = Instant::now();
+    let tcp = TcpStream::connect((host, port))
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    tcp.set_nodelay(true)?;
+    let name = ServerName::try_from(host.to_string())?;
+    let stream = tls
+        .connect(name, tcp)
+        .await
+        .with_context(|| format!("Failed to set up TLS with {}", host))?;
+    // the connector hands the stream over mid-handshake when it can send early data
+    let sends_early = stream.get_ref().1.is_handshaking();
+
+    let mut req = Request::get(conn::target(url));
+    *req.headers_mut().unwrap() = headers.clone();
+    let req = req
+        .header(header::HOST, conn::authority(url))
+        .body(Body::empty())?;
+    let (response, stream) = conn::exchange(stream, req).await?;
+    let accepted = stream.get_ref().1.is_early_data_accepted();
+    Ok(Attempt {
+        response,
+        early: sends_early.then_some(accepted),
+        elapsed: start.elapsed(),
+    })
+}
+
+/// GET `url` with the request sent as TLS 1.3 early data (0-RTT). Early data
+/// needs a session ticket, so a first connection does the request normally
+/// and a second one resumes it. As GET is idempotent, a replay of the early
+/// data by an attacker does no harm.
+pub async fn fetch(url: &str, headers: &HeaderMap) -> Result<(Response, EarlyData)> {
+    let url: Url = url.parse()?;
+    if url.scheme() != "https" {
+        return Err(anyhow!(format!(
+            "Failed to send early data: {} is not an https URL",
+            url
+        )));
+    }
+    let tls = connector()?;
+    let first = attempt(&tls, &url, headers).await?;
+    let second = attempt(&tls, &url, headers).await?;
+    let outcome = match second.early {
+        Some(true) => EarlyData::Accepted,
+        Some(false) => EarlyData::Rejected,
+        None => EarlyData::NotOffered,
+    };
+    eprintln!(
+        "{} full handshake {}, resumed {}",
+        "Timing:".bold(),
+        format_duration(first.elapsed),
+        format_duration(second.elapsed)
+    );
+    Ok((second.response.into(), outcome))
+}
+
+pub fn report(outcome: EarlyData) {
+    let text = match outcome {
+        EarlyData::Accepted => {
+            "accepted, the request went out with the first flight (0-RTT)".green()
+        }
+        EarlyData::Rejected => {
+            "rejected by the server, the request was resent after the handshake".yellow()
+        }
+        EarlyData::NotOffered => {
+            "not attempted, the server's session ticket does not allow early data".yellow()
+        }
+    };
+    eprintln!("{} {}", "Early data:".bold(), text);
+}
@@ -0,0 +1,272 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use colored::Colorize;
+use encoding_rs::Encoding;
+use mime::Mime;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+
+use crate::{body, mixed};
+
+/// Headers shown first, in this order, before the rest.
+const LEADING: [&str; 5] = ["from", "to", "cc", "date", "subject"];
+
+#[derive(Debug, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub content_type: String,
+    /// decoded
+    pub size: usize,
+}
+
+/// A message/rfc822 body, as mail APIs and webhook debug endpoints send.
+#[derive(Debug)]
+pub struct Message {
+    pub headers: HeaderMap,
+    /// text parts, decoded, with their type
+    pub texts: Vec<(String, String)>,
+    pub attachments: Vec<Attachment>,
+}
+
+pub fn is_message(m: &Mime) -> bool {
+    m.type_() == mime::MESSAGE && m.subtype() == "rfc822"
+}
+
+pub fn parse(bytes: &[u8]) -> Message {
+    let (headers, body) = mixed::message(bytes);
+    let mut message = Message {
+        headers,
+        texts: Vec::new(),
+        attachments: Vec::new(),
+    };
+    let headers = message.headers.clone();
+    collect(&headers, &body, &mut message);
+    message
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Walk the MIME tree of one entity, keeping text and listing the rest.
+fn collect(headers: &HeaderMap, bytes: &[u8], message: &mut Message) {
+    let m: Mime = header(headers, CONTENT_TYPE.as_str())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(mime::TEXT_PLAIN);
+    if let Some(parts) = mixed::parts(&m, bytes) {
+        // alternatives say the same thing, so the plain one will do
+        let plain = parts.iter().position(|p| {
+            header(&p.headers, CONTENT_TYPE.as_str()).is_none_or(|v| v.starts_with("text/plain"))
+        });
+        match plain.filter(|_| m.subtype() == "alternative") {
+            Some(i) => collect(&parts[i].headers, &parts[i].body, message),
+            None => {
+                for part in parts.iter() {
+                    collect(&part.headers, &part.body, message);
+                }
+            }
+        }
+        return;
+    }
+    let data = transfer_decode(header(headers, "content-transfer-encoding"), bytes);
+    let disposition = header(headers, "content-disposition").unwrap_or_default();
+    let name = param(disposition, "filename").or_else(|| {
+        param(
+            header(headers, CONTENT_TYPE.as_str()).unwrap_or_default(),
+            "name",
+        )
+    });
+    if m.type_() == mime::TEXT && !disposition.starts_with("attachment") && name.is_none() {
+        message.texts.push((
+            m.essence_str().to_string(),
+            body::decode_text(&data, Some(&m)).into_owned(),
+        ));
+        return;
+    }
+    message.attachments.push(Attachment {
+        name: name.map_or_else(|| "(unnamed)".to_string(), |n| decode_words(&n)),
+        content_type: m.essence_str().to_string(),
+        size: data.len(),
+    });
+}
+
+/// The `key=value` parameter of a structured header, quoted or not.
+fn param(value: &str, key: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case(key)
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+fn transfer_decode(encoding: Option<&str>, bytes: &[u8]) -> Vec<u8> {
+    match encoding.map(|e| e.trim().to_ascii_lowercase()).as_deref() {
+        Some("base64") => {
+            let clean: Vec<u8> = bytes
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            STANDARD.decode(clean).unwrap_or_else(|_| bytes.to_vec())
+        }
+        Some("quoted-printable") => quoted_printable(bytes, false),
+        _ => bytes.to_vec(),
+    }
+}
+
+/// Quoted-printable, or the `Q` encoding of headers where `_` is a space.
+fn quoted_printable(bytes: &[u8], q: bool) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'=' if bytes[i + 1..].starts_with(b"\r\n") => i += 2,
+            b'=' if bytes[i + 1..].starts_with(b"\n") => i += 1,
+            b'=' => {
+                let hex = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|h| std::str::from_utf8(h).ok())
+                    .and_then(|h| u8::from_str_radix(h, 16).ok());
+                match hex {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'='),
+                }
+            }
+            b'_' if q => out.push(b' '),
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// An RFC 2047 `=?charset?B|Q?text?=` word at the start of `s`, decoded,
+/// and its length.
+fn encoded_word(s: &str) -> Option<(String, usize)> {
+    let inner = s.strip_prefix("=?")?;
+    let (charset, rest) = inner.split_once('?')?;
+    let (encoding, rest) = rest.split_once('?')?;
+    let end = rest.find("?=")?;
+    let text = &rest[..end];
+    let bytes = match encoding {
+        "B" | "b" => STANDARD.decode(text).ok()?,
+        "Q" | "q" => quoted_printable(text.as_bytes(), true),
+        _ => return None,
+    };
+    // RFC 2231 allows a language after the charset
+    let label = charset.split('*').next().unwrap_or(charset);
+    let decoded = Encoding::for_label(label.as_bytes())?.decode(&bytes).0;
+    let len = 2 + charset.len() + 1 + encoding.len() + 1 + end + 2;
+    Some((decoded.into_owned(), len))
+}
+
+/// A header value with its encoded words decoded.
+pub fn decode_words(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    // whitespace between two encoded words is not part of the text
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let Some((text, len)) = encoded_word(&rest[start..]) else {
+            out.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            after_word = false;
+            continue;
+        };
+        let gap = &rest[..start];
+        if !(after_word && gap.trim().is_empty()) {
+            out.push_str(gap);
+        }
+        out.push_str(&text);
+        rest = &rest[start + len..];
+        after_word = true;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The headers, From, To, Cc, Date and Subject first, then the text parts
+/// and a list of the attachments.
+pub fn print(message: &Message, sorted: bool) {
+    let rows = crate::header_rows(&message.headers, sorted);
+    let leading = |name: &str| {
+        LEADING
+            .iter()
+            .position(|l| name.trim_end().trim_end_matches(':') == *l)
+    };
+    let (mut first, rest): (Vec<_>, Vec<_>) = rows
+        .into_iter()
+        .partition(|(name, _)| leading(name).is_some());
+    first.sort_by_key(|(name, _)| leading(name));
+    for (name, value) in first {
+        println!("{} {}", name.green().bold(), decode_words(&value).bold());
+    }
+    for (name, value) in rest {
+        println!("{} {}", name.green(), decode_words(&value));
+    }
+    for (content_type, text) in message.texts.iter() {
+        println!("\n{}", format!("── {} ──", content_type).dimmed());
+        println!("{}", text.trim_end());
+    }
+    if !message.attachments.is_empty() {
+        println!("\n{}", "Attachments:".bold());
+        let width = message
+            .attachments
+            .iter()
+            .map(|a| a.size.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for a in message.attachments.iter() {
+            println!(
+                "  {:>width$}  {}  {}",
+                a.size,
+                a.name,
+                a.content_type.dimmed(),
+                width = width
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_works() {
+        let mail = b"From: =?UTF-8?Q?Jos=C3=A9?= <jose@example.com>\r\n\
+            Subject: =?UTF-8?B?UmVwb3J0?= =?UTF-8?B?IHJlYWR5?=\r\n\
+            Content-Type: multipart/mixed; boundary=b1\r\n\r\n\
+            --b1\r\nContent-Type: multipart/alternative; boundary=b2\r\n\r\n\
+            --b2\r\nContent-Type: text/plain; charset=utf-8\r\n\
+            Content-Transfer-Encoding: quoted-printable\r\n\r\n\
+            Caf=C3=A9 at =\r\nnoon\r\n\
+            --b2\r\nContent-Type: text/html\r\n\r\n<p>Caf&eacute;</p>\r\n--b2--\r\n\
+            --b1\r\nContent-Type: application/pdf; name=\"q3.pdf\"\r\n\
+            Content-Disposition: attachment; filename=\"q3.pdf\"\r\n\
+            Content-Transfer-Encoding: base64\r\n\r\nJVBE\r\nRi0x\r\n--b1--\r\n";
+        let message = parse(mail);
+        assert_eq!(
+            decode_words(header(&message.headers, "from").unwrap()),
+            "José <jose@example.com>"
+        );
+        assert_eq!(
+            decode_words(header(&message.headers, "subject").unwrap()),
+            "Report ready"
+        );
+        assert_eq!(
+            message.texts,
+            [("text/plain".to_string(), "Café at noon".to_string())]
+        );
+        assert_eq!(
+            message.attachments,
+            [Attachment {
+                name: "q3.pdf".into(),
+                content_type: "application/pdf".into(),
+                size: 6,
+            }]
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::ValueEnum;
+use hyper::body::Bytes;
+use mime::Mime;
+use reqwest::{
+    header::{self, HeaderMap},
+    redirect, Response, StatusCode, Url, Version,
+};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde_json::Value;
+
+use crate::body;
+
+/// How long the exchange took, from sending the request.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Timing {
+    /// until the response headers arrived
+    #[serde(rename = "headers_ms", serialize_with = "millis")]
+    pub headers: Duration,
+    /// until the whole body was read
+    #[serde(rename = "total_ms", serialize_with = "millis")]
+    pub total: Duration,
+}
+
+fn millis<S: Serializer>(d: &Duration, s: S) -> std::result::Result<S::Ok, S::Error> {
+    s.serialize_f64(d.as_secs_f64() * 1000.0)
+}
+
+/// A redirect followed on the way to the final response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Redirect {
+    pub status: u16,
+    pub from: String,
+    pub to: String,
+}
+
+tokio::task_local! {
+    /// Redirects followed since the last `Exchange::read`, filled in by
+    /// `policy`. reqwest follows redirects in the task awaiting the response,
+    /// so concurrent requests keep their chains apart.
+    static REDIRECTS: RefCell<Vec<Redirect>>;
+}
+
+/// Run `f` with its redirects recorded for `Exchange::read`.
+pub async fn track<F: Future>(f: F) -> F::Output {
+    REDIRECTS.scope(RefCell::new(Vec::new()), f).await
+}
+
+/// The default redirect policy (at most 10), keeping a record of the chain.
+pub fn policy() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        if attempt.previous().len() > 10 {
+            return attempt.error("too many redirects");
+        }
+        let redirect = Redirect {
+            status: attempt.status().as_u16(),
+            from: attempt.previous().last().map(Url::to_string).unwrap_or_default(),
+            to: attempt.url().to_string(),
+        };
+        // outside `track` there is nobody to tell
+        REDIRECTS.try_with(|r| r.borrow_mut().push(redirect)).ok();
+        attempt.follow()
+    })
+}
+
+/// Set by `--max-response-size`: bodies are not read past it.
+static MAX_RESPONSE_SIZE: AtomicU64 = AtomicU64::new(u64::MAX);
+
+pub fn limit_response_size(max: u64) {
+    MAX_RESPONSE_SIZE.store(max, Ordering::Relaxed);
+}
+
+/// An error once `size` bytes of the body of `url` are over
+/// `--max-response-size`.
+pub fn check_size(url: &Url, size: u64) -> Result<()> {
+    let max = MAX_RESPONSE_SIZE.load(Ordering::Relaxed);
+    if size > max {
+        return Err(anyhow!(format!(
+            "Failed to read {}: the response is over --max-response-size ({} bytes)",
+            url, max
+        )));
+    }
+    Ok(())
+}
+
+/// `chunks` of `len` bytes in all as one buffer: the chunk itself when there
+/// is only one, else copied once into a buffer of the right size.
+pub fn concat(mut chunks: Vec<Bytes>, len: usize) -> Bytes {
+    if chunks.len() <= 1 {
+        return chunks.pop().unwrap_or_default();
+    }
+    let mut body = Vec::with_capacity(len);
+    for chunk in chunks.iter() {
+        body.extend_from_slice(chunk);
+    }
+    body.into()
+}
+
+/// One response as received, with how it was reached. The pretty printer
+/// and `--json-output` both start from it.
+#[derive(Debug)]
+pub struct Exchange {
+    /// `None` for responses that did not come through reqwest, like those
+    /// over a Unix socket
+    pub url: Option<Url>,
+    pub status: StatusCode,
+    pub version: Version,
+    pub headers: HeaderMap,
+    /// Shared with whatever the body is handed to, never copied to be read
+    pub body: Bytes,
+    pub timing: Timing,
+    pub redirects: Vec<Redirect>,
+}
+
+impl Exchange {
+    /// Read `resp` to the end; `started` is when the request was sent.
+    pub async fn read(resp: Response, started: Instant) -> Result<Self> {
+        let headers_at = started.elapsed();
+        // responses converted from hyper ones get a placeholder URL
+        let url =
+            Some(resp.url().clone()).filter(|u| u.host_str() != Some("no.url.provided.local"));
+        let status = resp.status();
+        let version = resp.version();
+        let headers = resp.headers().clone();
+        let from = resp.url().clone();
+        check_size(&from, resp.content_length().unwrap_or(0))?;
+        let mut resp = resp;
+        let mut chunks = Vec::new();
+        let mut len = 0;
+        while let Some(chunk) = resp.chunk().await? {
+            len += chunk.len();
+            check_size(&from, len as u64)?;
+            chunks.push(chunk);
+        }
+        let body = concat(chunks, len);
+        Ok(Self {
+            url,
+            status,
+            version,
+            headers,
+            body,
+            timing: Timing {
+                headers: headers_at,
+                total: started.elapsed(),
+            },
+            redirects: REDIRECTS.try_with(RefCell::take).unwrap_or_default(),
+        })
+    }
+
+    /// One line for logs: status, final URL, size and time.
+    pub fn summary(&self) -> String {
+        let url = self.url.as_ref().map_or(String::new(), |u| format!(" {}", u));
+        let redirects = match self.redirects.len() {
+            0 => String::new(),
+            1 => " after 1 redirect".into(),
+            n => format!(" after {} redirects", n),
+        };
+        format!(
+            "HTTP {}{} {} bytes in {} ms{}",
+            self.status,
+            url,
+            self.body.len(),
+            self.timing.total.as_millis(),
+            redirects
+        )
+    }
+
+    pub fn mime(&self) -> Option<Mime> {
+        self.headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+    }
+
+    /// The body for JSON output and how it is given: parsed JSON, text, or
+    /// base64 for anything that is not text.
+    fn body_value(&self) -> (Value, Option<&'static str>) {
+        if self.body.is_empty() {
+            return (Value::Null, None);
+        }
+        let mime = self.mime();
+        if let Some(value) = serde_json::from_slice::<Value>(&self.body)
+            .ok()
+            .filter(|_| mime.as_ref().is_some_and(is_json))
+        {
+            return (value, Some("json"));
+        }
+        let charset = mime.as_ref().and_then(|m| m.get_param(mime::CHARSET));
+        if charset.is_some()
+            || body::charset_override().is_some()
+            || std::str::from_utf8(&self.body).is_ok()
+        {
+            let text = body::decode_text(&self.body, mime.as_ref());
+            return (Value::String(text.into_owned()), Some("text"));
+        }
+        (Value::String(STANDARD.encode(&self.body)), Some("base64"))
+    }
+}
+
+/// What `--get` prints of a response instead of the response.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Field {
+    /// The URL the response came from, after redirects
+    FinalUrl,
+    /// The status code
+    Status,
+}
+
+impl Field {
+    pub fn of(self, exchange: &Exchange) -> Result<String> {
+        match self {
+            Self::FinalUrl => exchange.url.as_ref().map(Url::to_string).ok_or_else(|| {
+                anyhow!("Failed to get the final URL: the response did not come over HTTP(S)")
+            }),
+            Self::Status => Ok(exchange.status.as_u16().to_string()),
+        }
+    }
+}
+
+fn is_json(m: &Mime) -> bool {
+    m.subtype() == mime::JSON || m.suffix() == Some(mime::JSON)
+}
+
+impl Serialize for Exchange {
+    fn serialize<S: Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                serde_json::json!({
+                    "name": name.as_str(),
+                    "value": String::from_utf8_lossy(value.as_bytes()),
+                })
+            })
+            .collect();
+        let (body, encoding) = self.body_value();
+        let mut out = s.serialize_struct("Exchange", 9)?;
+        out.serialize_field("url", &self.url.as_ref().map(Url::as_str))?;
+        out.serialize_field("status", &self.status.as_u16())?;
+        out.serialize_field("reason", &self.status.canonical_reason())?;
+        out.serialize_field("version", &format!("{:?}", self.version))?;
+        out.serialize_field("headers", &headers)?;
+        out.serialize_field("body", &body)?;
+        out.serialize_field("body_encoding", &encoding)?;
+        out.serialize_field("timing", &self.timing)?;
+        out.serialize_field("redirects", &self.redirects)?;
+        out.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exchange(content_type: &str, body: &[u8]) -> Exchange {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        Exchange {
+            url: Some("http://localhost/a".parse().unwrap()),
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers,
+            body: Bytes::copy_from_slice(body),
+            timing: Timing {
+                headers: Duration::from_millis(5),
+                total: Duration::from_millis(8),
+            },
+            redirects: vec![Redirect {
+                status: 301,
+                from: "http://localhost/".into(),
+                to: "http://localhost/a".into(),
+            }],
+        }
+    }
+
+    #[test]
+    fn serialize_works() {
+        let json = serde_json::to_value(exchange("application/hal+json", b"{\"a\": [1]}")).unwrap();
+        assert_eq!(json["status"], 200);
+        assert_eq!(json["reason"], "OK");
+        assert_eq!(json["version"], "HTTP/1.1");
+        assert_eq!(json["headers"][0]["name"], "content-type");
+        assert_eq!(json["body"], serde_json::json!({"a": [1]}));
+        assert_eq!(json["body_encoding"], "json");
+        assert_eq!(json["timing"]["total_ms"], 8.0);
+        assert_eq!(json["redirects"][0]["status"], 301);
+
+        let text = serde_json::to_value(exchange("text/plain", b"{not json")).unwrap();
+        assert_eq!(text["body"], "{not json");
+        assert_eq!(text["body_encoding"], "text");
+        let binary = serde_json::to_value(exchange("image/png", &[0x89, 0xff, 0])).unwrap();
+        assert_eq!(binary["body"], "if8A");
+        assert_eq!(binary["body_encoding"], "base64");
+    }
+
+    #[test]
+    fn field_works() {
+        let exchange = exchange("text/plain", b"");
+        assert_eq!(Field::FinalUrl.of(&exchange).unwrap(), "http://localhost/a");
+        assert_eq!(Field::Status.of(&exchange).unwrap(), "200");
+        assert!(exchange.summary().ends_with("after 1 redirect"));
+        let socket = Exchange { url: None, ..exchange };
+        assert!(Field::FinalUrl.of(&socket).is_err());
+    }
+
+    #[test]
+    fn concat_works() {
+        let one = Bytes::from_static(b"whole");
+        assert_eq!(concat(vec![one.clone()], 5).as_ptr(), one.as_ptr());
+        let parts = vec![Bytes::from_static(b"ab"), Bytes::from_static(b"cd")];
+        assert_eq!(concat(parts, 4), "abcd");
+        assert!(concat(Vec::new(), 0).is_empty());
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use mime::Mime;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde_json::Value;
+
+use crate::{body, jsonpath};
+
+/// `--expect-status`: codes and classes such as `200,204` or `2xx`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusExpect(Vec<String>);
+
+impl FromStr for StatusExpect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let patterns: Vec<String> = s
+            .split(',')
+            .map(|p| p.trim().to_ascii_lowercase())
+            .collect();
+        let valid = |p: &String| {
+            p.len() == 3
+                && p.chars().next().is_some_and(|c| ('1'..='5').contains(&c))
+                && (p[1..].chars().all(|c| c.is_ascii_digit()) || &p[1..] == "xx")
+        };
+        if !patterns.iter().all(valid) {
+            return Err(anyhow!(format!(
+                "Failed to parse status {}: give codes or classes, e.g. `200,204` or `2xx`",
+                s
+            )));
+        }
+        Ok(Self(patterns))
+    }
+}
+
+impl StatusExpect {
+    fn matches(&self, status: StatusCode) -> bool {
+        let code = status.as_u16().to_string();
+        self.0
+            .iter()
+            .any(|p| *p == code || (p.ends_with("xx") && code.starts_with(&p[..1])))
+    }
+}
+
+/// `--expect-header`: `Name` to be present, or `Name:value` to have that value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderExpect {
+    name: String,
+    value: Option<String>,
+}
+
+impl FromStr for HeaderExpect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once(':') {
+            Some((name, value)) => (name.trim(), Some(value.trim().to_string())),
+            None => (s.trim(), None),
+        };
+        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow!(format!("Failed to parse {}: invalid header name", s)))?;
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+/// `--expect-json`: `PATH=VALUE`, the value as JSON or else a string, or
+/// `PATH` alone for the path to match something.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonExpect {
+    text: String,
+    path: jsonpath::Path,
+    value: Option<Value>,
+}
+
+impl FromStr for JsonExpect {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the first `=` outside `['...']`
+        let mut depth = 0;
+        let split = s.char_indices().find_map(|(i, c)| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '=' if depth == 0 => return Some(i),
+                _ => {}
+            }
+            None
+        });
+        let (path, value) = match split {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        Ok(Self {
+            text: path.to_string(),
+            path: path.parse()?,
+            value: value
+                .map(|v| serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.into()))),
+        })
+    }
+}
+
+/// Every expectation from the command line.
+#[derive(Debug, Clone, Default)]
+pub struct Expectations {
+    pub status: Option<StatusExpect>,
+    pub headers: Vec<HeaderExpect>,
+    pub body_contains: Vec<String>,
+    pub json: Vec<JsonExpect>,
+}
+
+impl Expectations {
+    fn len(&self) -> usize {
+        usize::from(self.status.is_some())
+            + self.headers.len()
+            + self.body_contains.len()
+            + self.json.len()
+    }
+
+    /// What `status`, `headers` and the decoded `bytes` of a response fail
+    /// to meet.
+    fn failures(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        bytes: &[u8],
+        m: Option<&Mime>,
+    ) -> Vec<String> {
+        let mut failed = Vec::new();
+        if let Some(expect) = self.status.as_ref().filter(|e| !e.matches(status)) {
+            failed.push(format!(
+                "status: expected {}, got {}",
+                expect.0.join(" or "),
+                status
+            ));
+        }
+        for expect in self.headers.iter() {
+            let got = headers
+                .get(expect.name.as_str())
+                .map(|v| String::from_utf8_lossy(v.as_bytes()));
+            match (&expect.value, got) {
+                (_, None) => failed.push(format!("header {}: missing", expect.name)),
+                (Some(want), Some(got)) if *want != got => failed.push(format!(
+                    "header {}: expected {:?}, got {:?}",
+                    expect.name, want, got
+                )),
+                _ => {}
+            }
+        }
+        let text = body::decode_text(bytes, m);
+        for needle in self
+            .body_contains
+            .iter()
+            .filter(|n| !text.contains(n.as_str()))
+        {
+            failed.push(format!("body: does not contain {:?}", needle));
+        }
+        if !self.json.is_empty() {
+            match serde_json::from_slice::<Value>(text.as_bytes()) {
+                Err(_) => {
+                    failed.push("body: not JSON, so no JSONPath expectation can hold".to_string())
+                }
+                Ok(json) => {
+                    for expect in self.json.iter() {
+                        let found = expect.path.select(&json);
+                        match (&expect.value, found.first()) {
+                            (_, None) => failed.push(format!("{}: matches nothing", expect.text)),
+                            (Some(want), Some(got)) if want != *got => failed
+                                .push(format!("{}: expected {}, got {}", expect.text, want, got)),
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        failed
+    }
+}
+
+static EXPECTATIONS: OnceLock<Expectations> = OnceLock::new();
+static CHECKED: AtomicUsize = AtomicUsize::new(0);
+static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Set once the command line is parsed.
+pub fn init(expectations: Expectations) {
+    if expectations.len() > 0 {
+        EXPECTATIONS.set(expectations).ok();
+    }
+}
+
+/// Hold a response to the expectations, if there are any.
+pub fn check(status: StatusCode, headers: &HeaderMap, bytes: &[u8], m: Option<&Mime>) {
+    let Some(expectations) = EXPECTATIONS.get() else {
+        return;
+    };
+    CHECKED.fetch_add(1, Ordering::Relaxed);
+    let failed = expectations.failures(status, headers, bytes, m);
+    FAILURES.lock().unwrap().extend(failed);
+}
+
+/// Print how the responses did against the expectations, and fail when any
+/// was not met.
+pub fn report() -> Result<()> {
+    let Some(expectations) = EXPECTATIONS.get() else {
+        return Ok(());
+    };
+    let failures = FAILURES.lock().unwrap();
+    let responses = CHECKED.load(Ordering::Relaxed);
+    if responses == 0 {
+        return Err(anyhow!("Failed expectations: no response was checked"));
+    }
+    if failures.is_empty() {
+        eprintln!(
+            "{} {} expectations met",
+            "✓".green().bold(),
+            expectations.len() * responses
+        );
+        return Ok(());
+    }
+    for failure in failures.iter() {
+        eprintln!("{} {}", "✗".red().bold(), failure);
+    }
+    Err(anyhow!(format!(
+        "Failed expectations: {} not met",
+        failures.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_works() {
+        let expectations = Expectations {
+            status: Some("2xx,304".parse().unwrap()),
+            headers: vec![
+                "x-request-id".parse().unwrap(),
+                "content-type: application/json".parse().unwrap(),
+            ],
+            body_contains: vec!["\"ok\"".into()],
+            json: vec![
+                "$.data.count=3".parse().unwrap(),
+                "$.data.name=ada".parse().unwrap(),
+            ],
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let body = br#"{"status": "ok", "data": {"count": 3, "name": "ada"}}"#;
+        assert_eq!(
+            expectations.failures(StatusCode::OK, &headers, body, None),
+            ["header x-request-id: missing"]
+        );
+
+        let body = br#"{"status": "down", "data": {"count": 2}}"#;
+        assert_eq!(
+            expectations.failures(StatusCode::BAD_GATEWAY, &headers, body, None),
+            [
+                "status: expected 2xx or 304, got 502 Bad Gateway",
+                "header x-request-id: missing",
+                "body: does not contain \"\\\"ok\\\"\"",
+                "$.data.count: expected 3, got 2",
+                "$.data.name: matches nothing",
+            ]
+        );
+        assert!("20x".parse::<StatusExpect>().is_err());
+    }
+}
@@ -0,0 +1,514 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, StatusCode,
+};
+
+use crate::auth::{self, Auth};
+
+/// Statuses a cache may store without explicit freshness (RFC 9111 §4.2.2).
+const HEURISTICALLY_CACHEABLE: [u16; 12] =
+    [200, 203, 204, 206, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Differences between Date and the local clock up to this are just the
+/// header's one second resolution plus transit time.
+const SKEW_TOLERANCE: Duration = Duration::from_secs(5);
+
+/// Caching-related facts about a response.
+#[derive(Debug, Default)]
+pub struct CacheInfo {
+    pub status: u16,
+    pub cache_control: BTreeMap<String, Option<String>>,
+    pub date: Option<SystemTime>,
+    /// `Some(UNIX_EPOCH)` when present but invalid, which means already expired
+    pub expires: Option<SystemTime>,
+    pub last_modified: Option<SystemTime>,
+    pub etag: Option<String>,
+    pub age: Option<u64>,
+}
+
+/// Where a freshness lifetime comes from.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Lifetime {
+    MaxAge(Duration),
+    Expires(Duration),
+    /// 10% of the time since Last-Modified
+    Heuristic(Duration),
+}
+
+impl Lifetime {
+    pub fn duration(&self) -> Duration {
+        match *self {
+            Lifetime::MaxAge(d) | Lifetime::Expires(d) | Lifetime::Heuristic(d) => d,
+        }
+    }
+
+    fn source(&self) -> &'static str {
+        match self {
+            Lifetime::MaxAge(_) => "max-age",
+            Lifetime::Expires(_) => "Expires - Date",
+            Lifetime::Heuristic(_) => "heuristic, 10% of Last-Modified age",
+        }
+    }
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn http_date(headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    header_str(headers, name).and_then(|v| httpdate::parse_http_date(v).ok())
+}
+
+/// Directives of a Cache-Control header, keyed by lowercase name.
+pub fn parse_cache_control(s: &str) -> BTreeMap<String, Option<String>> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| match d.split_once('=') {
+            Some((k, v)) => (
+                k.trim().to_ascii_lowercase(),
+                Some(v.trim().trim_matches('"').to_string()),
+            ),
+            None => (d.to_ascii_lowercase(), None),
+        })
+        .collect()
+}
+
+impl CacheInfo {
+    pub fn from_headers(status: StatusCode, headers: &HeaderMap) -> Self {
+        let cache_control = headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| parse_cache_control(v).into_iter())
+            .collect();
+        let expires = header_str(headers, header::EXPIRES)
+            .map(|v| httpdate::parse_http_date(v).unwrap_or(SystemTime::UNIX_EPOCH));
+        Self {
+            status: status.as_u16(),
+            cache_control,
+            date: http_date(headers, header::DATE),
+            expires,
+            last_modified: http_date(headers, header::LAST_MODIFIED),
+            etag: header_str(headers, header::ETAG).map(|s| s.to_string()),
+            age: header_str(headers, header::AGE).and_then(|v| v.trim().parse().ok()),
+        }
+    }
+
+    pub fn has(&self, directive: &str) -> bool {
+        self.cache_control.contains_key(directive)
+    }
+
+    /// Freshness lifetime for a private (browser-like) cache.
+    pub fn lifetime(&self) -> Option<Lifetime> {
+        if let Some(secs) = self
+            .cache_control
+            .get("max-age")
+            .and_then(|v| v.as_deref()?.parse::<u64>().ok())
+        {
+            return Some(Lifetime::MaxAge(Duration::from_secs(secs)));
+        }
+        if let Some(expires) = self.expires {
+            let date = self.date.unwrap_or_else(SystemTime::now);
+            return Some(Lifetime::Expires(
+                expires.duration_since(date).unwrap_or(Duration::ZERO),
+            ));
+        }
+        if let Some(modified) = self.last_modified {
+            if HEURISTICALLY_CACHEABLE.contains(&self.status) {
+                let date = self.date.unwrap_or_else(SystemTime::now);
+                let since = date.duration_since(modified).unwrap_or(Duration::ZERO);
+                return Some(Lifetime::Heuristic(since / 10));
+            }
+        }
+        None
+    }
+
+    /// Age of the response when it was received: the larger of the Age
+    /// header and how long ago the Date header says it was generated.
+    pub fn current_age(&self, now: SystemTime) -> Duration {
+        let apparent = self
+            .date
+            .and_then(|d| now.duration_since(d).ok())
+            .unwrap_or(Duration::ZERO);
+        apparent.max(Duration::from_secs(self.age.unwrap_or(0)))
+    }
+}
+
+/// How far `a` is from `b`, and whether it is later.
+fn offset(a: SystemTime, b: SystemTime) -> (Duration, bool) {
+    match a.duration_since(b) {
+        Ok(d) => (d, true),
+        Err(e) => (e.duration(), false),
+    }
+}
+
+/// Rows comparing the response's timestamps with the local clock at `now`:
+/// clock skew, the age of the copy and when it expires on either clock.
+pub fn clock_rows(info: &CacheInfo, now: SystemTime) -> Vec<(&'static str, String)> {
+    let mut rows = Vec::new();
+    let Some(date) = info.date else {
+        rows.push((
+            "Clock skew",
+            "unknown, no valid Date header".yellow().to_string(),
+        ));
+        return rows;
+    };
+    let (skew, behind) = offset(now, date);
+    let skewed = skew > SKEW_TOLERANCE;
+    rows.push((
+        "Clock skew",
+        if skewed {
+            format!(
+                "server clock is {} {} local time",
+                format_secs(skew),
+                if behind { "behind" } else { "ahead of" }
+            )
+            .yellow()
+            .to_string()
+        } else {
+            format!(
+                "none, Date is within {}s of local time",
+                SKEW_TOLERANCE.as_secs()
+            )
+            .green()
+            .to_string()
+        },
+    ));
+
+    let age = info.current_age(now);
+    let mut text = format_secs(age);
+    if let Some(header) = info.age {
+        text += &format!(" (Age: {}", header);
+        if behind && skewed && age > Duration::from_secs(header) {
+            text += ", but the apparent age from Date includes the clock skew";
+        }
+        text += ")";
+    } else if behind && skewed {
+        text += " by Date, mostly clock skew";
+    }
+    rows.push(("Copy age", text));
+
+    if let Some(expires) = info.expires {
+        let text = if expires == SystemTime::UNIX_EPOCH {
+            "invalid Expires, treated as already expired"
+                .yellow()
+                .to_string()
+        } else {
+            let lifetime = expires.duration_since(date).unwrap_or(Duration::ZERO);
+            let (left, future) = offset(expires, now);
+            let local = if future {
+                format!("in {} by the local clock", format_secs(left))
+            } else {
+                format!("{} ago by the local clock", format_secs(left))
+            };
+            let text = format!("{} after Date, {}", format_secs(lifetime), local);
+            if !lifetime.is_zero() && !future {
+                format!(
+                    "{}; clients with this clock consider it stale on arrival",
+                    text
+                )
+                .red()
+                .to_string()
+            } else {
+                text
+            }
+        };
+        let ignored = if info.cache_control.contains_key("max-age") {
+            " (max-age takes precedence)"
+        } else {
+            ""
+        };
+        rows.push(("Expiry", text + ignored));
+    }
+
+    if info.last_modified.is_some_and(|m| m > date) {
+        rows.push((
+            "Last-Modified",
+            "later than Date, the server clock or the file times are off"
+                .yellow()
+                .to_string(),
+        ));
+    }
+    rows
+}
+
+/// `1h 2m 3s` style rendering of whole seconds.
+pub fn format_secs(d: Duration) -> String {
+    let mut secs = d.as_secs();
+    if secs == 0 {
+        return "0s".into();
+    }
+    let mut parts = Vec::new();
+    for (unit, size) in [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)] {
+        if secs >= size {
+            parts.push(format!("{}{}", secs / size, unit));
+            secs %= size;
+        }
+    }
+    parts.join(" ")
+}
+
+/// Fetch `url`, replay it conditionally with each validator, and print a
+/// cacheability report.
+pub async fn probe(client: Client, auth: Option<&dyn Auth>, url: &str) -> Result<()> {
+    let resp = auth::sign_and_send(&client, client.get(url).build()?, auth).await?;
+    let info = CacheInfo::from_headers(resp.status(), resp.headers());
+    let headers = resp.headers().clone();
+    let status = resp.status();
+    resp.bytes().await?;
+
+    let row = |k: &str, v: &str| println!("{:<16}{}", format!("{}:", k).bold(), v);
+    row("Status", &status.to_string());
+    for (label, name) in [
+        ("Cache-Control", header::CACHE_CONTROL),
+        ("Expires", header::EXPIRES),
+        ("Age", header::AGE),
+        ("Vary", header::VARY),
+        ("Pragma", header::PRAGMA),
+    ] {
+        if let Some(v) = header_str(&headers, name) {
+            row(label, v);
+        }
+    }
+    row("ETag", info.etag.as_deref().unwrap_or("(none)"));
+    row(
+        "Last-Modified",
+        header_str(&headers, header::LAST_MODIFIED).unwrap_or("(none)"),
+    );
+
+    let now = SystemTime::now();
+    for (label, text) in clock_rows(&info, now) {
+        row(label, &text);
+    }
+
+    let age = info.current_age(now);
+    let lifetime = info.lifetime();
+    match lifetime {
+        Some(l) => {
+            let left = l.duration().saturating_sub(age);
+            row(
+                "Freshness",
+                &format!(
+                    "{} ({}), age {}, {}",
+                    format_secs(l.duration()),
+                    l.source(),
+                    format_secs(age),
+                    if left.is_zero() {
+                        "stale".to_string()
+                    } else {
+                        format!("fresh for {}", format_secs(left))
+                    }
+                ),
+            );
+        }
+        None => row("Freshness", "(no explicit or heuristic lifetime)"),
+    }
+
+    let mut revalidates = false;
+    if let Some(etag) = &info.etag {
+        let status = conditional(&client, auth, url, header::IF_NONE_MATCH, etag).await?;
+        revalidates |= status == StatusCode::NOT_MODIFIED;
+        row("Revalidation", &revalidation("If-None-Match", status));
+    }
+    if let Some(modified) = header_str(&headers, header::LAST_MODIFIED) {
+        let status = conditional(&client, auth, url, header::IF_MODIFIED_SINCE, modified).await?;
+        revalidates |= status == StatusCode::NOT_MODIFIED;
+        row("Revalidation", &revalidation("If-Modified-Since", status));
+    }
+
+    row("Verdict", &verdict(&info, lifetime, age, revalidates));
+    Ok(())
+}
+
+async fn conditional(
+    client: &Client,
+    auth: Option<&dyn Auth>,
+    url: &str,
+    name: header::HeaderName,
+    value: &str,
+) -> Result<StatusCode> {
+    let req = client.get(url).header(name, value).build()?;
+    let resp = auth::sign_and_send(client, req, auth).await?;
+    let status = resp.status();
+    resp.bytes().await?;
+    Ok(status)
+}
+
+fn revalidation(name: &str, status: StatusCode) -> String {
+    let result = if status == StatusCode::NOT_MODIFIED {
+        status.to_string().green()
+    } else {
+        format!("{} (validator ignored)", status).yellow()
+    };
+    format!("{} -> {}", name, result)
+}
+
+/// One line summary of how caches will treat the response.
+pub fn verdict(
+    info: &CacheInfo,
+    lifetime: Option<Lifetime>,
+    age: Duration,
+    revalidates: bool,
+) -> String {
+    if info.has("no-store") {
+        return "not cacheable (no-store)".red().to_string();
+    }
+    let scope = if info.has("private") {
+        " in private caches only"
+    } else {
+        ""
+    };
+    let fresh = lifetime.is_some_and(|l| l.duration() > age);
+    let verdict = if info.has("no-cache") {
+        if revalidates {
+            format!("cacheable{}, revalidated on every use", scope).green()
+        } else {
+            format!(
+                "cacheable{}, but must revalidate and revalidation fails",
+                scope
+            )
+            .yellow()
+        }
+    } else if fresh {
+        let heuristic = matches!(lifetime, Some(Lifetime::Heuristic(_)));
+        let v = format!("cacheable{}", scope)
+            + if heuristic {
+                " (heuristic lifetime only)"
+            } else {
+                ""
+            };
+        if heuristic {
+            v.yellow()
+        } else {
+            v.green()
+        }
+    } else if revalidates {
+        format!(
+            "cacheable{}, stale on arrival but revalidates with 304",
+            scope
+        )
+        .yellow()
+    } else {
+        "effectively not cacheable (no freshness and no working validators)".red()
+    };
+    verdict.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(headers: &[(&'static str, &str)]) -> CacheInfo {
+        let mut map = HeaderMap::new();
+        for (k, v) in headers {
+            map.append(*k, v.parse().unwrap());
+        }
+        CacheInfo::from_headers(StatusCode::OK, &map)
+    }
+
+    #[test]
+    fn parse_cache_control_works() {
+        let cc = parse_cache_control("public, Max-Age=60, no-cache=\"Set-Cookie\"");
+        assert_eq!(cc.get("public"), Some(&None));
+        assert_eq!(cc.get("max-age"), Some(&Some("60".into())));
+        assert_eq!(cc.get("no-cache"), Some(&Some("Set-Cookie".into())));
+    }
+
+    #[test]
+    fn lifetime_works() {
+        let date = "Wed, 21 Oct 2015 07:28:00 GMT";
+        let i = info(&[
+            ("cache-control", "max-age=60"),
+            ("expires", "Wed, 21 Oct 2015 08:28:00 GMT"),
+        ]);
+        assert_eq!(
+            i.lifetime(),
+            Some(Lifetime::MaxAge(Duration::from_secs(60)))
+        );
+
+        let i = info(&[("date", date), ("expires", "Wed, 21 Oct 2015 08:28:00 GMT")]);
+        assert_eq!(
+            i.lifetime(),
+            Some(Lifetime::Expires(Duration::from_secs(3600)))
+        );
+
+        let i = info(&[("date", date), ("expires", "0")]);
+        assert_eq!(i.lifetime(), Some(Lifetime::Expires(Duration::ZERO)));
+
+        let i = info(&[
+            ("date", date),
+            ("last-modified", "Sun, 11 Oct 2015 07:28:00 GMT"),
+        ]);
+        assert_eq!(
+            i.lifetime(),
+            Some(Lifetime::Heuristic(Duration::from_secs(86400)))
+        );
+
+        assert_eq!(info(&[("date", date)]).lifetime(), None);
+    }
+
+    #[test]
+    fn verdict_works() {
+        let i = info(&[("cache-control", "no-store, max-age=60")]);
+        assert!(verdict(&i, i.lifetime(), Duration::ZERO, true).contains("not cacheable"));
+
+        let i = info(&[("cache-control", "private, max-age=60")]);
+        assert!(verdict(&i, i.lifetime(), Duration::ZERO, false).contains("private caches only"));
+
+        let i = info(&[("etag", "\"x\"")]);
+        assert!(verdict(&i, i.lifetime(), Duration::ZERO, true).contains("revalidates with 304"));
+        assert!(verdict(&i, i.lifetime(), Duration::ZERO, false).contains("not cacheable"));
+    }
+
+    #[test]
+    fn clock_rows_works() {
+        let now = httpdate::parse_http_date("Wed, 21 Oct 2015 07:30:00 GMT").unwrap();
+        let text = |i: &CacheInfo, label| {
+            clock_rows(i, now)
+                .into_iter()
+                .find(|(l, _)| *l == label)
+                .map(|(_, t)| t)
+        };
+
+        let i = info(&[("date", "Wed, 21 Oct 2015 07:29:58 GMT"), ("age", "30")]);
+        assert!(text(&i, "Clock skew").unwrap().contains("none"));
+        assert!(text(&i, "Copy age").unwrap().starts_with("30s (Age: 30)"));
+
+        let i = info(&[
+            ("date", "Wed, 21 Oct 2015 07:20:00 GMT"),
+            ("expires", "Wed, 21 Oct 2015 07:25:00 GMT"),
+            ("last-modified", "Wed, 21 Oct 2015 08:00:00 GMT"),
+        ]);
+        assert!(text(&i, "Clock skew")
+            .unwrap()
+            .contains("10m behind local time"));
+        assert!(text(&i, "Copy age").unwrap().contains("mostly clock skew"));
+        let expiry = text(&i, "Expiry").unwrap();
+        assert!(expiry.contains("5m after Date, 5m ago by the local clock"));
+        assert!(expiry.contains("stale on arrival"));
+        assert!(text(&i, "Last-Modified").is_some());
+
+        let i = info(&[("date", "Wed, 21 Oct 2015 07:31:00 GMT"), ("expires", "0")]);
+        assert!(text(&i, "Clock skew")
+            .unwrap()
+            .contains("1m ahead of local time"));
+        assert!(text(&i, "Expiry").unwrap().contains("invalid Expires"));
+
+        assert_eq!(clock_rows(&info(&[]), now).len(), 1);
+    }
+
+    #[test]
+    fn format_secs_works() {
+        assert_eq!(format_secs(Duration::ZERO), "0s");
+        assert_eq!(format_secs(Duration::from_secs(3723)), "1h 2m 3s");
+        assert_eq!(format_secs(Duration::from_secs(90000)), "1d 1h");
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Map, Value};
+
+use crate::KvPair;
+
+/// The GraphQL document: read from `query` when it names a file, otherwise
+/// taken literally.
+pub fn load_query(query: &str) -> Result<String> {
+    if std::path::Path::new(query).is_file() {
+        std::fs::read_to_string(query).with_context(|| format!("Failed to read {}", query))
+    } else {
+        Ok(query.into())
+    }
+}
+
+/// Standard `{"query", "variables", "operationName"}` request envelope.
+/// Variable values that parse as JSON keep their type, others are strings.
+pub fn envelope(query: &str, vars: &[KvPair], operation: Option<&str>) -> Value {
+    let variables: Map<String, Value> = vars
+        .iter()
+        .map(|p| {
+            let v = serde_json::from_str(&p.v).unwrap_or_else(|_| Value::String(p.v.clone()));
+            (p.k.clone(), v)
+        })
+        .collect();
+    let mut body = json!({ "query": query, "variables": variables });
+    if let Some(op) = operation {
+        body["operationName"] = op.into();
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_works() {
+        let vars = [
+            KvPair {
+                k: "id".into(),
+                v: "42".into(),
+            },
+            KvPair {
+                k: "name".into(),
+                v: "bob".into(),
+            },
+            KvPair {
+                k: "tags".into(),
+                v: "[\"a\"]".into(),
+            },
+        ];
+        assert_eq!(
+            envelope("query Q { me }", &vars, Some("Q")),
+            json!({
+                "query": "query Q { me }",
+                "variables": { "id": 42, "name": "bob", "tags": ["a"] },
+                "operationName": "Q",
+            })
+        );
+        assert_eq!(
+            envelope("{ me }", &[], None),
+            json!({ "query": "{ me }", "variables": {} })
+        );
+        // as given with `--var`, base64 padding and all
+        let cursor = crate::parse_templated_kv_pair("cursor=YWJj==").unwrap();
+        assert_eq!(
+            envelope("{ me }", &[cursor], None)["variables"],
+            json!({ "cursor": "YWJj==" })
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, Request, RequestBuilder,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{compression, exchange::Exchange, secrets};
+
+/// An HTTP Archive (HAR 1.2), as written by browser devtools and proxies.
+/// Fields are optional when reading, as producers leave out different ones.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Har {
+    pub log: Log,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Log {
+    pub version: String,
+    pub creator: Creator,
+    #[serde(default)]
+    pub entries: Vec<Entry>,
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Self {
+            version: "1.2".into(),
+            creator: Creator {
+                name: "httpie".into(),
+                version: env!("CARGO_PKG_VERSION").into(),
+            },
+            entries: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Creator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    #[serde(default)]
+    pub started_date_time: String,
+    /// milliseconds
+    #[serde(default)]
+    pub time: f64,
+    pub request: HarRequest,
+    #[serde(default)]
+    pub response: HarResponse,
+    #[serde(default)]
+    pub cache: serde_json::Value,
+    #[serde(default)]
+    pub timings: Timings,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NameValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub query_string: Vec<NameValue>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<PostData>,
+    #[serde(default = "unknown")]
+    pub headers_size: i64,
+    #[serde(default = "unknown")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PostData {
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub status_text: String,
+    #[serde(default)]
+    pub http_version: String,
+    #[serde(default)]
+    pub cookies: Vec<NameValue>,
+    #[serde(default)]
+    pub headers: Vec<NameValue>,
+    #[serde(default)]
+    pub content: Content,
+    #[serde(default, rename = "redirectURL")]
+    pub redirect_url: String,
+    #[serde(default = "unknown")]
+    pub headers_size: i64,
+    #[serde(default = "unknown")]
+    pub body_size: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Content {
+    pub size: i64,
+    #[serde(default)]
+    pub mime_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `base64` for binary bodies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+/// Milliseconds per phase; `-1` where not measured.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Timings {
+    #[serde(default)]
+    pub send: f64,
+    #[serde(default)]
+    pub wait: f64,
+    #[serde(default)]
+    pub receive: f64,
+}
+
+fn unknown() -> i64 {
+    -1
+}
+
+fn name_values(headers: &HeaderMap) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: String::from_utf8_lossy(value.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+/// `t` as an ISO 8601 UTC timestamp with milliseconds.
+pub fn iso8601(t: SystemTime) -> String {
+    let d = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = d.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        d.subsec_millis()
+    )
+}
+
+/// The request half of an entry, taken before the request is sent;
+/// `defaults` are the headers the client adds.
+pub fn request(req: &Request, defaults: &HeaderMap) -> (HarRequest, SystemTime) {
+    let mut headers = defaults.clone();
+    headers.extend(req.headers().clone());
+    let post_data = req.body().map(|body| PostData {
+        mime_type: headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+        text: body
+            .as_bytes()
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+            .unwrap_or_default(),
+    });
+    let body_size = match req.body() {
+        Some(body) => body.as_bytes().map_or(-1, |b| b.len() as i64),
+        None => 0,
+    };
+    let request = HarRequest {
+        method: req.method().to_string(),
+        url: req.url().to_string(),
+        http_version: format!("{:?}", req.version()),
+        cookies: Vec::new(),
+        headers: name_values(&headers),
+        query_string: req
+            .url()
+            .query_pairs()
+            .map(|(name, value)| NameValue {
+                name: name.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect(),
+        post_data,
+        headers_size: -1,
+        body_size,
+    };
+    (request, SystemTime::now())
+}
+
+/// The response half of an entry, with the body decompressed as far as
+/// possible and base64 encoded when it is not text.
+pub fn response(exchange: &Exchange) -> HarResponse {
+    let codings = exchange
+        .headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(compression::codings)
+        .unwrap_or_default();
+    let body =
+        compression::decode(&codings, exchange.body.clone()).unwrap_or_else(|_| exchange.body.clone());
+    let (text, encoding) = match std::str::from_utf8(&body) {
+        Ok(text) => (text.to_string(), None),
+        Err(_) => (STANDARD.encode(&body), Some("base64".to_string())),
+    };
+    let header = |name| {
+        exchange
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    HarResponse {
+        status: exchange.status.as_u16(),
+        status_text: exchange
+            .status
+            .canonical_reason()
+            .unwrap_or_default()
+            .into(),
+        http_version: format!("{:?}", exchange.version),
+        cookies: Vec::new(),
+        headers: name_values(&exchange.headers),
+        content: Content {
+            size: body.len() as i64,
+            mime_type: header(header::CONTENT_TYPE),
+            text: Some(text),
+            encoding,
+        },
+        redirect_url: header(header::LOCATION),
+        headers_size: -1,
+        body_size: exchange.body.len() as i64,
+    }
+}
+
+/// Append the exchange to the archive at `path`, creating it if needed.
+pub fn append(
+    path: &Path,
+    request: HarRequest,
+    started: SystemTime,
+    exchange: &Exchange,
+) -> Result<()> {
+    let mut har = match std::fs::read_to_string(path) {
+        Ok(s) => serde_json::from_str(&s)
+            .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e)))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Har::default(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let ms = |d: std::time::Duration| d.as_secs_f64() * 1000.0;
+    let timing = exchange.timing;
+    har.log.entries.push(Entry {
+        started_date_time: iso8601(started),
+        time: ms(timing.total),
+        request,
+        response: response(exchange),
+        cache: serde_json::json!({}),
+        timings: Timings {
+            send: 0.0,
+            wait: ms(timing.headers),
+            receive: ms(timing.total.saturating_sub(timing.headers)),
+        },
+    });
+    // secrets go back to their placeholders, so the file can be shared
+    let text = secrets::redact(&serde_json::to_string_pretty(&har)?);
+    std::fs::write(path, text + "\n")
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+pub fn load(path: &Path) -> Result<Har> {
+    let s = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&s)
+        .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Headers not to send again: HTTP/2 pseudo headers, and those the client
+/// works out for itself.
+fn replayable(name: &str) -> bool {
+    !name.starts_with(':')
+        && !["host", "content-length", "connection", "transfer-encoding"]
+            .contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// A request rebuilt from a recorded entry.
+pub fn rebuild(client: &Client, req: &HarRequest) -> Result<RequestBuilder> {
+    let method = req.method.parse().map_err(|_| {
+        anyhow!(format!(
+            "Failed to replay {}: bad method {}",
+            req.url, req.method
+        ))
+    })?;
+    let mut builder = client.request(method, &req.url);
+    for h in req.headers.iter().filter(|h| replayable(&h.name)) {
+        builder = builder.header(&h.name, &h.value);
+    }
+    if let Some(post) = &req.post_data {
+        let has_type = req
+            .headers
+            .iter()
+            .any(|h| h.name.eq_ignore_ascii_case("content-type"));
+        if !has_type && !post.mime_type.is_empty() {
+            builder = builder.header(header::CONTENT_TYPE, &post.mime_type);
+        }
+        builder = builder.body(post.text.clone());
+    }
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn iso8601_works() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_445_412_480_123);
+        assert_eq!(iso8601(t), "2015-10-21T07:28:00.123Z");
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn rebuild_works() {
+        // trimmed from a browser export
+        let har: Har = serde_json::from_str(
+            r#"{"log": {"version": "1.2", "creator": {"name": "Firefox", "version": "1"},
+                "entries": [{"request": {"method": "POST", "url": "http://localhost/items",
+                "headers": [{"name": ":authority", "value": "localhost"},
+                            {"name": "X-Token", "value": "t"}],
+                "postData": {"mimeType": "application/json", "text": "{\"a\":1}"}}}]}}"#,
+        )
+        .unwrap();
+        let entry = &har.log.entries[0];
+        let req = rebuild(&Client::new(), &entry.request)
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(req.method(), "POST");
+        assert_eq!(req.headers()["x-token"], "t");
+        assert_eq!(req.headers()[header::CONTENT_TYPE], "application/json");
+        assert!(!req.headers().contains_key(":authority"));
+        assert_eq!(req.body().unwrap().as_bytes().unwrap(), b"{\"a\":1}");
+    }
+}
@@ -0,0 +1,259 @@
+use std::{num::NonZeroUsize, thread};
+
+use syntect::{
+    highlighting::{HighlightIterator, HighlightState, Highlighter, Theme},
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
+    util::{as_24_bit_terminal_escaped, LinesWithEndings},
+};
+
+/// Bodies smaller than this are highlighted on one thread, as starting the
+/// others costs more than it saves.
+const PARALLEL_ABOVE: usize = 256 * 1024;
+/// Size of the pieces highlighted at once, on one thread or another.
+const CHUNK: usize = 256 * 1024;
+
+/// Where the parser and the highlighter are at a line boundary.
+type State = (ParseState, HighlightState);
+
+fn initial(syntax: &SyntaxReference, highlighter: &Highlighter) -> State {
+    (
+        ParseState::new(syntax),
+        HighlightState::new(highlighter, ScopeStack::new()),
+    )
+}
+
+/// `text` as terminal escapes, from `state`, and the state it ends in.
+fn highlight_from(
+    text: &str,
+    mut state: State,
+    ps: &SyntaxSet,
+    highlighter: &Highlighter,
+) -> (String, State) {
+    let mut out = String::with_capacity(text.len() * 2);
+    for line in LinesWithEndings::from(text) {
+        let Ok(ops) = state.0.parse_line(line, ps) else {
+            // what cannot be parsed is printed as is
+            out.push_str(line);
+            continue;
+        };
+        let ranges: Vec<_> =
+            HighlightIterator::new(&mut state.1, &ops, line, highlighter).collect();
+        out.push_str(&as_24_bit_terminal_escaped(&ranges, true));
+    }
+    (out, state)
+}
+
+/// What a JSON parser is inside of after `text`, given what it was inside
+/// of before: brackets, and `:` for the value of an object member up to
+/// the comma after it. Strings are skipped so their brackets do not count.
+fn open_brackets(text: &str, open: &mut Vec<u8>, in_string: &mut bool) {
+    let mut escaped = false;
+    for &b in text.as_bytes() {
+        match (b, *in_string) {
+            (_, true) if escaped => escaped = false,
+            (b'\\', true) => escaped = true,
+            (b'"', _) => *in_string = !*in_string,
+            (b'{' | b'[', false) => open.push(b),
+            (b':', false) if open.last() == Some(&b'{') => open.push(b),
+            (b',', false) if open.last() == Some(&b':') => {
+                open.pop();
+            }
+            (b'}', false) => {
+                if open.last() == Some(&b':') {
+                    open.pop();
+                }
+                open.pop();
+            }
+            (b']', false) => {
+                open.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lines that leave a JSON parser inside the same things as `open`, to
+/// guess the state a chunk starts in without parsing everything before it.
+fn json_prefix(open: &[u8]) -> String {
+    let mut prefix = String::from("\n");
+    for (i, &b) in open.iter().enumerate() {
+        match b {
+            // a member whose value is done, or holds what comes next
+            b':' if i + 1 == open.len() => prefix.push_str("\"k\": 0\n"),
+            b':' => prefix.push_str("\"k\": "),
+            _ => {
+                prefix.push(b as char);
+                prefix.push('\n');
+            }
+        }
+    }
+    prefix
+}
+
+/// `text` cut into pieces of about `size` bytes at line ends.
+fn split(text: &str, size: usize) -> Vec<&str> {
+    let mut chunks = Vec::with_capacity(text.len() / size + 1);
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        end = rest[end..].find('\n').map_or(rest.len(), |i| end + i + 1);
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+/// A state in a form that can leave the thread it was made on, which the
+/// parser's own cannot; it compares as the state does.
+fn fingerprint(state: &State) -> String {
+    format!("{:?}", state)
+}
+
+/// Highlight `text` as `syntax` in chunks of about `size` bytes, `threads`
+/// of them at a time, handing each to `emit` in order before the next batch
+/// starts; so a slow terminal holds the work back, and no more than a batch
+/// is kept formatted. Each chunk, bar the first, starts from a guess at the
+/// state before it, and is highlighted again from the real state when the
+/// guess was wrong, so the output is the same as from one thread. Returns
+/// how many chunks needed that.
+fn highlight_chunks(
+    text: &str,
+    syntax: &SyntaxReference,
+    ps: &SyntaxSet,
+    theme: &Theme,
+    (threads, size): (usize, usize),
+    mut emit: impl FnMut(String),
+) -> usize {
+    let highlighter = Highlighter::new(theme);
+    let chunks = split(text, size);
+    if threads < 2 {
+        let mut state = initial(syntax, &highlighter);
+        for chunk in chunks {
+            let (out, end) = highlight_from(chunk, state, ps, &highlighter);
+            emit(out);
+            state = end;
+        }
+        return 0;
+    }
+    // what to parse first to end up where each chunk starts
+    let json = syntax.name == "JSON";
+    let (mut open, mut in_string) = (Vec::new(), false);
+    let prefixes: Vec<String> = chunks
+        .iter()
+        .map(|chunk| {
+            let prefix = if json {
+                json_prefix(&open)
+            } else {
+                "\n".into()
+            };
+            if json {
+                open_brackets(chunk, &mut open, &mut in_string);
+            }
+            prefix
+        })
+        .collect();
+    let guess = |i: usize| {
+        let start = initial(syntax, &highlighter);
+        match i {
+            0 => start,
+            _ => highlight_from(&prefixes[i], start, ps, &highlighter).1,
+        }
+    };
+
+    let start = initial(syntax, &highlighter);
+    let mut at = fingerprint(&start);
+    // the real state, when it is on this thread rather than another's
+    let mut state = Some(start);
+    let mut redone = 0;
+    let (guess, highlighter) = (&guess, &highlighter);
+    for batch in (0..chunks.len()).collect::<Vec<_>>().chunks(threads) {
+        let speculated: Vec<(String, String, String)> = thread::scope(|scope| {
+            let handles: Vec<_> = batch
+                .iter()
+                .map(|&i| {
+                    let chunk = chunks[i];
+                    scope.spawn(move || {
+                        let start = guess(i);
+                        let from = fingerprint(&start);
+                        let (out, end) = highlight_from(chunk, start, ps, highlighter);
+                        (out, from, fingerprint(&end))
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for (&i, (out, from, end)) in batch.iter().zip(speculated) {
+            if from == at {
+                emit(out);
+                at = end;
+                state = None;
+                continue;
+            }
+            redone += 1;
+            // the guess for the chunk before held, so it gives the real state
+            let real = state
+                .take()
+                .unwrap_or_else(|| highlight_from(chunks[i - 1], guess(i - 1), ps, highlighter).1);
+            let (out, end) = highlight_from(chunks[i], real, ps, highlighter);
+            emit(out);
+            at = fingerprint(&end);
+            state = Some(end);
+        }
+    }
+    redone
+}
+
+/// Highlight `text` as `syntax` with `theme`, handing the output to `emit`
+/// a piece at a time.
+pub fn highlight(
+    text: &str,
+    syntax: &SyntaxReference,
+    ps: &SyntaxSet,
+    theme: &Theme,
+    emit: impl FnMut(String),
+) {
+    let threads = match text.len() {
+        0..PARALLEL_ABOVE => 1,
+        _ => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    };
+    highlight_chunks(text, syntax, ps, theme, (threads, CHUNK), emit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntect::highlighting::ThemeSet;
+
+    #[test]
+    fn highlight_works() {
+        let ps = SyntaxSet::load_defaults_newlines();
+        let ts = ThemeSet::load_defaults();
+        let theme = &ts.themes["base16-ocean.light"];
+
+        let items: Vec<_> = (0..400)
+            .map(|i| serde_json::json!({"id": i, "tags": ["a", "{b"], "nested": {"deep": [i, null]}}))
+            .collect();
+        let json = serde_json::to_string_pretty(&serde_json::json!({"items": items})).unwrap();
+        // the JSON guesses all hold; the last two chunks start inside a
+        // comment, which a fresh state is not, so those are done again
+        let html = "<p class=\"x\">text <b>bold</b></p>\n".repeat(600)
+            + "<!--\n"
+            + &"commented out\n".repeat(1600)
+            + "-->\n";
+        for (text, ext, expected) in [(&json, "json", 0), (&html, "html", 2)] {
+            let syntax = ps.find_syntax_by_extension(ext).unwrap();
+            let size = text.len() / 4 + 1;
+            let mut one = Vec::new();
+            highlight_chunks(text, syntax, &ps, theme, (1, size), |s| one.push(s));
+            // one batch, then two, which have to carry the state across
+            for threads in [4, 2] {
+                let mut many = Vec::new();
+                let redone =
+                    highlight_chunks(text, syntax, &ps, theme, (threads, size), |s| many.push(s));
+                assert_eq!(one.concat(), many.concat(), "{}", ext);
+                assert_eq!(many.len(), 4);
+                assert_eq!(redone, expected, "{}", ext);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use reqwest::ClientBuilder;
+
+use crate::Http2Opts;
+
+/// Apply the HTTP/2 settings to the regular client. reqwest cannot advertise
+/// a stream limit or header table size, so those are refused here rather than
+/// silently dropped.
+pub fn configure(mut builder: ClientBuilder, opts: &Http2Opts) -> Result<ClientBuilder> {
+    let unsupported = [
+        (
+            "--h2-max-concurrent-streams",
+            opts.h2_max_concurrent_streams,
+        ),
+        ("--h2-header-table-size", opts.h2_header_table_size),
+    ];
+    if let Some((flag, _)) = unsupported.iter().find(|(_, v)| v.is_some()) {
+        return Err(anyhow!(format!(
+            "Failed to apply {}: only the h2 subcommand can set it",
+            flag
+        )));
+    }
+    if opts.http2 {
+        builder = builder.http2_prior_knowledge();
+    }
+    if let Some(interval) = opts.h2_ping_interval {
+        builder = builder
+            .http2_keep_alive_interval(interval)
+            .http2_keep_alive_while_idle(true);
+    }
+    Ok(builder
+        .http2_initial_stream_window_size(opts.h2_window_size)
+        .http2_initial_connection_window_size(opts.h2_connection_window)
+        .http2_max_frame_size(opts.h2_max_frame_size))
+}
+
+/// A connection builder for the `h2` subcommand with all the settings applied.
+pub fn builder(opts: &Http2Opts) -> h2::client::Builder {
+    let mut builder = h2::client::Builder::new();
+    if let Some(size) = opts.h2_window_size {
+        builder.initial_window_size(size);
+    }
+    if let Some(size) = opts.h2_connection_window {
+        builder.initial_connection_window_size(size);
+    }
+    if let Some(size) = opts.h2_max_frame_size {
+        builder.max_frame_size(size);
+    }
+    if let Some(n) = opts.h2_max_concurrent_streams {
+        builder.max_concurrent_streams(n);
+    }
+    if let Some(size) = opts.h2_header_table_size {
+        builder.header_table_size(size);
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configure_refuses_unsupported_settings() {
+        let opts = Http2Opts {
+            h2_window_size: Some(1 << 16),
+            ..Default::default()
+        };
+        assert!(configure(reqwest::Client::builder(), &opts).is_ok());
+        let opts = Http2Opts {
+            h2_header_table_size: Some(0),
+            ..Default::default()
+        };
+        let err = configure(reqwest::Client::builder(), &opts).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to apply --h2-header-table-size: only the h2 subcommand can set it"
+        );
+    }
+}
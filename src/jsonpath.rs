@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Wildcard,
+    Slice(Option<i64>, Option<i64>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Step {
+    /// `..`: apply the selector at any depth below the current nodes
+    recursive: bool,
+    selector: Selector,
+}
+
+/// A JSONPath expression such as `$.data.items[*].name`. The jq-like forms
+/// `.data.items[].name` are accepted as well.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Path(Vec<Step>);
+
+impl FromStr for Path {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |msg: &str| anyhow!(format!("Failed to parse filter {}: {}", s, msg));
+        let rest = s.trim();
+        let rest = rest.strip_prefix('$').unwrap_or(rest);
+        let chars: Vec<char> = rest.chars().collect();
+        let mut steps = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let mut recursive = false;
+            match chars[i] {
+                '.' => {
+                    i += 1;
+                    if chars.get(i) == Some(&'.') {
+                        recursive = true;
+                        i += 1;
+                    }
+                    if chars.get(i) == Some(&'[') {
+                        continue_bracket(&chars, &mut i, recursive, &mut steps)
+                            .map_err(|e| err(&e))?;
+                        continue;
+                    }
+                    let start = i;
+                    while i < chars.len() && chars[i] != '.' && chars[i] != '[' {
+                        i += 1;
+                    }
+                    let name: String = chars[start..i].iter().collect();
+                    let selector = match name.as_str() {
+                        "" if i == chars.len() && steps.is_empty() && !recursive => break,
+                        "" => return Err(err("empty name")),
+                        "*" => Selector::Wildcard,
+                        _ => Selector::Name(name),
+                    };
+                    steps.push(Step {
+                        recursive,
+                        selector,
+                    });
+                }
+                '[' => continue_bracket(&chars, &mut i, false, &mut steps).map_err(|e| err(&e))?,
+                c => return Err(err(&format!("unexpected '{}'", c))),
+            }
+        }
+        Ok(Self(steps))
+    }
+}
+
+/// Parse a `[...]` selector starting at `chars[*i]`.
+fn continue_bracket(
+    chars: &[char],
+    i: &mut usize,
+    recursive: bool,
+    steps: &mut Vec<Step>,
+) -> Result<(), String> {
+    let close = chars[*i..]
+        .iter()
+        .position(|&c| c == ']')
+        .ok_or("missing ']'")?
+        + *i;
+    let inner: String = chars[*i + 1..close].iter().collect();
+    let inner = inner.trim();
+    *i = close + 1;
+    let parse_int = |s: &str| -> Result<Option<i64>, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            Ok(None)
+        } else {
+            s.parse().map(Some).map_err(|_| format!("bad index {}", s))
+        }
+    };
+    let selector = if inner.is_empty() || inner == "*" {
+        Selector::Wildcard
+    } else if let Some(name) = inner
+        .strip_prefix('\'')
+        .and_then(|n| n.strip_suffix('\''))
+        .or_else(|| inner.strip_prefix('"').and_then(|n| n.strip_suffix('"')))
+    {
+        Selector::Name(name.into())
+    } else if let Some((start, end)) = inner.split_once(':') {
+        Selector::Slice(parse_int(start)?, parse_int(end)?)
+    } else {
+        Selector::Index(parse_int(inner)?.unwrap())
+    };
+    steps.push(Step {
+        recursive,
+        selector,
+    });
+    Ok(())
+}
+
+impl Selector {
+    fn apply<'a>(&self, v: &'a Value, out: &mut Vec<&'a Value>) {
+        match (self, v) {
+            (Selector::Name(name), Value::Object(map)) => out.extend(map.get(name)),
+            (Selector::Index(i), Value::Array(items)) => {
+                let i = if *i < 0 { items.len() as i64 + i } else { *i };
+                out.extend(usize::try_from(i).ok().and_then(|i| items.get(i)));
+            }
+            (Selector::Wildcard, Value::Array(items)) => out.extend(items.iter()),
+            (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+            (Selector::Slice(start, end), Value::Array(items)) => {
+                let len = items.len() as i64;
+                let clamp = |i: i64| (if i < 0 { len + i } else { i }).clamp(0, len) as usize;
+                let (start, end) = (clamp(start.unwrap_or(0)), clamp(end.unwrap_or(len)));
+                if start < end {
+                    out.extend(items[start..end].iter());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `v` and everything nested in it, depth first.
+fn descendants<'a>(v: &'a Value, out: &mut Vec<&'a Value>) {
+    out.push(v);
+    match v {
+        Value::Array(items) => items.iter().for_each(|c| descendants(c, out)),
+        Value::Object(map) => map.values().for_each(|c| descendants(c, out)),
+        _ => {}
+    }
+}
+
+impl Path {
+    /// All values in `root` matched by the expression, in document order.
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut nodes = vec![root];
+        for step in self.0.iter() {
+            let mut next = Vec::new();
+            for node in nodes {
+                if step.recursive {
+                    let mut all = Vec::new();
+                    descendants(node, &mut all);
+                    for n in all {
+                        step.selector.apply(n, &mut next);
+                    }
+                } else {
+                    step.selector.apply(node, &mut next);
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn select(expr: &str, v: &Value) -> Vec<Value> {
+        let path: Path = expr.parse().unwrap();
+        path.select(v).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn path_works() {
+        let v = json!({
+            "data": { "items": [
+                { "name": "a", "tags": ["x"] },
+                { "name": "b", "tags": [] },
+                { "name": "c" },
+            ]},
+            "weird key": 1,
+        });
+        assert_eq!(select("$", &v), vec![v.clone()]);
+        assert_eq!(
+            select("$.data.items[*].name", &v),
+            vec![json!("a"), json!("b"), json!("c")]
+        );
+        assert_eq!(
+            select(".data.items[].name", &v),
+            vec![json!("a"), json!("b"), json!("c")]
+        );
+        assert_eq!(select("$.data.items[-1].name", &v), vec![json!("c")]);
+        assert_eq!(
+            select("$.data.items[0:2].name", &v),
+            vec![json!("a"), json!("b")]
+        );
+        assert_eq!(select("$['weird key']", &v), vec![json!(1)]);
+        assert_eq!(select("$..tags[0]", &v), vec![json!("x")]);
+        assert_eq!(select("$..name", &v).len(), 3);
+        assert_eq!(select("$.missing", &v), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn path_errors() {
+        assert!("$.a[".parse::<Path>().is_err());
+        assert!("$.a[x]".parse::<Path>().is_err());
+        assert!("$.a..".parse::<Path>().is_err());
+        assert!("$a".parse::<Path>().is_err());
+    }
+}
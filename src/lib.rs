@@ -0,0 +1,2855 @@
+use std::{collections::{HashMap, HashSet}, fmt, net::SocketAddr, path::PathBuf, str::FromStr, sync::{Arc, OnceLock}, time::{Duration, Instant, SystemTime}};
+
+use anyhow::{anyhow, Ok, Result};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use colored::{Colorize};
+use futures_util::StreamExt;
+use mime::Mime;
+use indicatif::ProgressBar;
+use reqwest::{header, multipart::Form, Client, Method, Request, RequestBuilder, Response, Url};
+use syntect::{parsing::SyntaxSet, highlighting::ThemeSet};
+
+mod archive;
+mod auth;
+mod bench;
+mod body;
+mod chunked;
+mod collection;
+mod compat;
+mod compression;
+mod config;
+mod conn;
+mod cookie;
+mod crash;
+mod curl;
+mod decode;
+mod diff;
+mod document;
+mod download;
+mod early;
+mod email;
+mod exchange;
+mod expect;
+mod freshness;
+mod graphql;
+mod har;
+// public for the benchmarks only
+#[doc(hidden)]
+pub mod highlight;
+mod http2;
+mod jsonpath;
+mod limit;
+mod lint;
+mod memory;
+mod mixed;
+mod mock;
+mod multiplex;
+mod oauth2;
+mod output;
+mod pac;
+mod paginate;
+mod prefetch;
+mod problem;
+mod proxy;
+mod query;
+mod repl;
+mod secrets;
+mod shadow;
+mod slo;
+mod sse;
+mod stats;
+mod store;
+mod template;
+mod transcript;
+mod tus;
+mod upload;
+mod usage;
+mod validators;
+mod watch;
+mod ws;
+
+pub use memory::Counting;
+
+/// Simple program to greet a person
+#[derive(Parser, Debug)]
+#[command(name = "Httpie")]
+#[command(author = "Yin Zhang. <miracleyin@live.com>")]
+#[command(version = "1.0")]
+#[command(about = "Http tool", long_about = None)]
+#[command(propagate_version = true)]
+pub struct Opts {
+    #[command(subcommand)]
+    subcmd: SubCommand,
+    /// Send a cookie, `name=value` (repeatable), to the host of the first
+    /// request only
+    #[arg(long = "cookie", global = true, value_parser = parse_templated_kv_pair)]
+    cookies: Vec<KvPair>,
+    /// Load cookies from this file and save received ones back to it
+    /// (JSON for `*.json`, Netscape cookies.txt format otherwise)
+    #[arg(long, global = true)]
+    cookie_jar: Option<PathBuf>,
+    /// Build the Accept header from a preference list, e.g. `json,xml;q=0.8`,
+    /// and report which representation the server picked
+    #[arg(long, global = true, value_parser = parse_negotiate)]
+    negotiate: Option<String>,
+    /// Accept-Language to send, e.g. `de-DE,en;q=0.7`
+    #[arg(long, global = true, value_parser = parse_lang_header)]
+    lang_header: Option<String>,
+    /// User-Agent to send instead of `Rust Httpie`
+    #[arg(long, global = true)]
+    user_agent: Option<String>,
+    /// Default headers removed by `Name:` items on the command line
+    #[arg(skip)]
+    unset_headers: Vec<String>,
+    /// Hosts of a batch whose names failed to resolve, with the error
+    #[arg(skip)]
+    unresolved: HashMap<String, String>,
+    /// Print an equivalent curl command instead of sending the request
+    #[arg(long, global = true)]
+    curl: bool,
+    /// Decompress bodies that are gzip data despite having no Content-Encoding
+    #[arg(long, global = true)]
+    gunzip: bool,
+    /// Offer brotli, zstd, gzip and deflate, decode what the server picks and
+    /// report the encoding and compression ratio
+    #[arg(long, global = true)]
+    compressed: bool,
+    /// Gzip the request body and send it with Content-Encoding: gzip
+    #[arg(long, global = true)]
+    compress: bool,
+    /// Show Content-Encoded response bodies as the raw bytes received instead
+    /// of decompressing them
+    #[arg(long, global = true)]
+    no_decompress: bool,
+    /// Sort response headers by name and JSON object keys, for output that
+    /// diffs well
+    #[arg(long, global = true)]
+    sorted: bool,
+    /// Print only the values of a JSON body matched by a JSONPath expression,
+    /// e.g. `$.data.items[*].name`
+    #[arg(long, global = true, value_parser = parse_filter)]
+    filter: Option<jsonpath::Path>,
+    /// Print filtered strings without quotes and other values compactly,
+    /// one per line
+    #[arg(short, long, global = true, requires = "filter")]
+    raw: bool,
+    /// Print the exchange as one JSON document (status, headers, body, timing
+    /// and redirects) for other tools to consume
+    #[arg(long, global = true, conflicts_with = "filter")]
+    json_output: bool,
+    /// Print only this of each response, e.g. `final-url` for where
+    /// redirects ended up
+    #[arg(long, global = true, value_enum, conflicts_with_all = ["filter", "json_output"])]
+    get: Option<exchange::Field>,
+    /// Append each exchange to this HTTP Archive (HAR) file, for browser
+    /// devtools, proxies and `replay-har`
+    #[arg(long, global = true)]
+    har_out: Option<PathBuf>,
+    /// Behave for CI logs: no colors, progress bars or prompts, exit codes
+    /// as with `--check-status`, and a one line summary of each response
+    #[arg(long, global = true)]
+    ci: bool,
+    /// Print at most this much of a body, e.g. `64KiB`, saying how much was
+    /// left out
+    #[arg(long, global = true, value_parser = parse_size)]
+    max_body_print: Option<u64>,
+    /// Give up on responses larger than this, e.g. `100MiB`, downloads
+    /// included
+    #[arg(long, global = true, value_parser = parse_size)]
+    max_response_size: Option<u64>,
+    /// Write response bodies to the terminal at most this fast, e.g.
+    /// `50KiB/s`, for slow links that would otherwise fall behind
+    #[arg(long, global = true, value_parser = parse_render_rate)]
+    max_render_rate: Option<u64>,
+    /// Report allocations while sending and reading (`request`) and while
+    /// decoding and printing (`format`), and the peak RSS, on stderr
+    #[arg(long, global = true)]
+    profile_memory: bool,
+    /// Decode response bodies with this charset, e.g. `gb2312` or
+    /// `shift_jis`, whatever the Content-Type says
+    #[arg(long, global = true, value_parser = parse_charset)]
+    response_charset: Option<&'static encoding_rs::Encoding>,
+    /// Show binary bodies as JSON: `msgpack`, `protobuf`, or
+    /// `protobuf:<descriptor.bin>:<MessageType>` for field names from a
+    /// `protoc --descriptor_set_out` file
+    #[arg(long, global = true)]
+    decode: Option<decode::Format>,
+    /// Unpack a zip or tar response into this directory, after listing it
+    #[arg(long, global = true)]
+    extract: Option<PathBuf>,
+    /// Keep response bodies in ~/.httpie/store under their SHA-256, for
+    /// `show` to print or diff later
+    #[arg(long, global = true)]
+    store: bool,
+    /// Ignore the per-host `rate_limit`s of the config for this run
+    #[arg(long, global = true)]
+    no_shared_limit: bool,
+    /// Send at most this many requests a second when fetching several URLs
+    /// or with `bench`
+    #[arg(long, global = true, value_parser = parse_rate)]
+    rate: Option<f64>,
+    /// Wait this long between the starts of those requests, in milliseconds
+    /// or with a unit, e.g. `250` or `1.5s`
+    #[arg(long, global = true, value_parser = parse_delay)]
+    delay: Option<Duration>,
+    /// Fail unless the response status is one of these, e.g. `200,204` or
+    /// `2xx`
+    #[arg(long, global = true)]
+    expect_status: Option<expect::StatusExpect>,
+    /// Fail unless the response has this header, or with `Name:value` this
+    /// value (repeatable)
+    #[arg(long, global = true)]
+    expect_header: Vec<expect::HeaderExpect>,
+    /// Fail unless the body contains this text (repeatable)
+    #[arg(long, global = true)]
+    expect_body_contains: Vec<String>,
+    /// Fail unless the value at a JSONPath of the body is this, e.g.
+    /// `$.data.count=3`, or with the path alone that it matches anything
+    /// (repeatable)
+    #[arg(long, global = true)]
+    expect_json: Vec<expect::JsonExpect>,
+    /// Choose the proxy per request with a proxy auto-config script (URL or file)
+    #[arg(long, global = true)]
+    proxy_pac: Option<String>,
+    /// Resolve `{{VAR}}` placeholders in URLs, headers and body values from
+    /// this file as well as the environment
+    #[arg(long, global = true)]
+    env_file: Option<PathBuf>,
+    /// Scheme for URLs given without one, e.g. `example.com/api`
+    #[arg(long, global = true, default_value = "http", value_parser = ["http", "https"])]
+    default_scheme: String,
+    /// Use the base URL and headers of this profile from the config
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Mirror each request to this endpoint (origin plus optional path
+    /// prefix) concurrently and diff its response against the primary one
+    #[arg(long, global = true, value_parser = parse_url)]
+    shadow: Option<String>,
+    /// Send requests over this Unix domain socket instead of TCP, e.g.
+    /// `/var/run/docker.sock`; the URL still gives the path and Host
+    #[arg(long, global = true)]
+    unix_socket: Option<PathBuf>,
+    /// Connect to `addr` for `host`, as `host:port:addr[,addr...]` like curl
+    /// (repeatable); the override applies to every port of the host
+    #[arg(long, global = true, value_parser = parse_resolve)]
+    resolve: Vec<(String, Vec<SocketAddr>)>,
+    /// Priority header (RFC 9218) to send: urgency 0-7 and incremental,
+    /// e.g. `u=1` or `u=5,i`
+    #[arg(long, global = true, value_parser = parse_priority)]
+    priority: Option<String>,
+    /// Sign requests with AWS SigV4 using the keys of this profile in
+    /// ~/.aws/credentials; implies `--auth-type aws4`
+    #[arg(long, global = true)]
+    aws_profile: Option<String>,
+    /// Print the canonical request and string to sign along with the
+    /// signature, for debugging signature mismatches
+    #[arg(long, global = true)]
+    show_canonical: bool,
+    /// Send the OAuth2 token saved under this name by `oauth2`, fetching a
+    /// new one when it has expired
+    #[arg(long, global = true, conflicts_with_all = ["auth", "auth_type", "aws_profile"])]
+    session: Option<String>,
+    /// Send the request several times in a row and check a latency
+    /// objective, e.g. `p95=300ms@20` (repeatable); misses exit non-zero
+    #[arg(long, global = true)]
+    slo: Vec<slo::Objective>,
+    #[command(flatten)]
+    http2: Http2Opts,
+    #[command(flatten)]
+    conditional: ConditionalOpts,
+    #[command(flatten)]
+    compat: CompatOpts,
+}
+
+/// Flags that work as in HTTPie for Python, so that scripts and habits
+/// carry over.
+#[derive(Args, Debug, Default, Clone)]
+#[command(next_help_heading = "HTTPie compatibility")]
+struct CompatOpts {
+    /// Send body fields as a JSON object (the default) and prefer JSON back
+    #[arg(short, long, global = true, conflicts_with = "form")]
+    json: bool,
+    /// Send body fields form encoded, or as multipart/form-data with files
+    #[arg(short, long, global = true)]
+    form: bool,
+    /// Credentials, `user[:password]` with the password prompted for when
+    /// left out, or a token with `--auth-type bearer`
+    #[arg(short, long, global = true)]
+    auth: Option<String>,
+    /// How `--auth` is sent, `basic` unless `--aws-profile` is given
+    #[arg(short = 'A', long, global = true, value_enum)]
+    auth_type: Option<compat::AuthType>,
+    /// The scheme for `--auth`, set up once the command line is parsed
+    #[arg(skip)]
+    auth_scheme: Option<Arc<dyn auth::Auth>>,
+    /// `yes` to check TLS certificates, `no` to skip the check, or a CA
+    /// bundle to check them against
+    #[arg(long, global = true, default_value = "yes", value_parser = compat::parse_verify)]
+    verify: compat::Verify,
+    /// What to print: `H` request headers, `B` request body, `h` response
+    /// headers, `b` response body
+    #[arg(short, long = "print", global = true, default_value = "hb", value_parser = compat::parse_print)]
+    print: compat::Print,
+    /// Print the whole exchange, like `--print HBhb`
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Save the response body to a file, named after the response, instead
+    /// of printing it
+    #[arg(short, long, global = true)]
+    download: bool,
+    /// File to save to with `--download`
+    #[arg(short, long, global = true, requires = "download")]
+    output: Option<PathBuf>,
+    /// Exit with 3, 4 or 5 when a response is a redirect that was not
+    /// followed, a client error or a server error
+    #[arg(long, global = true)]
+    check_status: bool,
+}
+
+impl CompatOpts {
+    fn printing(&self) -> compat::Print {
+        if self.verbose {
+            compat::Print::ALL
+        } else {
+            self.print
+        }
+    }
+}
+
+/// Conditional requests, for checking how APIs and CDNs revalidate.
+#[derive(Args, Debug, Default, Clone)]
+#[command(next_help_heading = "Conditional requests")]
+struct ConditionalOpts {
+    /// Send If-Modified-Since: an HTTP date, or a file whose modification
+    /// time is used
+    #[arg(long, global = true, value_parser = validators::parse_since)]
+    if_modified_since: Option<SystemTime>,
+    /// Send If-None-Match with this entity tag (quoted if needed)
+    #[arg(long, global = true)]
+    if_none_match: Option<String>,
+    /// Send the ETag and Last-Modified last seen for the URL as validators,
+    /// and remember the ones from the response
+    #[arg(long, global = true)]
+    cached: bool,
+    /// Where `--cached` keeps validators [default: ~/.httpie/validators.json]
+    #[arg(long, global = true)]
+    validator_cache: Option<PathBuf>,
+}
+
+/// HTTP/2 connection settings, for tuning and for reproducing flow-control
+/// problems. They apply to HTTP/2 connections only: negotiated over TLS, or
+/// forced with `--http2`.
+#[derive(Args, Debug, Default, Clone)]
+#[command(next_help_heading = "HTTP/2")]
+struct Http2Opts {
+    /// Speak HTTP/2 without negotiating it (prior knowledge), also over http://
+    #[arg(long, global = true)]
+    http2: bool,
+    /// Initial flow-control window of each stream, e.g. `16KiB`
+    #[arg(long, global = true, value_parser = parse_window_size)]
+    h2_window_size: Option<u32>,
+    /// Initial flow-control window of the connection as a whole
+    #[arg(long, global = true, value_parser = parse_window_size)]
+    h2_connection_window: Option<u32>,
+    /// Largest frame payload the server may send us (16KiB to 16MiB)
+    #[arg(long, global = true, value_parser = parse_frame_size)]
+    h2_max_frame_size: Option<u32>,
+    /// SETTINGS_MAX_CONCURRENT_STREAMS to advertise (`h2` subcommand only)
+    #[arg(long, global = true)]
+    h2_max_concurrent_streams: Option<u32>,
+    /// SETTINGS_HEADER_TABLE_SIZE to advertise, the HPACK table the server
+    /// may use for response headers (`h2` subcommand only)
+    #[arg(long, global = true, value_parser = parse_u32_size)]
+    h2_header_table_size: Option<u32>,
+    /// Send a PING at this interval, e.g. `500ms` or `10s`
+    #[arg(long, global = true, value_parser = parse_duration)]
+    h2_ping_interval: Option<Duration>,
+}
+
+#[derive(Subcommand, Debug)]
+enum SubCommand {
+    Get(Get),
+    Post(Post),
+    Upload(Upload),
+    Tus(Tus),
+    Bench(Bench),
+    Freshness(Freshness),
+    ImportCurl(ImportCurl),
+    Graphql(Graphql),
+    Diff(Diff),
+    Sse(Sse),
+    Replay(Replay),
+    ReplayHar(ReplayHar),
+    Ws(Ws),
+    Proxy(Proxy),
+    ServeRecord(ServeRecord),
+    Save(Save),
+    Run(Run),
+    H2(H2),
+    /// Open an interactive prompt for sending requests
+    Repl(Repl),
+    /// Print a shell completion script
+    Completions(Completions),
+    /// Print the man page
+    Man,
+    /// List the aliases defined in ~/.httpie/config.toml and .httpie.toml
+    Alias,
+    Lint(Lint),
+    Show(Show),
+    Oauth2(Oauth2),
+    Usage(Usage),
+}
+
+// get
+#[derive(Args, Debug)]
+struct Get {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// More URLs to fetch concurrently, and query parameters for all of them:
+    /// `key==value`, or `key:=json` where an array such as `ids:=[1,2,3]`
+    /// gives several values; `Name:value` headers, `Name;` for an empty one
+    /// and `Name:` to leave a default header out (`localhost:8080` and
+    /// `example.com:8080` are hosts, other hosts with a port need a scheme)
+    #[arg(value_parser = parse_get_item)]
+    items: Vec<GetItem>,
+    /// How array values are written into the query string
+    #[arg(long, value_enum, default_value_t)]
+    array_format: query::ArrayFormat,
+    /// How many of several URLs are fetched at once
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    parallel: u16,
+    /// Fetch once per Accept-Language (comma separated) and diff the bodies
+    #[arg(long, value_delimiter = ',', value_parser = parse_lang_header)]
+    locale_matrix: Vec<String>,
+    /// Send the request as TLS 1.3 early data (0-RTT) on a resumed connection
+    /// and report whether the server accepted it; uses its own connections,
+    /// so cookies and proxies do not apply
+    #[arg(long)]
+    early_data: bool,
+    /// Fetch again at this interval, e.g. `10s`, one line per response, and
+    /// flag bodies whose size or entropy is sharply off the recent ones
+    #[arg(long, value_parser = parse_duration, conflicts_with_all = ["locale_matrix", "early_data"])]
+    watch: Option<Duration>,
+    /// Fetch the pages after this one, following `Link: <...>; rel="next"`
+    /// or `--next-field`; JSON array pages are joined into one array
+    #[arg(long, conflicts_with_all = ["locale_matrix", "early_data", "watch"])]
+    follow_pagination: bool,
+    /// Stop following pages after this many
+    #[arg(long, default_value_t = 10, requires = "follow_pagination", value_parser = clap::value_parser!(u32).range(1..))]
+    max_pages: u32,
+    /// JSONPath of the next page's URL in JSON bodies, e.g. `$.links.next`,
+    /// for APIs without Link headers
+    #[arg(long, requires = "follow_pagination", value_parser = parse_filter)]
+    next_field: Option<jsonpath::Path>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum GetItem {
+    Url(String),
+    Query(query::QueryItem),
+    /// `Name:value` a request header, `Name;` one with an empty value
+    Header(KvPair),
+    /// `Name:`, a default header not to send
+    Unset(String),
+}
+
+fn parse_get_item(s: &str) -> Result<GetItem> {
+    if query::is_item(s) {
+        return Ok(GetItem::Query(query::parse_item(s)?));
+    }
+    // what looks like a host, as `:3000` or `example.com:8080`, is a URL
+    let name = s.split([':', ';']).next().unwrap_or_default();
+    let host = s.contains("://") || name.contains(['.', '[', '/']) || name.eq_ignore_ascii_case("localhost");
+    match parse_templated_body_item(s) {
+        Result::Ok(BodyItem::Header(pair)) if !host => Ok(GetItem::Header(pair)),
+        Result::Ok(BodyItem::Unset(name)) if !host => Ok(GetItem::Unset(name)),
+        _ => Ok(GetItem::Url(parse_url(s)?)),
+    }
+}
+
+/// `--default-scheme`, found ahead of clap like the env file so that
+/// `parse_url` can use it.
+static DEFAULT_SCHEME: OnceLock<String> = OnceLock::new();
+
+/// The value of `flag` in `args`, given as `flag value` or `flag=value`,
+/// for the few options needed before clap runs.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == flag {
+            return iter.next().map(String::as_str);
+        }
+        if let Some(value) = arg.strip_prefix(flag).and_then(|r| r.strip_prefix('=')) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn parse_url(s: &str) -> Result<String> {
+    // a path is relative to the configured base URL
+    let s = match config::base() {
+        Some(base) if s.starts_with('/') => format!("{}{}", base.trim_end_matches('/'), s),
+        _ => s.to_string(),
+    };
+    let s = template::render(&s)?;
+    expand_url(&s, DEFAULT_SCHEME.get().map_or("http", String::as_str))
+}
+
+/// Expand HTTPie's shorthands, `:3000/path` for localhost and `example.com`
+/// without a scheme, and check that the result is an http(s) URL.
+fn expand_url(s: &str, default_scheme: &str) -> Result<String> {
+    let s = s.trim();
+    let expanded = if let Some(rest) = s.strip_prefix(':') {
+        let sep = if rest.is_empty() || rest.starts_with('/') { "" } else { ":" };
+        format!("{}://localhost{}{}", default_scheme, sep, rest)
+    } else if s.contains("://") {
+        s.to_string()
+    } else {
+        let authority = s.split(['/', '?', '#']).next().unwrap_or_default();
+        let host_port = authority.rsplit('@').next().unwrap_or_default();
+        let looks_like_host = host_port.contains(['.', ':'])
+            || host_port.starts_with('[')
+            || host_port.eq_ignore_ascii_case("localhost");
+        if !looks_like_host {
+            return Err(anyhow!(format!(
+                "Failed to parse URL {}: expected e.g. example.com/path, :3000/path or http://{}",
+                s, s
+            )));
+        }
+        format!("{}://{}", default_scheme, s)
+    };
+    let url: Url = expanded.parse().map_err(|e: url::ParseError| {
+        let reason = match e {
+            url::ParseError::EmptyHost => "the host is missing".to_string(),
+            url::ParseError::InvalidPort => "the port must be a number from 0 to 65535".to_string(),
+            url::ParseError::InvalidIpv6Address => "the IPv6 address is malformed".to_string(),
+            url::ParseError::InvalidIpv4Address => "the IPv4 address is malformed".to_string(),
+            url::ParseError::InvalidDomainCharacter => {
+                "the host contains a character not allowed in domain names".to_string()
+            }
+            e => e.to_string(),
+        };
+        anyhow!(format!("Failed to parse URL {}: {}", s, reason))
+    })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!(format!(
+            "Failed to parse URL {}: unsupported scheme {}, use http or https",
+            s,
+            url.scheme()
+        )));
+    }
+    Ok(expanded)
+}
+
+fn parse_template(s: &str) -> Result<String> {
+    template::render(s)
+}
+
+/// Expand a `json,xml;q=0.8` style list into an Accept header value.
+fn parse_negotiate(s: &str) -> Result<String> {
+    let mut ranges = Vec::new();
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, params) = entry.split_once(';').unwrap_or((entry, ""));
+        let media = match name.trim() {
+            "json" => "application/json",
+            "xml" => "application/xml",
+            "html" => "text/html",
+            "yaml" => "application/yaml",
+            "text" => "text/plain",
+            "csv" => "text/csv",
+            "*" => "*/*",
+            other if other.contains('/') => other,
+            other => return Err(anyhow!(format!("Unknown media type {}", other))),
+        };
+        let mut range = media.to_string();
+        for param in params.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            check_q(param, entry)?;
+            range += &format!(";{}", param);
+        }
+        ranges.push(range);
+    }
+    if ranges.is_empty() {
+        return Err(anyhow!(format!("Failed to parse {}", s)));
+    }
+    Ok(ranges.join(", "))
+}
+
+fn check_q(param: &str, entry: &str) -> Result<()> {
+    if let Some(q) = param.strip_prefix("q=") {
+        if !q.parse::<f32>().is_ok_and(|v| (0.0..=1.0).contains(&v)) {
+            return Err(anyhow!(format!("Invalid q-value {} in {}", q, entry)));
+        }
+    }
+    Ok(())
+}
+
+/// An RFC 9218 Priority value, `u=3` for the urgency (0 to 7) and `i` for
+/// incremental, put back in the order the RFC writes them.
+fn parse_priority(s: &str) -> Result<String> {
+    let mut urgency = None;
+    let mut incremental = false;
+    for param in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match param.split_once('=') {
+            Some(("u", v)) => match v.parse::<u8>() {
+                Result::Ok(u) if u <= 7 => urgency = Some(u),
+                _ => return Err(anyhow!(format!("Failed to parse {}: urgency must be 0 to 7", s))),
+            },
+            None if param == "i" => incremental = true,
+            Some(("i", "?1")) => incremental = true,
+            Some(("i", "?0")) => incremental = false,
+            _ => return Err(anyhow!(format!("Failed to parse {}: unknown parameter {}", s, param))),
+        }
+    }
+    let mut params: Vec<String> = urgency.map(|u| format!("u={}", u)).into_iter().collect();
+    if incremental {
+        params.push("i".into());
+    }
+    if params.is_empty() {
+        return Err(anyhow!(format!("Failed to parse {}", s)));
+    }
+    Ok(params.join(", "))
+}
+
+/// Validate a `de-DE,en;q=0.7` style language list for Accept-Language.
+fn parse_lang_header(s: &str) -> Result<String> {
+    let mut langs = Vec::new();
+    for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (tag, params) = entry.split_once(';').unwrap_or((entry, ""));
+        let tag = tag.trim();
+        let valid = tag == "*"
+            || tag
+                .split('-')
+                .all(|p| !p.is_empty() && p.len() <= 8 && p.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !valid {
+            return Err(anyhow!(format!("Invalid language tag {}", tag)));
+        }
+        let mut lang = tag.to_string();
+        for param in params.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            check_q(param, entry)?;
+            lang += &format!(";{}", param);
+        }
+        langs.push(lang);
+    }
+    if langs.is_empty() {
+        return Err(anyhow!(format!("Failed to parse {}", s)));
+    }
+    Ok(langs.join(", "))
+}
+
+// post
+#[derive(Args, Debug)]
+struct Post {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Body items: `key=value` fields, `key@path` file uploads, or `@path` raw
+    /// body; `Name:value` headers, `Name;` for an empty one and `Name:` to
+    /// leave a default header out
+    #[arg(value_parser = parse_templated_body_item)]
+    body: Vec<BodyItem>,
+}
+
+// upload
+#[derive(Args, Debug)]
+struct Upload {
+    file: String,
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Size of each part, e.g. `8MiB` or `5000000`
+    #[arg(long, default_value = "8MiB", value_parser = parse_size)]
+    part_size: u64,
+    /// Number of parts uploaded concurrently
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    parallel: u16,
+    /// Resume state file, `<file>.upload-state` by default
+    #[arg(long)]
+    state: Option<PathBuf>,
+}
+
+// tus
+#[derive(Args, Debug)]
+struct Tus {
+    #[command(subcommand)]
+    cmd: TusCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum TusCommand {
+    /// Upload a file with the tus resumable upload protocol
+    Upload(TusUpload),
+}
+
+#[derive(Args, Debug)]
+struct TusUpload {
+    file: String,
+    /// tus creation endpoint
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Size of each PATCH request, e.g. `8MiB`
+    #[arg(long, default_value = "8MiB", value_parser = parse_size)]
+    chunk_size: u64,
+    /// Resume state file, `<file>.tus-state` by default
+    #[arg(long)]
+    state: Option<PathBuf>,
+}
+
+// bench
+#[derive(Args, Debug)]
+struct Bench {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Total number of requests to send
+    #[arg(short = 'n', long, default_value_t = 100)]
+    requests: usize,
+    /// Number of requests in flight at once
+    #[arg(short, long, default_value_t = 1)]
+    concurrency: usize,
+    /// Start with one request in flight and ramp up while responses stay
+    /// fast and successful, backing off on errors, 429 or 5xx statuses and
+    /// slow responses (AIMD); `-c` is then the most allowed
+    #[arg(long)]
+    adaptive: bool,
+    /// Request method
+    #[arg(short = 'X', long, default_value = "GET", value_parser = parse_method)]
+    method: Method,
+    /// Body items, as for `post`
+    #[arg(value_parser = parse_templated_body_item)]
+    body: Vec<BodyItem>,
+}
+
+// freshness
+#[derive(Args, Debug)]
+struct Freshness {
+    #[arg(value_parser = parse_url)]
+    url: String,
+}
+
+// import-curl
+#[derive(Args, Debug)]
+struct ImportCurl {
+    /// The curl command, as one quoted string or as separate words after `--`
+    #[arg(required = true, num_args = 1.., allow_hyphen_values = true, trailing_var_arg = true)]
+    command: Vec<String>,
+}
+
+// graphql
+#[derive(Args, Debug)]
+struct Graphql {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// GraphQL document, or a file containing it
+    #[arg(short, long)]
+    query: String,
+    /// Variable, `name=value`; JSON values keep their type (repeatable)
+    #[arg(long = "var", value_parser = parse_templated_kv_pair)]
+    vars: Vec<KvPair>,
+    /// Operation to run when the document defines several
+    #[arg(long)]
+    operation_name: Option<String>,
+}
+
+// sse
+#[derive(Args, Debug)]
+struct Sse {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Record received events with timestamps to a transcript file
+    #[arg(long)]
+    record: Option<PathBuf>,
+}
+
+// ws
+#[derive(Args, Debug)]
+struct Ws {
+    /// `ws://` or `wss://` URL
+    #[arg(value_parser = parse_template)]
+    url: String,
+    /// Record messages with timestamps to a transcript file
+    #[arg(long)]
+    record: Option<PathBuf>,
+}
+
+// h2
+#[derive(Args, Debug)]
+struct H2 {
+    /// Origin to connect to; `http://` means cleartext HTTP/2 (prior knowledge)
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// Print response bodies as well as stream timings
+    #[arg(long)]
+    body: bool,
+}
+
+// repl
+#[derive(Args, Debug)]
+struct Repl {
+    /// Base URL that paths typed at the prompt are relative to [default:
+    /// the `base` of the config]
+    #[arg(value_parser = parse_url)]
+    base: Option<String>,
+}
+
+// completions
+#[derive(Args, Debug)]
+struct Completions {
+    /// Shell to generate a completion script for
+    #[arg(value_enum)]
+    shell: clap_complete::Shell,
+}
+
+// replay
+#[derive(Args, Debug)]
+struct Replay {
+    /// Transcript written by `--record`
+    transcript: PathBuf,
+    /// Playback speed relative to the recording; 0 prints without delays
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+}
+
+// replay-har
+/// Send the requests recorded in a HAR file again, in order
+#[derive(Args, Debug)]
+struct ReplayHar {
+    /// HAR file from `--har-out`, browser devtools or a proxy
+    har: PathBuf,
+    /// Only replay entries whose URL contains this text
+    #[arg(long)]
+    only: Option<String>,
+}
+
+// proxy
+#[derive(Args, Debug)]
+struct Proxy {
+    /// Port, or address and port, to listen on
+    #[arg(long, default_value = "8888", value_parser = parse_listen)]
+    listen: SocketAddr,
+    /// Decrypt HTTPS traffic with certificates from a local CA; clients need
+    /// to trust its `ca.pem`
+    #[arg(long)]
+    intercept: bool,
+    /// Where the CA is generated and kept [default: ~/.httpie/ca]
+    #[arg(long)]
+    ca_dir: Option<PathBuf>,
+    /// Accept invalid certificates from upstream servers
+    #[arg(long)]
+    insecure: bool,
+}
+
+// serve-record
+/// Run a local server that forwards to a target and records each exchange,
+/// or answers from earlier recordings for offline testing
+#[derive(Args, Debug)]
+struct ServeRecord {
+    /// Port, or address and port, to listen on
+    #[arg(long, default_value = "8080", value_parser = parse_listen)]
+    port: SocketAddr,
+    /// Where to forward requests, optionally with a path prefix
+    #[arg(long, required_unless_present = "replay", value_parser = parse_url)]
+    target: Option<String>,
+    /// Save every exchange as a file in this directory
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Answer from the exchanges recorded in this directory instead of
+    /// forwarding
+    #[arg(long, conflicts_with = "target")]
+    replay: Option<PathBuf>,
+    /// Accept invalid certificates from the target
+    #[arg(long)]
+    insecure: bool,
+}
+
+// diff
+/// Fetch two URLs and show how their responses differ, with JSON bodies
+/// normalized first
+#[derive(Args, Debug)]
+struct Diff {
+    #[arg(value_parser = parse_url)]
+    url: String,
+    /// URL to compare against, e.g. staging against production
+    #[arg(value_parser = parse_url, required_unless_present = "against")]
+    other: Option<String>,
+    /// Compare the body against this file instead of a second response
+    #[arg(long, conflicts_with = "other")]
+    against: Option<PathBuf>,
+    /// Headers to compare along with the status and body, comma separated
+    #[arg(long, value_delimiter = ',', default_value = "content-type")]
+    headers: Vec<String>,
+}
+
+// show
+/// Print a body kept with `--store`, or diff it against another, by any
+/// unique prefix of its hash
+#[derive(Args, Debug)]
+struct Show {
+    hash: String,
+    /// Hash of a second stored body to diff against
+    #[arg(long)]
+    diff: Option<String>,
+}
+
+// lint
+/// Check saved requests, aliases and profiles without sending anything:
+/// unresolved `{{VAR}}`s, invalid URLs and headers, missing `@` files
+#[derive(Args, Debug)]
+struct Lint {
+    /// Collection file
+    #[arg(long, default_value = COLLECTION)]
+    collection: PathBuf,
+}
+
+// usage
+/// Show how often each subcommand and flag was used, as counted with
+/// `usage_stats = true` in the config, and the flags never used
+#[derive(Args, Debug)]
+struct Usage {
+    /// Forget what was counted so far
+    #[arg(long)]
+    reset: bool,
+}
+
+// oauth2
+/// Get an OAuth2 access token and save it for `--session` (`default`
+/// unless `--session` names another)
+#[derive(Args, Debug)]
+struct Oauth2 {
+    #[arg(long, value_enum, default_value_t)]
+    flow: oauth2::Flow,
+    /// Token endpoint
+    #[arg(long, value_parser = parse_url)]
+    token_url: String,
+    /// Device authorization endpoint, for `--flow device-code`
+    #[arg(long, value_parser = parse_url, required_if_eq("flow", "device-code"))]
+    device_url: Option<String>,
+    #[arg(long)]
+    client_id: String,
+    /// Left out for public clients of the device flow
+    #[arg(long, value_parser = parse_template)]
+    client_secret: Option<String>,
+    /// Space separated scopes to ask for
+    #[arg(long)]
+    scope: Option<String>,
+}
+
+// save
+#[derive(Args, Debug)]
+struct Save {
+    /// Name to save the request under, replacing any request of that name
+    name: String,
+    /// URL; `{{VAR}}` placeholders here and in headers and body items are
+    /// saved as is and resolved by `run`
+    url: String,
+    /// Body items: `key=value` fields, `key@path` file uploads, or `@path` raw body
+    #[arg(value_parser = parse_body_item)]
+    body: Vec<BodyItem>,
+    /// Method; POST when there are body items, GET otherwise
+    #[arg(short = 'X', long, value_parser = parse_method)]
+    method: Option<Method>,
+    /// Header, `Name: value` (repeatable)
+    #[arg(short = 'H', long = "header", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+    /// Collection file
+    #[arg(long, default_value = COLLECTION)]
+    collection: PathBuf,
+}
+
+// run
+#[derive(Args, Debug)]
+struct Run {
+    /// Name of a saved request
+    name: String,
+    /// Override, `key=value`: `url`, `method`, a saved header or a body
+    /// field (repeatable)
+    #[arg(long = "set", value_parser = parse_kv_pair)]
+    set: Vec<KvPair>,
+    /// Collection file
+    #[arg(long, default_value = COLLECTION)]
+    collection: PathBuf,
+}
+
+const COLLECTION: &str = "httpie-collection.json";
+
+fn parse_header(s: &str) -> Result<(String, String)> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!(format!("Failed to parse header {}: expected `Name: value`", s)))?;
+    let name: header::HeaderName = name.trim().parse()?;
+    let value: header::HeaderValue = value.trim().parse()?;
+    Ok((name.to_string(), value.to_str()?.to_string()))
+}
+
+/// A curl style `host:port:addr[,addr]` pin, e.g.
+/// `api.test:443:10.0.0.1,[::1]`; a port of `*` stands for any.
+fn parse_resolve(s: &str) -> Result<(String, Vec<SocketAddr>)> {
+    let err = || anyhow!(format!("Failed to parse {}: expected host:port:addr", s));
+    let (host, rest) = s.split_once(':').ok_or_else(err)?;
+    let (port, addrs) = rest.split_once(':').ok_or_else(err)?;
+    // `*` matches any port in curl, which is all reqwest can do anyway
+    let port: u16 = if port == "*" { 0 } else { port.parse().map_err(|_| err())? };
+    let addrs = addrs
+        .split(',')
+        .map(|a| {
+            let a = a.trim().trim_start_matches('[').trim_end_matches(']');
+            a.parse().map(|ip| SocketAddr::new(ip, port)).map_err(|_| err())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    if host.is_empty() {
+        return Err(err());
+    }
+    Ok((host.to_ascii_lowercase(), addrs))
+}
+
+/// `8888` listens on localhost only, `0.0.0.0:8888` everywhere.
+fn parse_listen(s: &str) -> Result<SocketAddr> {
+    s.parse::<u16>()
+        .map(|port| SocketAddr::from(([127, 0, 0, 1], port)))
+        .or_else(|_| s.parse())
+        .map_err(|_| anyhow!(format!("Failed to parse listen address {}", s)))
+}
+
+fn parse_filter(s: &str) -> Result<jsonpath::Path> {
+    s.parse()
+}
+
+fn parse_method(s: &str) -> Result<Method> {
+    Ok(s.to_ascii_uppercase().parse()?)
+}
+
+fn parse_u32_size(s: &str) -> Result<u32> {
+    u32::try_from(parse_size(s)?).map_err(|_| anyhow!(format!("Failed to parse size {}: too large", s)))
+}
+
+fn parse_window_size(s: &str) -> Result<u32> {
+    // flow-control windows are 31 bit (RFC 9113, section 6.9.1)
+    let n = parse_u32_size(s)?;
+    if n > (1 << 31) - 1 {
+        return Err(anyhow!(format!("Failed to parse window size {}: at most 2^31-1", s)));
+    }
+    Ok(n)
+}
+
+fn parse_frame_size(s: &str) -> Result<u32> {
+    let n = parse_u32_size(s)?;
+    if !(1 << 14..1 << 24).contains(&n) {
+        return Err(anyhow!(format!(
+            "Failed to parse frame size {}: must be between 16KiB and 16MiB - 1",
+            s
+        )));
+    }
+    Ok(n)
+}
+
+/// `250ms`, `10s`, `2m`, or a number of seconds.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: f64 = n.parse().map_err(|_| anyhow!(format!("Failed to parse duration {}", s)))?;
+    let secs = match unit.trim() {
+        "ms" => n / 1000.0,
+        "" | "s" => n,
+        "m" => n * 60.0,
+        "h" => n * 3600.0,
+        _ => return Err(anyhow!(format!("Failed to parse duration {}", s))),
+    };
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| anyhow!(format!("Failed to parse duration {}: out of range", s)))
+}
+
+fn parse_rate(s: &str) -> Result<f64> {
+    match s.parse::<f64>() {
+        Result::Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(anyhow!(format!("Failed to parse rate {}: not a number of requests a second", s))),
+    }
+}
+
+/// A duration where a bare number is milliseconds.
+fn parse_delay(s: &str) -> Result<Duration> {
+    match s.parse::<u64>() {
+        Result::Ok(ms) => Ok(Duration::from_millis(ms)),
+        Err(_) => parse_duration(s),
+    }
+}
+
+fn parse_charset(s: &str) -> Result<&'static encoding_rs::Encoding> {
+    encoding_rs::Encoding::for_label(s.as_bytes())
+        .ok_or_else(|| anyhow!(format!("Failed to parse charset {}: not a known encoding", s)))
+}
+
+fn parse_size(s: &str) -> Result<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (n, unit) = s.split_at(split);
+    let n: u64 = n.parse().map_err(|_| anyhow!(format!("Failed to parse size {}", s)))?;
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "kib" => 1 << 10,
+        "m" | "mb" => 1000 * 1000,
+        "mib" => 1 << 20,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "gib" => 1 << 30,
+        _ => return Err(anyhow!(format!("Failed to parse size {}", s))),
+    };
+    n.checked_mul(scale)
+        .ok_or_else(|| anyhow!(format!("Failed to parse size {}: too large", s)))
+}
+
+fn parse_render_rate(s: &str) -> Result<u64> {
+    let rate = parse_size(s.trim_end_matches("/s"))?;
+    if rate == 0 {
+        return Err(anyhow!(format!("Failed to parse rate {}: it must be more than 0", s)));
+    }
+    Ok(rate)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct KvPair {
+    k: String,
+    v: String,
+}
+
+fn parse_kv_pair(s: &str) -> Result<KvPair> {
+    Ok(s.parse()?)
+}
+
+/// A pair with `{{VAR}}` placeholders in its value resolved.
+fn parse_templated_kv_pair(s: &str) -> Result<KvPair> {
+    let KvPair { k, v } = parse_kv_pair(s)?;
+    Ok(KvPair {
+        k,
+        v: template::render(&v)?,
+    })
+}
+
+impl FromStr for KvPair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // only the first `=` separates, values such as base64 may have more
+        let (k, v) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!(format!("Failed to parse {}", s)))?;
+        Ok(Self {
+            k: k.to_string(),
+            v: v.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum BodyItem {
+    /// `key=value`, a JSON (or form) field
+    Field(KvPair),
+    /// `key@path`, a file streamed as a multipart part
+    File(KvPair),
+    /// `@path`, a file streamed as the whole request body
+    Raw(String),
+    /// `Name:value` a request header, `Name;` one with an empty value
+    Header(KvPair),
+    /// `Name:`, a default header not to send
+    Unset(String),
+}
+
+/// A body item with `{{VAR}}` placeholders in its value or path resolved.
+fn parse_templated_body_item(s: &str) -> Result<BodyItem> {
+    let render = |KvPair { k, v }| -> Result<KvPair> {
+        Ok(KvPair {
+            k,
+            v: template::render(&v)?,
+        })
+    };
+    Ok(match parse_body_item(s)? {
+        BodyItem::Field(pair) => BodyItem::Field(render(pair)?),
+        BodyItem::File(pair) => BodyItem::File(render(pair)?),
+        BodyItem::Raw(path) => BodyItem::Raw(template::render(&path)?),
+        BodyItem::Header(pair) => BodyItem::Header(render(pair)?),
+        item @ BodyItem::Unset(_) => item,
+    })
+}
+
+impl fmt::Display for BodyItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Field(pair) => write!(f, "{}={}", pair.k, pair.v),
+            Self::File(pair) => write!(f, "{}@{}", pair.k, pair.v),
+            Self::Raw(path) => write!(f, "@{}", path),
+            Self::Header(pair) if pair.v.is_empty() => write!(f, "{};", pair.k),
+            Self::Header(pair) => write!(f, "{}:{}", pair.k, pair.v),
+            Self::Unset(name) => write!(f, "{}:", name),
+        }
+    }
+}
+
+fn parse_body_item(s: &str) -> Result<BodyItem> {
+    Ok(s.parse()?)
+}
+
+impl FromStr for BodyItem {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let header_name = |name: &str| {
+            header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!(format!("Failed to parse {}: invalid header name", s)))
+        };
+        // the earliest separator wins, so `a=b@c` is a field and `a@b=c` a
+        // file; `:=` is not a header, that is for query items
+        let header = s
+            .match_indices(':')
+            .map(|(i, _)| i)
+            .find(|i| !s[i + 1..].starts_with('='));
+        let sep = [s.find(['=', '@']), header].into_iter().flatten().min();
+        match sep {
+            Some(i) if Some(i) == header => {
+                let (name, value) = (&s[..i], &s[i + 1..]);
+                header_name(name)?;
+                if value.is_empty() {
+                    Ok(Self::Unset(name.into()))
+                } else {
+                    Ok(Self::Header(KvPair {
+                        k: name.into(),
+                        v: value.into(),
+                    }))
+                }
+            }
+            None if s.len() > 1 && s.ends_with(';') => {
+                let name = &s[..s.len() - 1];
+                header_name(name)?;
+                Ok(Self::Header(KvPair {
+                    k: name.into(),
+                    v: String::new(),
+                }))
+            }
+            Some(i) if s[i..].starts_with('@') => {
+                let (k, path) = (&s[..i], &s[i + 1..]);
+                if path.is_empty() {
+                    return Err(anyhow!(format!("Failed to parse {}: missing file path", s)));
+                }
+                if k.is_empty() {
+                    Ok(Self::Raw(path.into()))
+                } else {
+                    Ok(Self::File(KvPair {
+                        k: k.into(),
+                        v: path.into(),
+                    }))
+                }
+            }
+            _ => Ok(Self::Field(parse_kv_pair(s)?)),
+        }
+    }
+}
+
+async fn get(client: Client, args: &Get, opts: &Opts) -> Result<()> {
+    let query: Vec<_> = args
+        .items
+        .iter()
+        .filter_map(|i| match i {
+            GetItem::Query(q) => Some(q.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut urls = vec![query::append(&args.url, &query, args.array_format)?];
+    for item in args.items.iter() {
+        if let GetItem::Url(url) = item {
+            urls.push(query::append(url, &query, args.array_format)?);
+        }
+    }
+    if urls.len() > 1 {
+        return get_many(client, urls, args, opts).await;
+    }
+    let url = urls.remove(0);
+    if !args.locale_matrix.is_empty() {
+        return locale_matrix(client, &url, &args.locale_matrix, opts).await;
+    }
+    if opts.curl {
+        return print_curl(client.get(&url).build()?, &[], opts);
+    }
+    if let Some(interval) = args.watch {
+        return watch::run(&client, &url, interval, opts.compat.auth_scheme.as_deref()).await;
+    }
+    if args.follow_pagination {
+        return follow_pagination(&client, &url, args, opts).await;
+    }
+    if args.early_data {
+        let started = Instant::now();
+        let (resp, outcome) = early::fetch(&url, &default_headers(opts)?).await?;
+        print_resp(resp, started, opts).await?;
+        early::report(outcome);
+        return Ok(());
+    }
+    execute(&client, client.get(&url), opts).await
+}
+
+/// Fetch `url` and the pages after it, up to `--max-pages`. When every page
+/// is a JSON array their items are printed as one array under the headers
+/// of the first page; other pages are printed one after another.
+async fn follow_pagination(client: &Client, url: &str, args: &Get, opts: &Opts) -> Result<()> {
+    let auth = opts.compat.auth_scheme.as_deref();
+    let mut pages = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = Some(Url::parse(url)?);
+    while let Some(url) = next.take() {
+        if pages.len() == args.max_pages as usize {
+            next = Some(url);
+            break;
+        }
+        // a server pointing back at a page already fetched
+        if !seen.insert(url.clone()) {
+            break;
+        }
+        let req = client.get(url.clone()).build()?;
+        let started = Instant::now();
+        let exchange = exchange::Exchange::read(auth::sign_and_send(client, req, auth).await?, started).await?;
+        let codings = exchange
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(compression::codings)
+            .unwrap_or_default();
+        let body = compression::decode(&codings, exchange.body.clone())?;
+        let base = exchange.url.clone().unwrap_or(url);
+        if exchange.status.is_success() {
+            next = match &args.next_field {
+                Some(path) => paginate::next_field(&body, path, &base),
+                None => exchange
+                    .headers
+                    .get(header::LINK)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|link| paginate::next_link(link, &base)),
+            };
+        }
+        pages.push((exchange, body));
+    }
+    let count = pages.len();
+    let bodies: Vec<_> = pages.iter().map(|(_, body)| body.clone()).collect();
+    match paginate::concat(&bodies) {
+        Some(items) => {
+            let (mut first, _) = pages.remove(0);
+            first.headers.remove(header::CONTENT_ENCODING);
+            first.headers.remove(header::CONTENT_LENGTH);
+            first.body = serde_json::to_vec(&items)?.into();
+            print_exchange(first, opts)?;
+            eprintln!("{}", format!("{} items from {} pages", items.len(), count).dimmed());
+        }
+        None => {
+            for (i, (exchange, _)) in pages.into_iter().enumerate() {
+                if !opts.json_output {
+                    let url = exchange.url.as_ref().map_or(String::new(), |u| format!(": {}", u));
+                    // bodies need not end in a line break
+                    let gap = if i > 0 { "\n" } else { "" };
+                    println!("{}{}", gap, format!("── page {} of {}{} ──", i + 1, count, url).dimmed());
+                }
+                print_exchange(exchange, opts)?;
+            }
+        }
+    }
+    if let Some(next) = next {
+        eprintln!(
+            "{}",
+            format!("Stopped after {} pages (--max-pages), the next is {}", count, next).yellow()
+        );
+    }
+    Ok(())
+}
+
+/// What became of one of several URLs.
+enum Fetched {
+    Exchange(Box<exchange::Exchange>),
+    /// saved with `--download`, with its size
+    Saved(PathBuf, u64),
+}
+
+/// Fetch `urls` with at most `--parallel` requests in flight, printing each
+/// response under its URL in the order given.
+async fn get_many(client: Client, urls: Vec<String>, args: &Get, opts: &Opts) -> Result<()> {
+    if !args.locale_matrix.is_empty() || args.early_data || args.watch.is_some() || args.follow_pagination {
+        return Err(anyhow!(
+            "Failed to fetch several URLs: --locale-matrix, --early-data, --watch and --follow-pagination take one URL"
+        ));
+    }
+    if opts.shadow.is_some() || opts.conditional.cached || opts.compat.output.is_some() {
+        return Err(anyhow!("Failed to fetch several URLs: --shadow, --cached and --output take one URL"));
+    }
+    if opts.curl {
+        for url in urls.iter() {
+            print_curl(client.get(url).build()?, &[], opts)?;
+        }
+        return Ok(());
+    }
+    let defaults = default_headers(opts)?;
+    let pacer = limit::Pacer::new(opts.rate, opts.delay).map(Arc::new);
+    let mut tasks = Vec::new();
+    for url in urls.iter() {
+        let mut req = client.get(url).build()?;
+        validators::apply(req.headers_mut(), &opts.conditional, None)?;
+        let auth = opts.compat.auth_scheme.clone();
+        let (client, unix, defaults) = (client.clone(), opts.unix_socket.clone(), defaults.clone());
+        let download = opts.compat.download;
+        let pacer = pacer.clone();
+        let unresolved = prefetch::host_of(url).and_then(|host| Some((opts.unresolved.get(&host)?.clone(), host)));
+        tasks.push(async move {
+            if let Some((e, host)) = unresolved {
+                return Err(anyhow!(format!("Failed to resolve {}: {}", host, e)));
+            }
+            if let Some(pacer) = pacer {
+                pacer.wait().await;
+            }
+            let started = Instant::now();
+            let resp = match &unix {
+                Some(path) => {
+                    let mut req = req;
+                    if let Some(auth) = &auth {
+                        auth.apply(&mut req)?;
+                    }
+                    conn::unix(path, req, &defaults).await?
+                }
+                None => auth::sign_and_send(&client, req, auth.as_deref()).await?,
+            };
+            if download {
+                let (path, written) = download::save(resp, None, false).await?;
+                return Ok(Fetched::Saved(path, written));
+            }
+            Ok(Fetched::Exchange(Box::new(exchange::Exchange::read(resp, started).await?)))
+        });
+    }
+    // spawned as the stream is polled, so no more than `parallel` run at once
+    let mut results = futures_util::stream::iter(tasks)
+        .map(|task| tokio::spawn(exchange::track(task)))
+        .buffered(args.parallel as usize);
+    let mut failed = 0;
+    for url in urls.iter() {
+        let Some(result) = results.next().await else { break };
+        if !opts.json_output {
+            println!("{} {}\n", "==>".bold(), url.bold());
+        }
+        match result? {
+            Result::Ok(Fetched::Exchange(exchange)) => print_exchange(*exchange, opts)?,
+            Result::Ok(Fetched::Saved(path, written)) => {
+                println!("Saved {} bytes to {}", written, path.display())
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {}", "Error:".red().bold(), e);
+            }
+        }
+        if !opts.json_output {
+            println!();
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!(format!("Failed to fetch {} of {} URLs", failed, urls.len())));
+    }
+    Ok(())
+}
+
+/// Request `url` once per locale and diff each body against the first.
+async fn locale_matrix(client: Client, url: &str, langs: &[String], opts: &Opts) -> Result<()> {
+    let auth = opts.compat.auth_scheme.as_deref();
+    let mut bodies = Vec::new();
+    for lang in langs.iter() {
+        let req = client.get(url).header(header::ACCEPT_LANGUAGE, lang).build()?;
+        let started = Instant::now();
+        let exchange = exchange::Exchange::read(auth::sign_and_send(&client, req, auth).await?, started).await?;
+        let content_language = exchange
+            .headers
+            .get(header::CONTENT_LANGUAGE)
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+            .unwrap_or_else(|| "(none)".into());
+        let status = exchange.status;
+        let mime = exchange.mime();
+        let is_json = mime.as_ref().and_then(syntax_for) == Some("json");
+        let codings = exchange
+            .headers
+            .get(header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(compression::codings)
+            .unwrap_or_default();
+        let bytes = compression::decode(&codings, exchange.body)?;
+        let mut body = body::decode_text(&bytes, mime.as_ref()).into_owned();
+        // one value per line, so the diff points at what changed
+        if is_json {
+            body = jsonxf::pretty_print(&body).unwrap_or(body);
+        }
+        println!(
+            "{} {} -> {}, Content-Language: {}, {} bytes",
+            "Accept-Language:".bold(),
+            lang.yellow(),
+            status,
+            content_language,
+            body.len()
+        );
+        bodies.push((lang, body));
+    }
+    println!();
+
+    let (base_lang, base) = &bodies[0];
+    for (lang, body) in bodies.iter().skip(1) {
+        match diff::unified(base, body, base_lang, lang) {
+            Some(d) => diff::print_unified(&d),
+            None => println!(
+                "{}",
+                format!("{} is identical to {}", lang, base_lang).yellow()
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Fetch `url` for `httpie diff`: its comparable form and the response headline.
+async fn fetch_comparable(client: &Client, url: &str, names: &[String], opts: &Opts) -> Result<(String, String)> {
+    let req = client.get(url).build()?;
+    let auth = opts.compat.auth_scheme.as_deref();
+    let started = Instant::now();
+    let exchange = exchange::Exchange::read(auth::sign_and_send(client, req, auth).await?, started).await?;
+    let codings = exchange
+        .headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(compression::codings)
+        .unwrap_or_default();
+    let bytes = compression::decode(&codings, exchange.body.clone())?;
+    let body = diff::normalize_body(&bytes, exchange.mime().as_ref());
+    let comparable = diff::comparable(exchange.status, &exchange.headers, names, &body);
+    Ok((comparable, exchange.summary()))
+}
+
+async fn diff(client: Client, args: &Diff, opts: &Opts) -> Result<()> {
+    let (left, right, right_name) = match (&args.other, &args.against) {
+        (Some(other), _) => {
+            let (left, right) = tokio::join!(
+                fetch_comparable(&client, &args.url, &args.headers, opts),
+                fetch_comparable(&client, other, &args.headers, opts)
+            );
+            let ((left, left_summary), (right, right_summary)) = (left?, right?);
+            println!("{} {}\n{} {}\n", "<".red().bold(), left_summary, ">".green().bold(), right_summary);
+            (left, right, other.clone())
+        }
+        (None, Some(path)) => {
+            let bytes = std::fs::read(path)
+                .map_err(|e| anyhow!(format!("Failed to read {}: {}", path.display(), e)))?;
+            let (left, summary) = fetch_comparable(&client, &args.url, &[], opts).await?;
+            println!("{} {}\n", "<".red().bold(), summary);
+            // a file has no status or headers, so compare bodies only
+            let left = left.split_once("\n\n").map_or(left.clone(), |(_, body)| body.to_string());
+            (left, diff::normalize_body(&bytes, None), path.display().to_string())
+        }
+        (None, None) => unreachable!("clap requires one of them"),
+    };
+    match diff::unified(&left, &right, &args.url, &right_name) {
+        Some(d) => diff::print_unified(&d),
+        None => println!("{}", "Responses match".green()),
+    }
+    Ok(())
+}
+
+fn show(args: &Show) -> Result<()> {
+    let stored = store::get(&args.hash)?;
+    let describe = |s: &store::Stored| format!("{} HTTP {} {} at {}", &s.hash[..store::SHORT], s.meta.status, s.meta.url, s.meta.received);
+    let Some(other) = &args.diff else {
+        eprintln!("{}", describe(&stored).dimmed());
+        print_body(stored.mime(), &body::decode_text(&stored.body, stored.mime().as_ref()));
+        return Ok(());
+    };
+    let other = store::get(other)?;
+    println!("{} {}\n{} {}\n", "<".red().bold(), describe(&stored), ">".green().bold(), describe(&other));
+    let left = diff::normalize_body(&stored.body, stored.mime().as_ref());
+    let right = diff::normalize_body(&other.body, other.mime().as_ref());
+    match diff::unified(&left, &right, &stored.hash[..store::SHORT], &other.hash[..store::SHORT]) {
+        Some(d) => diff::print_unified(&d),
+        None => println!("{}", "Bodies match".green()),
+    }
+    Ok(())
+}
+
+async fn post(client: Client, args: &Post, opts: &Opts) -> Result<()> {
+    send(&client, client.post(&args.url), &args.body, opts).await
+}
+
+/// Attach body `items` to `req`, send it and print the response, or print
+/// the equivalent curl command with `--curl`.
+async fn send(
+    client: &Client,
+    req: RequestBuilder,
+    items: &[BodyItem],
+    opts: &Opts,
+) -> Result<()> {
+    // the client has its default headers, so a default can only be left out
+    // when the command line says so before it is built
+    if let Some(name) = items.iter().find_map(|i| match i {
+        BodyItem::Unset(name) if !opts.unset_headers.contains(name) => Some(name),
+        _ => None,
+    }) {
+        return Err(anyhow!(format!(
+            "Failed to remove {}: give `{}:` on the command line",
+            name, name
+        )));
+    }
+    if opts.curl {
+        // leave files unopened, curl reads them itself
+        let files = items.iter().any(|i| matches!(i, BodyItem::File(_) | BodyItem::Raw(_)));
+        let req = if files {
+            item_headers(req, items)
+        } else {
+            build_body(req, items, opts.compat.form).await?.0
+        };
+        return print_curl(req.build()?, items, opts);
+    }
+    let (req, progress) = build_body(req, items, opts.compat.form).await?;
+    execute(client, req, opts).await?;
+    if let Some(pb) = progress {
+        pb.finish();
+    }
+    Ok(())
+}
+
+/// Send `req` and print the response. With `--shadow` a copy goes to the
+/// shadow endpoint at the same time and the two responses are compared.
+async fn execute(client: &Client, req: RequestBuilder, opts: &Opts) -> Result<()> {
+    let mut req = req.build()?;
+    if opts.compress {
+        if let Some((raw, compressed)) = compression::compress_body(&mut req)? {
+            eprintln!("{} gzip, {} -> {} bytes", "Request body:".bold(), raw, compressed);
+        }
+    }
+    let cache_path = opts.conditional.validator_cache.clone().unwrap_or_else(validators::default_cache_path);
+    let mut cache = if opts.conditional.cached {
+        Some(validators::Cache::load(&cache_path)?)
+    } else {
+        None
+    };
+    let cached = cache.as_ref().and_then(|c| c.get(req.url()));
+    validators::apply(req.headers_mut(), &opts.conditional, cached)?;
+    let auth = opts.compat.auth_scheme.as_deref();
+    if let Some(auth) = auth {
+        auth.apply(&mut req)?;
+    }
+    if !opts.slo.is_empty() {
+        return slo::check(client.clone(), opts.compat.auth_scheme.clone(), req, &opts.slo).await;
+    }
+    let url = req.url().clone();
+    let sent = req.headers().clone();
+    let printing = opts.compat.printing();
+    if printing.request() {
+        compat::print_request(&req, &default_headers(opts)?, printing);
+    }
+    let har = match &opts.har_out {
+        Some(_) => Some(har::request(&req, &default_headers(opts)?)),
+        None => None,
+    };
+    let started = Instant::now();
+    let Some(base) = &opts.shadow else {
+        let resp = match &opts.unix_socket {
+            Some(path) => conn::unix(path, req, &default_headers(opts)?).await?,
+            None => auth::send(client, req, auth).await?,
+        };
+        if let Some(cache) = &mut cache {
+            if cache.record(&url, resp.headers()) {
+                cache.save(&cache_path)?;
+            }
+        }
+        let status = resp.status();
+        if opts.compat.download {
+            if printing.response_headers {
+                print_status(resp.version(), status);
+                print_headers(resp.headers(), opts.sorted);
+            }
+            download::run(resp, opts.compat.output.as_deref()).await?;
+        } else {
+            record_resp(resp, started, har, opts).await?;
+        }
+        validators::report(status, &sent);
+        return Ok(());
+    };
+    if opts.unix_socket.is_some() || opts.compat.download {
+        return Err(anyhow!("Failed to mirror request: --shadow does not work with --unix-socket or --download"));
+    }
+    let copy = shadow::mirror(&req, &base.parse()?, auth)?;
+    let (primary, secondary) = tokio::join!(
+        shadow::Outcome::fetch(client, req, auth),
+        shadow::Outcome::fetch(client, copy, auth)
+    );
+    let primary = primary?;
+    if let Some(cache) = &mut cache {
+        if cache.record(&url, &primary.headers) {
+            cache.save(&cache_path)?;
+        }
+    }
+    record_resp(primary.to_response(), started, har, opts).await?;
+    validators::report(primary.status, &sent);
+    shadow::report(&primary, secondary);
+    Ok(())
+}
+
+/// Attach body `items` to `req`: streamed raw file, multipart form when files
+/// are involved, JSON object otherwise. File uploads come with a progress bar.
+async fn build_body(
+    mut req: RequestBuilder,
+    items: &[BodyItem],
+    form: bool,
+) -> Result<(RequestBuilder, Option<ProgressBar>)> {
+    let mut body = HashMap::new();
+    let mut files = Vec::new();
+    let mut raw = Vec::new();
+    for item in items.iter() {
+        match item {
+            BodyItem::Field(pair) => {
+                body.insert(&pair.k, &pair.v);
+            }
+            BodyItem::File(pair) => files.push(pair),
+            BodyItem::Raw(path) => raw.push(path),
+            BodyItem::Header(_) | BodyItem::Unset(_) => {}
+        }
+    }
+    req = item_headers(req, items);
+
+    let mut progress = None;
+    if let Some(path) = raw.first() {
+        if raw.len() > 1 || !body.is_empty() || !files.is_empty() {
+            return Err(anyhow!("A raw `@file` body cannot be combined with other body items"));
+        }
+        let len = upload::file_size(path).await?;
+        let pb = upload::progress_bar(len);
+        req = req
+            .header(header::CONTENT_LENGTH, len)
+            .body(upload::file_body(path, &pb).await?);
+        progress = Some(pb);
+    } else if !files.is_empty() {
+        let mut total = 0;
+        for file in files.iter() {
+            total += upload::file_size(&file.v).await?;
+        }
+        let pb = upload::progress_bar(total);
+        let mut form = Form::new();
+        for (k, v) in body {
+            form = form.text(k.clone(), v.clone());
+        }
+        for file in files {
+            form = form.part(file.k.clone(), upload::file_part(&file.v, &pb).await?);
+        }
+        req = req.multipart(form);
+        progress = Some(pb);
+    } else if !body.is_empty() && form {
+        req = req.form(&body);
+    } else if !body.is_empty() {
+        req = req.json(&body);
+    }
+    Ok((req, progress))
+}
+
+/// `req` with the `Name:value` and `Name;` items as headers, replacing
+/// defaults of the same name.
+fn item_headers(mut req: RequestBuilder, items: &[BodyItem]) -> RequestBuilder {
+    for item in items.iter() {
+        if let BodyItem::Header(pair) = item {
+            req = req.header(pair.k.as_str(), pair.v.as_str());
+        }
+    }
+    req
+}
+
+fn print_curl(req: Request, items: &[BodyItem], opts: &Opts) -> Result<()> {
+    let mut curl = curl::Curl::new(req.method().clone(), req.url().as_str());
+    let mut headers = default_headers(opts)?;
+    headers.extend(req.headers().clone());
+    for (name, value) in headers.iter() {
+        if value.is_empty() {
+            // `Name:` would remove the header in curl
+            curl.arg("-H", &format!("{};", name));
+        } else {
+            curl.header(name.as_str(), &String::from_utf8_lossy(value.as_bytes()));
+        }
+    }
+    // nor should curl add its own
+    for name in opts.unset_headers.iter() {
+        curl.arg("-H", &format!("{}:", name));
+    }
+    if !opts.cookies.is_empty() {
+        let cookies: Vec<_> = opts.cookies.iter().map(|p| format!("{}={}", p.k, p.v)).collect();
+        curl.arg("-b", &cookies.join("; "));
+    }
+    if let Some(path) = &opts.unix_socket {
+        curl.arg("--unix-socket", &path.to_string_lossy());
+    }
+    for (host, addrs) in &opts.resolve {
+        let port = addrs[0].port();
+        let port = if port == 0 { "*".to_string() } else { port.to_string() };
+        let addrs: Vec<_> = addrs
+            .iter()
+            .map(|a| match a.ip() {
+                std::net::IpAddr::V6(ip) => format!("[{}]", ip),
+                ip => ip.to_string(),
+            })
+            .collect();
+        curl.arg("--resolve", &format!("{}:{}:{}", host, port, addrs.join(",")));
+    }
+    match &opts.compat.verify {
+        compat::Verify::Yes => {}
+        compat::Verify::No => {
+            curl.flag("--insecure");
+        }
+        compat::Verify::CaBundle(path) => {
+            curl.arg("--cacert", &path.to_string_lossy());
+        }
+    }
+    if let Some(jar) = &opts.cookie_jar {
+        // curl reads and writes the same Netscape format
+        let jar = jar.to_string_lossy();
+        curl.arg("-b", &jar).arg("-c", &jar);
+    }
+
+    let multipart = items.iter().any(|i| matches!(i, BodyItem::File(_)));
+    for item in items.iter() {
+        match item {
+            BodyItem::Field(pair) if multipart => {
+                curl.form(&pair.k, &pair.v);
+            }
+            BodyItem::File(pair) => {
+                curl.form_file(&pair.k, &pair.v);
+            }
+            BodyItem::Raw(path) => {
+                curl.data_file(path);
+            }
+            BodyItem::Field(_) | BodyItem::Header(_) | BodyItem::Unset(_) => {}
+        }
+    }
+    if let Some(body) = req.body().and_then(|b| b.as_bytes()) {
+        curl.data(body);
+    }
+    println!("{}", curl);
+    Ok(())
+}
+
+fn save(args: &Save) -> Result<()> {
+    let method = args.method.clone().unwrap_or(if args.body.is_empty() {
+        Method::GET
+    } else {
+        Method::POST
+    });
+    let mut c = collection::Collection::load(&args.collection)?;
+    c.requests.insert(
+        args.name.clone(),
+        collection::Request {
+            method: method.to_string(),
+            url: args.url.clone(),
+            headers: args.headers.iter().cloned().collect(),
+            body: args.body.iter().map(|i| i.to_string()).collect(),
+        },
+    );
+    c.save(&args.collection)?;
+    println!("Saved {} {} as {}", method, args.url, args.name.bold());
+    Ok(())
+}
+
+/// The saved request `run` sends, with its overrides applied.
+fn saved_request(args: &Run) -> Result<collection::Request> {
+    let mut collection = collection::Collection::load(&args.collection)?;
+    // the collection file wins over requests saved in the config
+    for (name, req) in config::current().requests.iter() {
+        collection.requests.entry(name.clone()).or_insert_with(|| req.clone());
+    }
+    let mut req = collection.get(&args.name)?.clone();
+    req.apply(&args.set)?;
+    Ok(req)
+}
+
+async fn run(client: Client, args: &Run, opts: &Opts) -> Result<()> {
+    let req = saved_request(args)?.render()?;
+    let mut builder = client.request(parse_method(&req.method)?, parse_url(&req.url)?);
+    for (name, value) in req.headers.iter() {
+        builder = builder.header(name, value);
+    }
+    send(&client, builder, &req.items()?, opts).await
+}
+
+async fn import_curl(client: Client, args: &ImportCurl, opts: &Opts) -> Result<()> {
+    let req = curl::CurlRequest::parse(&args.command)?
+        .to_request(&client)
+        .await?;
+    if opts.curl {
+        return print_curl(req.build()?, &[], opts);
+    }
+    execute(&client, req, opts).await
+}
+
+async fn graphql(client: Client, args: &Graphql, opts: &Opts) -> Result<()> {
+    let query = graphql::load_query(&args.query)?;
+    let body = graphql::envelope(&query, &args.vars, args.operation_name.as_deref());
+    let req = client.post(&args.url).json(&body);
+    if opts.curl {
+        return print_curl(req.build()?, &[], opts);
+    }
+    execute(&client, req, opts).await
+}
+
+/// A GraphQL response, its errors apart from its data; false when `value`
+/// has neither, and is no GraphQL response after all.
+fn print_graphql(value: &serde_json::Value) -> Result<bool> {
+    if value.get("errors").is_none() && value.get("data").is_none() {
+        return Ok(false);
+    }
+    if let Some(errors) = value.get("errors") {
+        println!("{}", "errors:".red().bold());
+        print_synctect(&serde_json::to_string_pretty(errors)?, "json");
+        println!();
+    }
+    if let Some(data) = value.get("data") {
+        println!("{}", "data:".green().bold());
+        print_synctect(&serde_json::to_string_pretty(data)?, "json");
+        println!();
+    }
+    Ok(true)
+}
+
+async fn upload(client: Client, args: &Upload, opts: &Opts) -> Result<()> {
+    let started = Instant::now();
+    let state = args
+        .state
+        .clone()
+        .unwrap_or_else(|| chunked::default_state_path(&args.file));
+    let resp = chunked::upload(
+        client,
+        opts.compat.auth_scheme.clone(),
+        args.url.parse()?,
+        &args.file,
+        args.part_size,
+        args.parallel as usize,
+        &state,
+    )
+    .await?;
+    Ok(print_resp(resp, started, opts).await?)
+}
+
+async fn tus(client: Client, args: &Tus, opts: &Opts) -> Result<()> {
+    let started = Instant::now();
+    let TusCommand::Upload(args) = &args.cmd;
+    let state = args
+        .state
+        .clone()
+        .unwrap_or_else(|| tus::default_state_path(&args.file));
+    let auth = opts.compat.auth_scheme.as_deref();
+    let resp = tus::upload(client, auth, args.url.parse()?, &args.file, args.chunk_size, &state).await?;
+    Ok(print_resp(resp, started, opts).await?)
+}
+
+async fn bench(client: Client, args: &Bench, opts: &Opts) -> Result<()> {
+    let req = client.request(args.method.clone(), &args.url);
+    let (req, _) = build_body(req, &args.body, opts.compat.form).await?;
+    let pacer = limit::Pacer::new(opts.rate, opts.delay).map(Arc::new);
+    if args.adaptive && args.concurrency < 2 {
+        return Err(anyhow!(
+            "Failed to run adaptively: give the most requests in flight with -c, e.g. -c 64"
+        ));
+    }
+    let adaptive = args.adaptive.then(|| Arc::new(bench::Aimd::new(args.concurrency)));
+    let auth = opts.compat.auth_scheme.clone();
+    let report = bench::run(client, auth, req.build()?, args.requests, args.concurrency, pacer, adaptive).await?;
+    bench::print_report(&report, args.concurrency);
+    Ok(())
+}
+
+/// The redirects followed to get to a response, if any, and the URL it
+/// came from in the end, after them and after normalization.
+fn print_redirects(exchange: &exchange::Exchange) {
+    for redirect in exchange.redirects.iter() {
+        println!("{}", format!("{} {} → {}", redirect.status, redirect.from, redirect.to).dimmed());
+    }
+    if let Some(url) = &exchange.url {
+        println!("{} {}", "Final URL:".bold(), url);
+    }
+}
+
+fn print_status(version: reqwest::Version, status: reqwest::StatusCode) {
+    let status = format!("{:?} {}", version, status).blue();
+    println!("{}\n", status);
+}
+
+/// Make the outcome of content negotiation stand out from the other headers.
+fn print_negotiated(headers: &header::HeaderMap) {
+    let header = |name| {
+        headers
+            .get(name)
+            .map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned())
+    };
+    let chosen = header(header::CONTENT_TYPE).unwrap_or_else(|| "(no Content-Type)".into());
+    let vary = header(header::VARY).unwrap_or_else(|| "(none)".into());
+    println!(
+        "{} {}  {} {}\n",
+        "Negotiated:".bold(),
+        chosen.yellow().bold(),
+        "Vary:".bold(),
+        vary
+    );
+}
+
+fn print_headers(headers: &header::HeaderMap, sorted: bool) {
+    print_header_table(headers, sorted);
+    println!();
+}
+
+/// A header value as text when it is UTF-8 without control characters,
+/// quoted with escapes otherwise.
+fn header_value(v: &header::HeaderValue) -> String {
+    std::str::from_utf8(v.as_bytes())
+        .ok()
+        .filter(|s| !s.chars().any(char::is_control))
+        .map(String::from)
+        .unwrap_or_else(|| format!("{:?}", v))
+}
+
+/// `name: value` rows with the values aligned in one column.
+fn header_rows(headers: &header::HeaderMap, sorted: bool) -> Vec<(String, String)> {
+    let mut rows: Vec<_> = headers
+        .iter()
+        .map(|(name, value)| (format!("{}:", name), header_value(value)))
+        .collect();
+    if sorted {
+        // stable, so repeated headers keep their order
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, _) in rows.iter_mut() {
+        *name = format!("{:<width$}", name, width = width);
+    }
+    rows
+}
+
+fn print_header_table(headers: &header::HeaderMap, sorted: bool) {
+    for (name, value) in header_rows(headers, sorted) {
+        println!("{} {}", name.green(), value);
+    }
+}
+
+/// JSON re-serialized with object keys in order (serde_json maps are sorted),
+/// or `body` unchanged when it is not JSON.
+#[doc(hidden)]
+pub fn sort_json(body: String) -> String {
+    serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|v| serde_json::to_string_pretty(&v).ok())
+        .unwrap_or(body)
+}
+
+fn print_body(m: Option<Mime>, body: &str) {
+    match m.as_ref().and_then(syntax_for) {
+        Some(ext) => print_synctect(body, ext),
+        None => output::write(&format!("{}\n", body)),
+    }
+}
+
+/// Syntax (by file extension) used to highlight a body of the given type.
+fn syntax_for(m: &Mime) -> Option<&'static str> {
+    // vendor types like `application/hal+json` highlight as their suffix
+    let subtype = m.suffix().unwrap_or_else(|| m.subtype());
+    match (m.type_().as_str(), subtype.as_str()) {
+        (_, "json") => Some("json"),
+        (_, "xml") => Some("xml"),
+        (_, "yaml" | "x-yaml") => Some("yaml"),
+        ("text", "html") => Some("html"),
+        ("text", "css") => Some("css"),
+        ("application" | "text", "javascript" | "x-javascript" | "ecmascript") => Some("js"),
+        _ => None,
+    }
+}
+
+fn print_synctect(s: &str, ext: &str) {
+    // NO_COLOR and --ci turn highlighting off along with other colors
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        output::write(s);
+        return;
+    }
+    let ps = SyntaxSet::load_defaults_newlines();
+    let ts = ThemeSet::load_defaults();
+    let syntex = ps.find_syntax_by_extension(ext).unwrap();
+    let theme = &ts.themes["base16-ocean.light"];
+    highlight::highlight(s, syntex, &ps, theme, |chunk| output::write(&chunk));
+}
+
+/// Read `resp`, sent at `started`, and print it.
+async fn print_resp(resp: Response, started: Instant, opts: &Opts) -> Result<()> {
+    print_exchange(exchange::Exchange::read(resp, started).await?, opts)
+}
+
+/// As `print_resp`, first adding the exchange to the `--har-out` archive.
+async fn record_resp(
+    resp: Response,
+    started: Instant,
+    har: Option<(har::HarRequest, SystemTime)>,
+    opts: &Opts,
+) -> Result<()> {
+    let exchange = exchange::Exchange::read(resp, started).await?;
+    if let (Some(path), Some((request, sent_at))) = (&opts.har_out, har) {
+        har::append(path, request, sent_at, &exchange)?;
+    }
+    print_exchange(exchange, opts)
+}
+
+/// Send the entries of a HAR file again, each through `execute`.
+async fn replay_har(client: &Client, args: &ReplayHar, opts: &Opts) -> Result<()> {
+    let archive = har::load(&args.har)?;
+    let entries: Vec<_> = archive
+        .log
+        .entries
+        .iter()
+        .filter(|e| args.only.as_ref().is_none_or(|only| e.request.url.contains(only.as_str())))
+        .collect();
+    if entries.is_empty() {
+        return Err(anyhow!(format!("Failed to replay {}: no matching entries", args.har.display())));
+    }
+    for entry in entries {
+        if !opts.json_output {
+            println!("{} {} {}\n", "==>".bold(), entry.request.method.blue(), entry.request.url.bold());
+        }
+        execute(client, har::rebuild(client, &entry.request)?, opts).await?;
+        if !opts.json_output {
+            println!();
+        }
+    }
+    Ok(())
+}
+
+fn print_exchange(mut exchange: exchange::Exchange, opts: &Opts) -> Result<()> {
+    let _format = memory::phase("format");
+    compat::observe(exchange.status);
+    let summary = (opts.ci && !opts.json_output).then(|| exchange.summary());
+    // filtered and JSON output are meant for scripts, so leave out everything else
+    let scripted = opts.filter.is_some() || opts.json_output || opts.get.is_some();
+    let printing = opts.compat.printing();
+    let decorate = !scripted && printing.response_headers;
+    if decorate {
+        print_redirects(&exchange);
+        print_status(exchange.version, exchange.status);
+        if opts.negotiate.is_some() {
+            print_negotiated(&exchange.headers);
+        }
+        print_headers(&exchange.headers, opts.sorted);
+    }
+    let mut mine = exchange.mime();
+    // for servers that send the wrong Content-Type, the config has the say
+    if let Some(syntax) = exchange.url.as_ref().and_then(|u| config::current().format_for(u)) {
+        mine = Some(syntax.mime(mine.as_ref()));
+    }
+    let codings = exchange
+        .headers
+        .get(header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(compression::codings);
+    let encoded = codings.is_some();
+    let codings = codings.unwrap_or_default();
+    let mut bytes = std::mem::take(&mut exchange.body);
+    let wire = bytes.len();
+    let decompress = !codings.is_empty() && !opts.no_decompress;
+    if decompress {
+        bytes = compression::decode(&codings, bytes)?;
+    }
+    expect::check(exchange.status, &exchange.headers, &bytes, mine.as_ref());
+    let stored = opts.store.then(|| store::put(&exchange, &bytes)).transpose()?;
+    let stored = stored.map(|hash| format!("stored {}", &hash[..store::SHORT]));
+    match (summary, &stored) {
+        (Some(summary), Some(stored)) => eprintln!("{} {}", summary, stored),
+        (Some(summary), None) => eprintln!("{}", summary),
+        (None, Some(stored)) if !opts.json_output => eprintln!("{}", stored.dimmed()),
+        _ => {}
+    }
+    if let Some(field) = opts.get {
+        println!("{}", field.of(&exchange)?);
+        return Ok(());
+    }
+    // documents and report bundles: what they are rather than binary
+    if let Some(doc) = (!scripted && opts.extract.is_none()).then(|| document::inspect(&bytes)).flatten() {
+        document::print(&doc, bytes.len());
+        return Ok(());
+    }
+    match archive::open(&bytes) {
+        Some(archive) if !scripted || opts.extract.is_some() => {
+            if !scripted {
+                archive::print(&archive);
+            }
+            if let Some(dir) = &opts.extract {
+                let n = archive::extract(&archive, dir)?;
+                eprintln!("{} {} files to {}", "Extracted".green().bold(), n, dir.display());
+            }
+            return Ok(());
+        }
+        None if opts.extract.is_some() => {
+            return Err(anyhow!("Failed to extract: the response is not a zip or tar archive"))
+        }
+        _ => {}
+    }
+    if decorate && (opts.compressed || !codings.is_empty()) {
+        let offered = opts.compressed.then_some(compression::OFFERED);
+        let decoded = (decompress || codings.is_empty()).then_some(bytes.len());
+        println!("{}\n", compression::report(offered, &codings, wire, decoded));
+    }
+    if !scripted && !printing.response_body {
+        return Ok(());
+    }
+    if !codings.is_empty() && opts.no_decompress && !opts.json_output {
+        let max = opts.max_body_print.map_or(usize::MAX, |m| m as usize);
+        print!("{}", body::hexdump(&bytes[..bytes.len().min(max)]));
+        print_cut(bytes.len().saturating_sub(max));
+        return Ok(());
+    }
+    // a frequent object storage misconfiguration: gzip files served as is
+    if !encoded && body::is_gzip(&bytes) {
+        if opts.gunzip {
+            bytes = body::gunzip(&bytes)?.into();
+        } else {
+            eprintln!(
+                "{}",
+                "Body looks gzip-compressed but has no Content-Encoding; rerun with --gunzip to decompress it"
+                    .yellow()
+            );
+        }
+    }
+    // binary formats as JSON, asked for or named by the Content-Type
+    let format = opts.decode.clone().or_else(|| mine.as_ref().and_then(decode::Format::for_mime));
+    if let Some(format) = format {
+        match format.decode(&bytes) {
+            Result::Ok(value) => {
+                let mut pretty = serde_json::to_vec_pretty(&value)?;
+                pretty.push(b'\n');
+                bytes = pretty.into();
+                mine = Some(mime::APPLICATION_JSON);
+            }
+            Err(e) if opts.decode.is_some() => return Err(e),
+            Err(_) => {}
+        }
+    }
+    if opts.json_output {
+        exchange.body = bytes;
+        println!("{}", serde_json::to_string_pretty(&exchange)?);
+        return Ok(());
+    }
+    // mail, as mail APIs and webhook inspectors return it
+    if opts.filter.is_none() && mine.as_ref().is_some_and(email::is_message) {
+        email::print(&email::parse(&bytes), opts.sorted);
+        return Ok(());
+    }
+    // batch and byte range responses, one part at a time
+    if let Some(parts) = mine.as_ref().filter(|_| opts.filter.is_none()).and_then(|m| mixed::parts(m, &bytes)) {
+        mixed::print(&parts, opts.sorted);
+        return Ok(());
+    }
+    // RFC 7807 errors, as what went wrong rather than as JSON
+    let problem = mine.as_ref().filter(|m| opts.filter.is_none() && problem::is_problem(m));
+    if let Some(problem) = problem.and_then(|_| problem::parse(&bytes)) {
+        print!("{}", problem::render(&problem, exchange.status));
+        return Ok(());
+    }
+    let mut body = body::decode_text(&bytes, mine.as_ref());
+    if opts.filter.is_none() && matches!(opts.subcmd, SubCommand::Graphql(_)) {
+        let value = serde_json::from_str(&body).ok();
+        if value.as_ref().map(print_graphql).transpose()? == Some(true) {
+            return Ok(());
+        }
+    }
+    if opts.sorted && mine.as_ref().and_then(syntax_for) == Some("json") {
+        body = sort_json(body.into_owned()).into();
+    }
+    match &opts.filter {
+        Some(filter) => print_filtered(&body, filter, opts.raw)?,
+        None => {
+            let cut = opts
+                .max_body_print
+                .map_or(0, |max| body::truncate(&mut body, max as usize));
+            let highlighted = mine.as_ref().and_then(syntax_for).is_some();
+            print_body(mine, &body);
+            // only highlighted bodies are printed without a newline
+            if cut > 0 && highlighted && !body.ends_with('\n') {
+                println!();
+            }
+            print_cut(cut);
+        }
+    }
+
+    Ok(())
+}
+
+/// The notice after a body cut short by `--max-body-print`.
+fn print_cut(cut: usize) {
+    if cut > 0 {
+        println!(
+            "{}",
+            format!("… {} more bytes (use --download --output <file>)", cut).dimmed()
+        );
+    }
+}
+
+fn print_filtered(body: &str, filter: &jsonpath::Path, raw: bool) -> Result<()> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| anyhow!(format!("Failed to parse response body as JSON: {}", e)))?;
+    for v in filter.select(&value) {
+        match v {
+            serde_json::Value::String(s) if raw => println!("{}", s),
+            _ if raw => println!("{}", v),
+            _ => print_synctect(&(serde_json::to_string_pretty(v)? + "\n"), "json"),
+        }
+    }
+    Ok(())
+}
+
+/// Headers the client sends with every request.
+fn default_headers(opts: &Opts) -> Result<header::HeaderMap> {
+    let mut headers = header::HeaderMap::new();
+
+    headers.insert("X-POWERED-BY", "RUST".parse()?);
+    let agent = opts.user_agent.as_deref().unwrap_or("Rust Httpie");
+    headers.insert(header::USER_AGENT, agent.parse()?);
+    if let Some(accept) = &opts.negotiate {
+        headers.insert(header::ACCEPT, accept.parse()?);
+    }
+    if let Some(lang) = &opts.lang_header {
+        headers.insert(header::ACCEPT_LANGUAGE, lang.parse()?);
+    }
+    if opts.compressed {
+        headers.insert(header::ACCEPT_ENCODING, compression::OFFERED.parse()?);
+    }
+    if let Some(priority) = &opts.priority {
+        headers.insert("priority", priority.parse()?);
+    }
+    if opts.compat.json && opts.negotiate.is_none() {
+        headers.insert(header::ACCEPT, "application/json, */*;q=0.5".parse()?);
+    }
+    if let Some(authorization) = opts.compat.auth_scheme.as_ref().and_then(|a| a.header()) {
+        headers.insert(header::AUTHORIZATION, authorization.parse()?);
+    }
+    for (name, value) in config::profile().map(|p| &p.headers).into_iter().flatten() {
+        headers.insert(
+            header::HeaderName::from_bytes(name.as_bytes())?,
+            template::render(value)?.parse()?,
+        );
+    }
+    // a GET has no body for header items to go with, so they are defaults
+    if let SubCommand::Get(args) = &opts.subcmd {
+        for item in args.items.iter() {
+            if let GetItem::Header(pair) = item {
+                headers.insert(header::HeaderName::from_bytes(pair.k.as_bytes())?, pair.v.parse()?);
+            }
+        }
+    }
+    for name in opts.unset_headers.iter() {
+        headers.remove(name.as_str());
+    }
+    Ok(headers)
+}
+
+/// The headers the `Name:` items of `subcmd` leave out, when it sends
+/// requests with items.
+fn unset_headers(subcmd: &SubCommand) -> Result<Vec<String>> {
+    let items = match subcmd {
+        SubCommand::Get(args) => {
+            return Ok(args
+                .items
+                .iter()
+                .filter_map(|i| match i {
+                    GetItem::Unset(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .collect())
+        }
+        SubCommand::Post(args) => args.body.clone(),
+        SubCommand::Bench(args) => args.body.clone(),
+        SubCommand::Run(args) => saved_request(args)?.items()?,
+        _ => Vec::new(),
+    };
+    Ok(items
+        .into_iter()
+        .filter_map(|i| match i {
+            BodyItem::Unset(name) => Some(name),
+            _ => None,
+        })
+        .collect())
+}
+
+/// The command line definition, for generating completions and man pages.
+pub fn command() -> clap::Command {
+    Opts::command().display_name("httpie").bin_name("httpie")
+}
+
+/// Parse `args` (including the program name) and run the command.
+pub async fn run_cli(args: Vec<String>) -> Result<()> {
+    crash::install();
+    crash::stage("loading the config");
+    let config = config::Config::load_all()?;
+    let args = config::expand_alias(args, &config, &command())?;
+    crash::record_args(&args, &command());
+    config::init(config, flag_value(&args, "--profile"))?;
+    // placeholders are resolved while parsing, so the env file comes first
+    template::init(template::env_file_arg(&args).as_deref())?;
+    if let Some(scheme) = flag_value(&args, "--default-scheme") {
+        DEFAULT_SCHEME.set(scheme.to_string()).ok();
+    }
+    crash::stage("parsing arguments");
+    let matches = Opts::command().get_matches_from(args);
+    let mut opts = Opts::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if config::current().usage_stats == Some(true) {
+        // counting is never worth failing the command for
+        if let Err(e) = usage::record(&usage::default_path(), &Opts::command(), &matches) {
+            eprintln!("{}", format!("{:#}", e).yellow());
+        }
+    }
+    crash::stage("setting up the client");
+    if opts.profile_memory {
+        memory::enable("request");
+    }
+    if opts.ci {
+        colored::control::set_override(false);
+        upload::hide_progress();
+        opts.compat.check_status = true;
+    }
+    if let Some(max) = opts.max_response_size {
+        exchange::limit_response_size(max);
+    }
+    if let Some(rate) = opts.max_render_rate {
+        output::limit_rate(rate);
+    }
+    if opts.no_shared_limit {
+        limit::disable();
+    }
+    if let Some(encoding) = opts.response_charset {
+        body::override_charset(encoding);
+    }
+    expect::init(expect::Expectations {
+        status: opts.expect_status.clone(),
+        headers: opts.expect_header.clone(),
+        body_contains: opts.expect_body_contains.clone(),
+        json: opts.expect_json.clone(),
+    });
+    opts.unset_headers = unset_headers(&opts.subcmd)?;
+    // set up once, as the password prompt may come up
+    opts.compat.auth_scheme = auth::from_args(
+        opts.compat.auth.as_deref(),
+        opts.compat.auth_type,
+        opts.aws_profile.as_deref(),
+        opts.show_canonical,
+        !opts.ci,
+    )?;
+    if let Some(name) = opts.session.as_deref().filter(|_| !matches!(opts.subcmd, SubCommand::Oauth2(_))) {
+        let token_client = compat::configure(Client::builder(), &opts.compat.verify)?.build()?;
+        opts.compat.auth_scheme = Some(Arc::new(oauth2::session(&token_client, name).await?));
+    }
+    let headers = default_headers(&opts)?;
+    let mut builder = Client::builder().default_headers(headers);
+    let jar = if !opts.cookies.is_empty() || opts.cookie_jar.is_some() {
+        let jar = Arc::new(cookie::CookieJar::load(
+            opts.cookie_jar.as_deref(),
+            &opts.cookies,
+        )?);
+        builder = builder.cookie_provider(jar.clone());
+        Some(jar)
+    } else {
+        None
+    };
+    if let Some(src) = &opts.proxy_pac {
+        let pac = pac::Pac::load(src).await?;
+        builder = builder.proxy(reqwest::Proxy::custom(move |url| pac.proxy_for(url)));
+    }
+    for (host, addrs) in &opts.resolve {
+        builder = builder.resolve_to_addrs(host, addrs);
+    }
+    // the names of a batch are looked up together, ahead of the requests
+    let batch = match &opts.subcmd {
+        SubCommand::Get(args) => args
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                GetItem::Url(url) => Some(url.as_str()),
+                _ => None,
+            })
+            .chain(std::iter::once(args.url.as_str()))
+            .collect(),
+        _ => Vec::new(),
+    };
+    if batch.len() > 1 && opts.unix_socket.is_none() && opts.proxy_pac.is_none() && !opts.curl && !prefetch::proxied() {
+        let pinned: Vec<String> = opts.resolve.iter().map(|(host, _)| host.to_ascii_lowercase()).collect();
+        let answers = prefetch::resolve(prefetch::hosts(batch, &pinned)).await;
+        for (host, addrs) in &answers.found {
+            builder = builder.resolve_to_addrs(host, addrs);
+        }
+        opts.unresolved = answers.failed;
+    }
+    builder = compat::configure(builder, &opts.compat.verify)?;
+    // the h2 subcommand manages its own connection
+    if !matches!(opts.subcmd, SubCommand::H2(_)) {
+        builder = http2::configure(builder, &opts.http2)?;
+    }
+    builder = builder.redirect(exchange::policy());
+    let client = builder.build()?;
+    crash::stage("running the command");
+    // redirects are recorded per task, for the output
+    exchange::track(async {
+        match opts.subcmd {
+            SubCommand::Get(ref args) => get(client, args, &opts).await?,
+            SubCommand::Post(ref args) => post(client, args, &opts).await?,
+            SubCommand::Upload(ref args) => upload(client, args, &opts).await?,
+            SubCommand::Tus(ref args) => tus(client, args, &opts).await?,
+            SubCommand::Bench(ref args) => bench(client, args, &opts).await?,
+            SubCommand::Freshness(ref args) => {
+                freshness::probe(client, opts.compat.auth_scheme.as_deref(), &args.url).await?
+            }
+            SubCommand::ImportCurl(ref args) => import_curl(client, args, &opts).await?,
+            SubCommand::Graphql(ref args) => graphql(client, args, &opts).await?,
+            SubCommand::Diff(ref args) => diff(client, args, &opts).await?,
+            SubCommand::Sse(ref args) => {
+                let recorder = args.record.as_deref().map(transcript::Recorder::create).transpose()?;
+                sse::stream(client, opts.compat.auth_scheme.as_deref(), &args.url, recorder).await?
+            }
+            SubCommand::Ws(ref args) => {
+                let recorder = args.record.as_deref().map(transcript::Recorder::create).transpose()?;
+                let mut headers = default_headers(&opts)?;
+                if !opts.cookies.is_empty() {
+                    let cookies: Vec<_> = opts.cookies.iter().map(|p| format!("{}={}", p.k, p.v)).collect();
+                    headers.insert(header::COOKIE, cookies.join("; ").parse()?);
+                }
+                ws::connect(&args.url, &headers, recorder).await?
+            }
+            SubCommand::Replay(ref args) => transcript::replay(&args.transcript, args.speed).await?,
+            SubCommand::ReplayHar(ref args) => replay_har(&client, args, &opts).await?,
+            SubCommand::H2(ref args) => {
+                multiplex::session(&args.url, &default_headers(&opts)?, args.body, &opts.http2).await?
+            }
+            SubCommand::Completions(ref args) => {
+                clap_complete::generate(args.shell, &mut command(), "httpie", &mut std::io::stdout())
+            }
+            SubCommand::Man => clap_mangen::Man::new(command()).render(&mut std::io::stdout())?,
+            SubCommand::Repl(ref args) => {
+                let base = match &args.base {
+                    Some(base) => Some(base.clone()),
+                    None => config::base().map(parse_url).transpose()?,
+                };
+                repl::run(client, base, &opts).await?
+            }
+            SubCommand::Alias => config::list_aliases(config::current(), &command()),
+            SubCommand::Lint(ref args) => lint::run(&args.collection, config::current(), &command())?,
+            SubCommand::Show(ref args) => show(args)?,
+            SubCommand::Usage(ref args) => {
+                let path = usage::default_path();
+                if args.reset {
+                    usage::Counts::default().save(&path)?;
+                }
+                usage::show(&path, &command(), config::current().usage_stats == Some(true))?
+            }
+            SubCommand::Oauth2(ref args) => {
+                let grant = oauth2::Grant {
+                    flow: args.flow,
+                    token_url: args.token_url.clone(),
+                    device_url: args.device_url.clone(),
+                    client_id: args.client_id.clone(),
+                    client_secret: args.client_secret.clone(),
+                    scope: args.scope.clone(),
+                };
+                oauth2::acquire(&client, grant, opts.session.as_deref().unwrap_or("default")).await?
+            }
+            SubCommand::Save(ref args) => save(args)?,
+            SubCommand::Run(ref args) => run(client, args, &opts).await?,
+            SubCommand::ServeRecord(ref args) => {
+                let target = args.target.as_deref().map(str::parse).transpose()?;
+                mock::serve(args.port, target, args.record.clone(), args.replay.clone(), args.insecure).await?
+            }
+            SubCommand::Proxy(ref args) => {
+                let ca = if args.intercept {
+                    let dir = args.ca_dir.clone().unwrap_or_else(proxy::default_ca_dir);
+                    let ca = proxy::Ca::load_or_create(&dir)?;
+                    println!("Clients must trust {} for HTTPS interception", dir.join("ca.pem").display());
+                    Some(ca)
+                } else {
+                    None
+                };
+                proxy::serve(args.listen, ca, args.insecure).await?
+            }
+        }
+        Ok(())
+    })
+    .await?;
+
+    crash::stage("finishing");
+    if let (Some(jar), Some(path)) = (jar, &opts.cookie_jar) {
+        jar.save(path)?;
+    }
+    memory::report();
+    expect::report()?;
+    if opts.compat.check_status {
+        compat::check_status()?;
+    }
+
+    Ok(())
+}
+
+/// The process exit code for an error from `run_cli`.
+pub fn exit_code(e: &anyhow::Error) -> u8 {
+    e.downcast_ref::<compat::StatusError>().map_or(1, |e| e.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_works() {
+        command().debug_assert();
+    }
+
+    #[test]
+    fn parse_url_works() {
+        assert!(parse_url("abs").is_err());
+        assert!(parse_url("http://abc.xyz").is_ok());
+        assert!(parse_url("https://httpbin.org/post").is_ok());
+    }
+
+    #[test]
+    fn expand_url_works() {
+        let http = |s| expand_url(s, "http").unwrap();
+        assert_eq!(http(":3000/path"), "http://localhost:3000/path");
+        assert_eq!(http(":/path?q=1"), "http://localhost/path?q=1");
+        assert_eq!(http(":"), "http://localhost");
+        assert_eq!(http("example.com/api"), "http://example.com/api");
+        assert_eq!(http("localhost:8080"), "http://localhost:8080");
+        assert_eq!(http("user@10.0.0.1/x"), "http://user@10.0.0.1/x");
+        assert_eq!(expand_url("example.com", "https").unwrap(), "https://example.com");
+        assert_eq!(http("https://a.b/c"), "https://a.b/c");
+        let err = |s| expand_url(s, "http").unwrap_err().to_string();
+        assert_eq!(err("localhost:99999"), "Failed to parse URL localhost:99999: the port must be a number from 0 to 65535");
+        assert_eq!(err("ftp://a.b"), "Failed to parse URL ftp://a.b: unsupported scheme ftp, use http or https");
+        assert_eq!(err("http://"), "Failed to parse URL http://: the host is missing");
+        assert!(err("get").starts_with("Failed to parse URL get: expected e.g."));
+    }
+
+    #[test]
+    fn parse_get_item_works() {
+        let header = |k: &str, v: &str| GetItem::Header(KvPair { k: k.into(), v: v.into() });
+        assert_eq!(parse_get_item("X-A:1").unwrap(), header("X-A", "1"));
+        assert_eq!(parse_get_item("X-Empty;").unwrap(), header("X-Empty", ""));
+        assert_eq!(parse_get_item("User-Agent:").unwrap(), GetItem::Unset("User-Agent".into()));
+        assert_eq!(parse_get_item("localhost:8080").unwrap(), GetItem::Url("http://localhost:8080".into()));
+        assert_eq!(parse_get_item("example.com:8080/x").unwrap(), GetItem::Url("http://example.com:8080/x".into()));
+        assert_eq!(parse_get_item(":3000").unwrap(), GetItem::Url("http://localhost:3000".into()));
+        assert!(matches!(parse_get_item("ids:=[1]").unwrap(), GetItem::Query(_)));
+    }
+
+    #[test]
+    fn flag_value_works() {
+        let args: Vec<String> = ["httpie", "--default-scheme=https", "get", "--env-file", "a", "--", "--x", "y"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(flag_value(&args, "--default-scheme"), Some("https"));
+        assert_eq!(flag_value(&args, "--env-file"), Some("a"));
+        assert_eq!(flag_value(&args, "--x"), None);
+        assert_eq!(flag_value(&args, "--default"), None);
+    }
+
+    #[test]
+    fn parse_kv_pair_wroks() {
+        assert!(parse_kv_pair("a").is_err());
+        assert_eq!(
+            parse_kv_pair("a=1").unwrap(),
+            KvPair {
+                k: "a".into(),
+                v: "1".into(),
+            }
+        );
+
+        assert_eq!(
+            parse_kv_pair("b=").unwrap(),
+            KvPair {
+                k: "b".into(),
+                v: "".into(),
+            }
+        );
+        assert_eq!(
+            parse_kv_pair("session=abc==").unwrap(),
+            KvPair {
+                k: "session".into(),
+                v: "abc==".into(),
+            }
+        )
+    }
+
+    #[test]
+    fn syntax_for_works() {
+        let syntax = |s: &str| syntax_for(&s.parse().unwrap());
+        assert_eq!(syntax("application/json; charset=utf-8"), Some("json"));
+        assert_eq!(syntax("application/hal+json"), Some("json"));
+        assert_eq!(syntax("text/xml"), Some("xml"));
+        assert_eq!(syntax("application/atom+xml"), Some("xml"));
+        assert_eq!(syntax("application/x-yaml"), Some("yaml"));
+        assert_eq!(syntax("text/html"), Some("html"));
+        assert_eq!(syntax("text/css"), Some("css"));
+        assert_eq!(syntax("text/javascript"), Some("js"));
+        assert_eq!(syntax("text/plain"), None);
+
+        let ps = SyntaxSet::load_defaults_newlines();
+        for ext in ["json", "xml", "yaml", "html", "css", "js"] {
+            assert!(ps.find_syntax_by_extension(ext).is_some(), "{}", ext);
+        }
+    }
+
+    #[test]
+    fn parse_negotiate_works() {
+        assert_eq!(
+            parse_negotiate("json,xml;q=0.8").unwrap(),
+            "application/json, application/xml;q=0.8"
+        );
+        assert_eq!(
+            parse_negotiate("application/hal+json, *;q=0.1").unwrap(),
+            "application/hal+json, */*;q=0.1"
+        );
+        assert!(parse_negotiate("json;q=2").is_err());
+        assert!(parse_negotiate("klingon").is_err());
+        assert!(parse_negotiate("").is_err());
+    }
+
+    #[test]
+    fn parse_lang_header_works() {
+        assert_eq!(
+            parse_lang_header("de-DE,en;q=0.7").unwrap(),
+            "de-DE, en;q=0.7"
+        );
+        assert_eq!(parse_lang_header("*").unwrap(), "*");
+        assert!(parse_lang_header("de_DE").is_err());
+        assert!(parse_lang_header("en;q=x").is_err());
+    }
+
+    #[test]
+    fn parse_priority_works() {
+        assert_eq!(parse_priority("u=1").unwrap(), "u=1");
+        assert_eq!(parse_priority("i, u=5").unwrap(), "u=5, i");
+        assert_eq!(parse_priority("u=0,i=?0").unwrap(), "u=0");
+        assert!(parse_priority("u=8").is_err());
+        assert!(parse_priority("x=1").is_err());
+        assert!(parse_priority("").is_err());
+    }
+
+    #[test]
+    fn parse_size_works() {
+        assert_eq!(parse_size("1024").unwrap(), 1024);
+        assert_eq!(parse_size("5M").unwrap(), 5_000_000);
+        assert_eq!(parse_size("8MiB").unwrap(), 8 << 20);
+        assert!(parse_size("MiB").is_err());
+        assert!(parse_size("3 parsecs").is_err());
+        assert!(parse_size("99999999999999gib").is_err());
+        assert_eq!(parse_charset("latin1").unwrap().name(), "windows-1252");
+        assert!(parse_charset("klingon").is_err());
+        assert_eq!(parse_window_size("64KiB").unwrap(), 65536);
+        assert!(parse_window_size("2GiB").is_err());
+        assert_eq!(parse_frame_size("16KiB").unwrap(), 16384);
+        assert!(parse_frame_size("1000").is_err());
+        assert!(parse_frame_size("16MiB").is_err());
+        assert_eq!(parse_render_rate("50KiB/s").unwrap(), 50 << 10);
+        assert!(parse_render_rate("0/s").is_err());
+    }
+
+    #[test]
+    fn parse_duration_works() {
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_delay("250").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_delay("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_rate("2.5").unwrap(), 2.5);
+        assert!(parse_rate("0").is_err());
+        assert_eq!(parse_duration("2m").unwrap(), Duration::from_secs(120));
+        assert!(parse_duration("ms").is_err());
+        assert!(parse_duration("5 days").is_err());
+        assert!(parse_duration(&format!("{}h", "9".repeat(400))).is_err());
+    }
+
+    #[test]
+    fn parse_header_works() {
+        assert_eq!(
+            parse_header("X-Token:  abc ").unwrap(),
+            ("x-token".to_string(), "abc".to_string())
+        );
+        assert!(parse_header("X-Token").is_err());
+        assert!(parse_header("Bad Name: x").is_err());
+    }
+
+    #[test]
+    fn parse_resolve_works() {
+        let (host, addrs) = parse_resolve("Example.com:443:127.0.0.1,[::1]").unwrap();
+        assert_eq!(host, "example.com");
+        assert_eq!(addrs, ["127.0.0.1:443".parse().unwrap(), "[::1]:443".parse().unwrap()]);
+        assert_eq!(parse_resolve("a:*:10.0.0.1").unwrap().1[0].port(), 0);
+        assert!(parse_resolve("a:443").is_err());
+        assert!(parse_resolve("a:x:10.0.0.1").is_err());
+        assert!(parse_resolve("a:443:nope").is_err());
+    }
+
+    #[test]
+    fn header_rows_works() {
+        let mut headers = header::HeaderMap::new();
+        headers.insert(header::SERVER, "nginx".parse().unwrap());
+        headers.append(header::SET_COOKIE, "b=2".parse().unwrap());
+        headers.append(header::SET_COOKIE, "a=1".parse().unwrap());
+        headers.insert(header::ETAG, header::HeaderValue::from_bytes(b"\"caf\xc3\xa9\"").unwrap());
+        headers.insert("x-bin", header::HeaderValue::from_bytes(b"a\xffb").unwrap());
+        let rows = header_rows(&headers, true);
+        let names: Vec<_> = rows.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["etag:      ", "server:    ", "set-cookie:", "set-cookie:", "x-bin:     "]);
+        let values: Vec<_> = rows.iter().map(|(_, v)| v.as_str()).collect();
+        assert_eq!(values, ["\"café\"", "nginx", "b=2", "a=1", "\"a\\xffb\""]);
+    }
+
+    #[test]
+    fn sort_json_works() {
+        assert_eq!(sort_json(r#"{"b":1,"a":{"d":[],"c":2}}"#.into()), "{\n  \"a\": {\n    \"c\": 2,\n    \"d\": []\n  },\n  \"b\": 1\n}");
+        assert_eq!(sort_json("not json".into()), "not json");
+    }
+
+    #[test]
+    fn parse_listen_works() {
+        assert_eq!(parse_listen("8888").unwrap(), "127.0.0.1:8888".parse().unwrap());
+        assert_eq!(parse_listen("0.0.0.0:80").unwrap(), "0.0.0.0:80".parse().unwrap());
+        assert!(parse_listen("localhost").is_err());
+    }
+
+    #[test]
+    fn parse_body_item_works() {
+        assert!(parse_body_item("a").is_err());
+        assert!(parse_body_item("a@").is_err());
+        assert_eq!(
+            parse_body_item("a=1").unwrap(),
+            BodyItem::Field(KvPair {
+                k: "a".into(),
+                v: "1".into(),
+            })
+        );
+        assert_eq!(
+            parse_body_item("file@/tmp/a.bin").unwrap(),
+            BodyItem::File(KvPair {
+                k: "file".into(),
+                v: "/tmp/a.bin".into(),
+            })
+        );
+        assert_eq!(
+            parse_body_item("@/tmp/a.bin").unwrap(),
+            BodyItem::Raw("/tmp/a.bin".into())
+        );
+        assert_eq!(
+            parse_body_item("email=a@b.c").unwrap(),
+            BodyItem::Field(KvPair {
+                k: "email".into(),
+                v: "a@b.c".into(),
+            })
+        );
+        assert_eq!(
+            parse_body_item("Authorization:Bearer a=b").unwrap(),
+            BodyItem::Header(KvPair {
+                k: "Authorization".into(),
+                v: "Bearer a=b".into(),
+            })
+        );
+        assert_eq!(
+            parse_body_item("User-Agent:").unwrap(),
+            BodyItem::Unset("User-Agent".into())
+        );
+        assert_eq!(parse_body_item("X-Empty;").unwrap().to_string(), "X-Empty;");
+        assert!(parse_body_item("bad name:1").is_err());
+        assert!(matches!(
+            parse_body_item("url=http://a").unwrap(),
+            BodyItem::Field(_)
+        ));
+    }
+}
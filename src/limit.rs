@@ -0,0 +1,192 @@
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::config;
+
+/// Set by `--no-shared-limit`.
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// A lock older than this was left behind by a run that died holding it.
+const STALE_LOCK: Duration = Duration::from_secs(5);
+
+/// A token bucket holding up to a second's worth of requests. Tokens go
+/// below zero as requests reserve their turn ahead of time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Bucket {
+    tokens: f64,
+    /// seconds since the epoch
+    updated: f64,
+}
+
+impl Bucket {
+    /// Take a token at `now` and return how long to wait before using it.
+    fn take(&mut self, rate: f64, now: f64) -> Duration {
+        let capacity = rate.max(1.0);
+        let elapsed = (now - self.updated).max(0.0);
+        self.tokens = (self.tokens + elapsed * rate).min(capacity) - 1.0;
+        self.updated = now;
+        if self.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-self.tokens / rate)
+    }
+}
+
+fn dir() -> PathBuf {
+    config::default_path().with_file_name("limits")
+}
+
+/// Hold `lock` for the length of `f`, so runs in parallel take turns with
+/// the bucket file. Waiting for the lock leaves the runtime free.
+async fn locked<T>(lock: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(lock) {
+            Ok(_) => break,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let stale = std::fs::metadata(lock)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| t.elapsed().ok())
+                    .is_some_and(|age| age > STALE_LOCK);
+                if stale {
+                    std::fs::remove_file(lock).ok();
+                } else {
+                    tokio::time::sleep(Duration::from_millis(2)).await;
+                }
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to create {}", lock.display()))
+            }
+        }
+    }
+    let result = f();
+    std::fs::remove_file(lock).ok();
+    result
+}
+
+/// Wait for a turn to send to the host of `url` when the config gives it a
+/// `rate_limit`, counting requests from every httpie run on this machine.
+pub async fn acquire(url: &Url) -> Result<()> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let Some(host) = url.host_str() else {
+        return Ok(());
+    };
+    let Some(&rate) = config::current().rate_limit.get(host) else {
+        return Ok(());
+    };
+    if rate <= 0.0 {
+        return Ok(());
+    }
+    let dir = dir();
+    std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.json", host));
+    let wait = locked(&path.with_extension("lock"), || {
+        let mut bucket: Bucket = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+        let wait = bucket.take(rate, now);
+        std::fs::write(&path, serde_json::to_string(&bucket)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(wait)
+    })
+    .await?;
+    tokio::time::sleep(wait).await;
+    Ok(())
+}
+
+/// Paces the requests of one run, for `--rate` and `--delay`: at most
+/// `rate` a second, starting out with no burst, and at least `delay` apart
+/// however many are in flight.
+pub struct Pacer {
+    rate: Option<f64>,
+    delay: Duration,
+    start: Instant,
+    /// the bucket and when the last request was let go
+    state: Mutex<(Bucket, Option<Instant>)>,
+}
+
+impl Pacer {
+    pub fn new(rate: Option<f64>, delay: Option<Duration>) -> Option<Self> {
+        if rate.is_none() && delay.is_none() {
+            return None;
+        }
+        Some(Self {
+            rate,
+            delay: delay.unwrap_or_default(),
+            start: Instant::now(),
+            state: Mutex::new((
+                Bucket {
+                    tokens: 1.0,
+                    updated: 0.0,
+                },
+                None,
+            )),
+        })
+    }
+
+    /// Wait for the next turn. Turns are handed out in the order asked for,
+    /// before waiting, so concurrent callers queue up behind each other.
+    pub async fn wait(&self) {
+        let now = Instant::now();
+        let wait = {
+            let mut state = self.state.lock().await;
+            let (bucket, last) = &mut *state;
+            let mut wait = match self.rate {
+                Some(rate) => bucket.take(rate, (now - self.start).as_secs_f64()),
+                None => Duration::ZERO,
+            };
+            if let Some(last) = *last {
+                wait = wait.max((last + self.delay).saturating_duration_since(now));
+            }
+            *last = Some(now + wait);
+            wait
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_works() {
+        let mut bucket = Bucket::default();
+        // a fresh bucket fills up to a second's worth
+        assert_eq!(bucket.take(2.0, 100.0), Duration::ZERO);
+        assert_eq!(bucket.take(2.0, 100.0), Duration::ZERO);
+        // then each request waits its turn behind those before it
+        assert_eq!(bucket.take(2.0, 100.0), Duration::from_millis(500));
+        assert_eq!(bucket.take(2.0, 100.0), Duration::from_secs(1));
+        assert_eq!(bucket.take(2.0, 102.0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn pacer_works() {
+        assert!(Pacer::new(None, None).is_none());
+        let pacer = Pacer::new(Some(50.0), Some(Duration::from_millis(30))).unwrap();
+        let start = Instant::now();
+        for _ in 0..3 {
+            pacer.wait().await;
+        }
+        // the first goes at once, the delay spaces out the other two
+        assert!(start.elapsed() >= Duration::from_millis(60));
+    }
+}
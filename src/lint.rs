@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Method,
+};
+
+use crate::{
+    collection, config::Config, curl::split_words, parse_body_item, parse_url, template, BodyItem,
+};
+
+/// Problems with a saved request, checked without sending it: the method,
+/// placeholders without a value, the URL, headers and files named by body
+/// items.
+pub fn request(req: &collection::Request) -> Vec<String> {
+    let mut problems = Vec::new();
+    if Method::from_bytes(req.method.to_ascii_uppercase().as_bytes()).is_err() {
+        problems.push(format!("invalid method {}", req.method));
+    }
+    match template::render(&req.url) {
+        Ok(url) => {
+            if let Err(e) = parse_url(&url) {
+                problems.push(e.to_string());
+            }
+        }
+        Err(e) => problems.push(e.to_string()),
+    }
+    for (name, value) in req.headers.iter() {
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            problems.push(format!("invalid header name {}", name));
+        }
+        match template::render(value) {
+            Ok(value) if HeaderValue::from_str(&value).is_err() => {
+                problems.push(format!("invalid value for header {}", name))
+            }
+            Ok(_) => {}
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+    for item in req.body.iter() {
+        let item = match template::render(item).and_then(|s| parse_body_item(&s)) {
+            Ok(item) => item,
+            Err(e) => {
+                problems.push(e.to_string());
+                continue;
+            }
+        };
+        if let BodyItem::File(crate::KvPair { v: path, .. }) | BodyItem::Raw(path) = &item {
+            if !Path::new(path).is_file() {
+                problems.push(format!("missing file {}", path));
+            }
+        }
+    }
+    problems
+}
+
+/// Problems with an alias: its words must make a valid command line, though
+/// it may leave required arguments to be given after it.
+pub fn alias(expansion: &str, cmd: &clap::Command) -> Option<String> {
+    let words = match split_words(expansion) {
+        Ok(words) => words,
+        Err(e) => return Some(e.to_string()),
+    };
+    let args = std::iter::once("httpie".to_string()).chain(words);
+    match cmd.clone().try_get_matches_from(args) {
+        Ok(_) => None,
+        Err(e) if e.kind() == clap::error::ErrorKind::MissingRequiredArgument => None,
+        Err(e) => {
+            let e = e.to_string();
+            let first = e.lines().next().unwrap_or_default();
+            Some(first.trim_start_matches("error: ").to_string())
+        }
+    }
+}
+
+/// Problems with the config: aliases, profile base URLs and headers, and
+/// the saved requests in it.
+fn config(config: &Config, cmd: &clap::Command) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    for (name, expansion) in config.alias.iter() {
+        if cmd.find_subcommand(name).is_some() {
+            problems.push((
+                format!("alias {}", name),
+                "shadowed by the built-in command".to_string(),
+            ));
+        } else if let Some(problem) = alias(expansion, cmd) {
+            problems.push((format!("alias {}", name), problem));
+        }
+    }
+    let bases = config
+        .profile
+        .iter()
+        .map(|(name, p)| (format!("profile {}", name), p.base.as_deref()))
+        .chain([("base".to_string(), config.base.as_deref())]);
+    for (source, base) in bases {
+        if let Some(Err(e)) = base.map(|b| template::render(b).and_then(|b| parse_url(&b))) {
+            problems.push((source, e.to_string()));
+        }
+    }
+    for (name, profile) in config.profile.iter() {
+        for (header, value) in profile.headers.iter() {
+            let valid = HeaderName::from_bytes(header.as_bytes()).is_ok();
+            match template::render(value) {
+                Ok(_) if !valid => problems.push((
+                    format!("profile {}", name),
+                    format!("invalid header name {}", header),
+                )),
+                Ok(_) => {}
+                Err(e) => problems.push((format!("profile {}", name), e.to_string())),
+            }
+        }
+    }
+    for (name, req) in config.requests.iter() {
+        for problem in request(req) {
+            problems.push((format!("request {}", name), problem));
+        }
+    }
+    problems
+}
+
+/// Check the collection at `path` and the config, printing each problem;
+/// an error when there is any.
+pub fn run(path: &Path, current: &Config, cmd: &clap::Command) -> Result<()> {
+    let collection = collection::Collection::load(path)?;
+    let mut problems: Vec<(String, String)> = Vec::new();
+    for (name, req) in collection.requests.iter() {
+        for problem in request(req) {
+            problems.push((format!("{}: request {}", path.display(), name), problem));
+        }
+    }
+    problems.extend(
+        config(current, cmd)
+            .into_iter()
+            .map(|(what, problem)| (format!("config: {}", what), problem)),
+    );
+    for (what, problem) in problems.iter() {
+        println!("{} {}: {}", "✗".red().bold(), what.bold(), problem);
+    }
+    let checked = collection.requests.len() + current.requests.len();
+    if problems.is_empty() {
+        println!(
+            "{} {} requests and {} aliases checked, no problems",
+            "✓".green().bold(),
+            checked,
+            current.alias.len()
+        );
+        return Ok(());
+    }
+    Err(anyhow!(format!(
+        "Failed lint: {} problems found",
+        problems.len()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_works() {
+        let req: collection::Request = serde_json::from_value(serde_json::json!({
+            "method": "FETCH ME",
+            "url": "https://{{LINT_TEST_UNSET_HOST}}/x",
+            "headers": {"Bad Name": "v"},
+            "body": ["a=1", "@/nonexistent/body.json"],
+        }))
+        .unwrap();
+        let problems = request(&req);
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(problems[1].contains("LINT_TEST_UNSET_HOST"));
+        assert_eq!(problems[3], "missing file /nonexistent/body.json");
+
+        let cmd = crate::command();
+        assert_eq!(alias("get", &cmd), None);
+        assert!(alias("get http://a.b/ --no-such-flag", &cmd).is_some());
+    }
+}
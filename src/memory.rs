@@ -0,0 +1,229 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use colored::Colorize;
+
+/// The system allocator, counting what goes through it once `enable` is
+/// called; until then each allocation costs one more relaxed load. The
+/// binary installs it, so the library leaves the choice to its users.
+pub struct Counting;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// the tests count with it too
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+impl Counting {
+    fn grow(size: usize) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(size, Ordering::Relaxed);
+        let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn shrink(size: usize) {
+        // what was live before counting started is not in LIVE
+        let _ = LIVE.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+            Some(live.saturating_sub(size))
+        });
+    }
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() && ENABLED.load(Ordering::Relaxed) {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() && ENABLED.load(Ordering::Relaxed) {
+            Self::grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        if ENABLED.load(Ordering::Relaxed) {
+            Self::shrink(layout.size());
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() && ENABLED.load(Ordering::Relaxed) {
+            Self::shrink(layout.size());
+            Self::grow(new_size);
+        }
+        new
+    }
+}
+
+/// The counters at one moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Snapshot {
+    allocations: usize,
+    allocated: usize,
+}
+
+impl Snapshot {
+    fn now() -> Self {
+        Self {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            allocated: ALLOCATED.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// What one phase of the pipeline took, summed over the times it ran.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Usage {
+    name: &'static str,
+    allocations: usize,
+    allocated: usize,
+    peak: usize,
+}
+
+/// The phase running, when it started, and the phases done so far in the
+/// order they first ran.
+static PHASES: Mutex<Option<(&'static str, Snapshot, Vec<Usage>)>> = Mutex::new(None);
+
+/// Book the phase running up to now and start `name`.
+fn switch(name: &'static str) -> Option<&'static str> {
+    let mut phases = PHASES.lock().unwrap();
+    let (current, started, done) = phases.as_mut()?;
+    let now = Snapshot::now();
+    let usage = match done.iter_mut().find(|u| u.name == *current) {
+        Some(usage) => usage,
+        None => {
+            done.push(Usage {
+                name: current,
+                ..Default::default()
+            });
+            done.last_mut().unwrap()
+        }
+    };
+    usage.allocations += now.allocations - started.allocations;
+    usage.allocated += now.allocated - started.allocated;
+    usage.peak = usage.peak.max(PEAK.load(Ordering::Relaxed));
+    PEAK.store(LIVE.load(Ordering::Relaxed), Ordering::Relaxed);
+    let previous = std::mem::replace(current, name);
+    *started = now;
+    Some(previous)
+}
+
+/// Start counting, with `name` as the first phase.
+pub fn enable(name: &'static str) {
+    *PHASES.lock().unwrap() = Some((name, Snapshot::now(), Vec::new()));
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Until dropped, allocations count towards phase `name`.
+pub struct Phase(Option<&'static str>);
+
+pub fn phase(name: &'static str) -> Phase {
+    Phase(switch(name))
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0 {
+            switch(previous);
+        }
+    }
+}
+
+/// The peak resident set size, from /proc where there is one.
+fn peak_rss() -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: usize = line["VmHWM:".len()..]
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Print what each phase allocated and the peak RSS, when counting.
+pub fn report() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    switch("");
+    let Some((_, _, done)) = PHASES.lock().unwrap().take() else {
+        return;
+    };
+    ENABLED.store(false, Ordering::Relaxed);
+    eprintln!("{}", "Memory:".bold());
+    for usage in done.iter() {
+        eprintln!(
+            "  {:<10}{} allocations, {} allocated, peak {} live",
+            usage.name,
+            usage.allocations,
+            format_size(usage.allocated),
+            format_size(usage.peak)
+        );
+    }
+    let rss = peak_rss().map_or_else(|| "unavailable".into(), format_size);
+    eprintln!("  {:<10}{}", "peak RSS", rss);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phase_works() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(3 * 1024 * 1024 / 2), "1.5 MiB");
+
+        // the counters are global, so only this test turns them on
+        enable("request");
+        {
+            let _format = phase("format");
+            let big = vec![0u8; 1 << 20];
+            std::hint::black_box(&big);
+        }
+        switch("");
+        let (_, _, done) = PHASES.lock().unwrap().take().unwrap();
+        ENABLED.store(false, Ordering::Relaxed);
+        let names: Vec<_> = done.iter().map(|u| u.name).collect();
+        assert_eq!(names, ["request", "format"]);
+        assert!(done[1].allocations >= 1);
+        assert!(done[1].allocated >= 1 << 20);
+        assert!(done[1].peak >= 1 << 20);
+        assert!(peak_rss().is_some_and(|rss| rss > 0));
+    }
+}
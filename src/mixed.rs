@@ -0,0 +1,171 @@
+use colored::Colorize;
+use mime::Mime;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+
+use crate::body;
+
+/// One part of a multipart body.
+#[derive(Debug)]
+pub struct Part {
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl Part {
+    fn mime(&self) -> Option<Mime> {
+        self.headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+    }
+}
+
+/// The parts of `bytes` when `m` is a multipart type with a boundary, as
+/// batch APIs (multipart/mixed) and byte range responses
+/// (multipart/byteranges) send.
+pub fn parts(m: &Mime, bytes: &[u8]) -> Option<Vec<Part>> {
+    if m.type_() != mime::MULTIPART {
+        return None;
+    }
+    let boundary = m.get_param(mime::BOUNDARY)?;
+    split(bytes, boundary.as_str())
+}
+
+/// Where `delim` next starts a line, from `from` on.
+fn find_delimiter(bytes: &[u8], delim: &[u8], from: usize) -> Option<usize> {
+    (from..=bytes.len().checked_sub(delim.len())?)
+        .find(|&i| (i == 0 || bytes[i - 1] == b'\n') && bytes[i..].starts_with(delim))
+}
+
+/// Split a multipart body on its boundary, leaving out the preamble and
+/// epilogue.
+fn split(bytes: &[u8], boundary: &str) -> Option<Vec<Part>> {
+    let delim = format!("--{}", boundary).into_bytes();
+    let mut at = find_delimiter(bytes, &delim, 0)?;
+    let mut parts = Vec::new();
+    loop {
+        let after = at + delim.len();
+        if bytes[after..].starts_with(b"--") {
+            return Some(parts);
+        }
+        let start = after + bytes[after..].iter().position(|&b| b == b'\n')? + 1;
+        let next = find_delimiter(bytes, &delim, start)?;
+        let mut end = next;
+        // the line break before a delimiter belongs to it
+        if end > start && bytes[end - 1] == b'\n' {
+            end -= 1;
+            if end > start && bytes[end - 1] == b'\r' {
+                end -= 1;
+            }
+        }
+        let (headers, body) = message(&bytes[start..end]);
+        parts.push(Part { headers, body });
+        at = next;
+    }
+}
+
+/// Headers and body of a MIME message, split at the first empty line.
+pub fn message(bytes: &[u8]) -> (HeaderMap, Vec<u8>) {
+    let (head, body) = if bytes.starts_with(b"\r\n") {
+        (&b""[..], &bytes[2..])
+    } else if bytes.starts_with(b"\n") {
+        (&b""[..], &bytes[1..])
+    } else if let Some(i) = bytes.windows(4).position(|w| w == b"\r\n\r\n") {
+        (&bytes[..i], &bytes[i + 4..])
+    } else if let Some(i) = bytes.windows(2).position(|w| w == b"\n\n") {
+        (&bytes[..i], &bytes[i + 2..])
+    } else {
+        (bytes, &b""[..])
+    };
+    let mut headers = HeaderMap::new();
+    for line in String::from_utf8_lossy(head).lines() {
+        if let Some((name, value)) = line.split_once(':') {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.trim().as_bytes()),
+                HeaderValue::from_str(value.trim()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+    }
+    (headers, body.to_vec())
+}
+
+/// Print each part with its headers and its body formatted by its own
+/// Content-Type, going into nested multiparts and embedded HTTP messages
+/// (`application/http`, as in OData batches).
+pub fn print(parts: &[Part], sorted: bool) {
+    for (i, part) in parts.iter().enumerate() {
+        println!(
+            "{}",
+            format!("── part {} of {} ──", i + 1, parts.len()).dimmed()
+        );
+        crate::print_headers(&part.headers, sorted);
+        print_part_body(part.mime(), &part.body, sorted);
+    }
+}
+
+fn print_part_body(m: Option<Mime>, bytes: &[u8], sorted: bool) {
+    if let Some(parts) = m.as_ref().and_then(|m| parts(m, bytes)) {
+        print(&parts, sorted);
+        return;
+    }
+    if m.as_ref().is_some_and(|m| m.subtype() == "http") {
+        let text = String::from_utf8_lossy(bytes);
+        let (start, rest) = text.split_once('\n').unwrap_or((&text, ""));
+        println!("{}", start.trim_end().bold());
+        let (headers, body) = message(rest.as_bytes());
+        crate::print_headers(&headers, sorted);
+        let inner = headers
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()?.parse().ok());
+        print_part_body(inner, &body, sorted);
+        return;
+    }
+    if bytes.is_empty() {
+        return;
+    }
+    let text = m
+        .as_ref()
+        .and_then(|m| m.get_param(mime::CHARSET))
+        .is_some()
+        || std::str::from_utf8(bytes).is_ok();
+    if !text {
+        print!("{}", body::hexdump(bytes));
+        return;
+    }
+    let mut s = body::decode_text(bytes, m.as_ref());
+    if sorted && m.as_ref().and_then(crate::syntax_for) == Some("json") {
+        s = crate::sort_json(s.into_owned()).into();
+    }
+    let highlighted = m.as_ref().and_then(crate::syntax_for).is_some();
+    crate::print_body(m, &s);
+    if highlighted && !s.ends_with('\n') {
+        println!();
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parts_works() {
+        let m: Mime = "multipart/mixed; boundary=batch_1".parse().unwrap();
+        let body = b"preamble\r\n--batch_1\r\nContent-Type: application/http\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\"a\":1}\r\n\
+            --batch_1\r\n\r\nno headers\r\n--batch_1--\r\nepilogue";
+        let found = parts(&m, body).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].headers[CONTENT_TYPE], "application/http");
+        let (headers, inner) = message(&found[0].body[b"HTTP/1.1 200 OK\r\n".len()..]);
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert_eq!(inner, b"{\"a\":1}");
+        assert!(found[1].headers.is_empty());
+        assert_eq!(found[1].body, b"no headers");
+
+        assert!(parts(&mime::TEXT_PLAIN, body).is_none());
+        // no closing delimiter
+        assert!(split(b"--b\r\n\r\nx", "b").is_none());
+    }
+}
@@ -0,0 +1,383 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use colored::Colorize;
+use hyper::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::{exchange::Exchange, har, proxy, shadow};
+
+/// One recorded exchange, a file of its own in the recordings directory.
+#[derive(Debug, Serialize, Deserialize)]
+struct Recording {
+    method: String,
+    /// path and query as the client sent them
+    path: String,
+    #[serde(default)]
+    body: String,
+    response: har::HarResponse,
+}
+
+impl Recording {
+    fn matches(&self, method: &str, path: &str) -> bool {
+        self.method == method && self.path == path
+    }
+}
+
+enum Mode {
+    Forward {
+        client: reqwest::Client,
+        target: Url,
+        record: Option<PathBuf>,
+    },
+    Replay(Vec<Recording>),
+}
+
+struct State {
+    mode: Mode,
+    seq: AtomicUsize,
+    /// keeps the logs of concurrent exchanges from interleaving
+    print: Mutex<()>,
+}
+
+/// `seq-method-path.json`, readable in a directory listing.
+fn file_name(seq: usize, method: &str, path: &str) -> String {
+    let path = path.split('?').next().unwrap_or_default();
+    let mut slug: String = path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .take(60)
+        .collect();
+    slug = slug.trim_matches('_').to_string();
+    if slug.is_empty() {
+        slug = "root".into();
+    }
+    format!("{:04}-{}-{}.json", seq, method.to_ascii_lowercase(), slug)
+}
+
+/// The recordings in `dir`, oldest first.
+fn load(dir: &Path) -> Result<Vec<Recording>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|e| Some(e.ok()?.path()))
+        .filter(|p| p.extension().is_some_and(|e| e == "json"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .map(|p| {
+            let s = std::fs::read_to_string(p)
+                .with_context(|| format!("Failed to read {}", p.display()))?;
+            serde_json::from_str(&s)
+                .map_err(|e| anyhow!(format!("Failed to parse {}: {}", p.display(), e)))
+        })
+        .collect()
+}
+
+/// The highest sequence number among the recordings in `dir`, so new ones are
+/// numbered after it even when earlier files have been deleted.
+fn last_seq(dir: &Path) -> Result<usize> {
+    Ok(std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|e| {
+            let name = e.ok()?.file_name();
+            let name = name.to_str()?.strip_suffix(".json")?;
+            name.split('-').next()?.parse().ok()
+        })
+        .max()
+        .unwrap_or(0))
+}
+
+/// The recording to answer with: the latest with the same method, path and
+/// body, or failing that the latest with the same method and path.
+fn lookup<'a>(
+    recordings: &'a [Recording],
+    method: &str,
+    path: &str,
+    body: &str,
+) -> Option<&'a Recording> {
+    let mut candidates = recordings.iter().rev().filter(|r| r.matches(method, path));
+    let first = candidates.clone().next()?;
+    Some(candidates.find(|r| r.body == body).unwrap_or(first))
+}
+
+/// Run a server on `addr` that forwards to `target`, printing every exchange
+/// and saving it to `record` if given, or one that answers from the
+/// recordings in `replay` without any upstream.
+pub async fn serve(
+    addr: SocketAddr,
+    target: Option<Url>,
+    record: Option<PathBuf>,
+    replay: Option<PathBuf>,
+    insecure: bool,
+) -> Result<()> {
+    let (mode, banner) = match (target, replay) {
+        (_, Some(dir)) => {
+            let recordings = load(&dir)?;
+            let banner = format!(
+                "replaying {} recordings from {}",
+                recordings.len(),
+                dir.display()
+            );
+            (Mode::Replay(recordings), banner)
+        }
+        (Some(target), None) => {
+            if let Some(dir) = &record {
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+            let client = reqwest::Client::builder()
+                .no_proxy()
+                .danger_accept_invalid_certs(insecure)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?;
+            let banner = match &record {
+                Some(dir) => format!("forwarding to {}, recording to {}", target, dir.display()),
+                None => format!("forwarding to {}", target),
+            };
+            (
+                Mode::Forward {
+                    client,
+                    target,
+                    record,
+                },
+                banner,
+            )
+        }
+        (None, None) => return Err(anyhow!("Failed to serve: give --target or --replay")),
+    };
+    // numbering goes on after what is already recorded
+    let recorded = match &mode {
+        Mode::Forward {
+            record: Some(dir), ..
+        } => last_seq(dir)?,
+        _ => 0,
+    };
+    let state = Arc::new(State {
+        mode,
+        seq: AtomicUsize::new(recorded + 1),
+        print: Mutex::new(()),
+    });
+    let make = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req))) }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|e| anyhow!(format!("Failed to listen on {}: {}", addr, e)))?
+        .serve(make);
+    println!("{} http://{}, {}", "Listening on".bold(), addr, banner);
+    Ok(server.await?)
+}
+
+async fn handle(state: Arc<State>, req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let result = match &state.mode {
+        Mode::Forward {
+            client,
+            target,
+            record,
+        } => forward(&state, client, target, record.as_deref(), req).await,
+        Mode::Replay(recordings) => replay(&state, recordings, req).await,
+    };
+    Ok(result.unwrap_or_else(|e| error(StatusCode::BAD_GATEWAY, &e.to_string())))
+}
+
+fn error(status: StatusCode, msg: &str) -> Response<Body> {
+    eprintln!("{} {}", status.to_string().red(), msg);
+    let mut resp = Response::new(Body::from(format!("{}\n", msg)));
+    *resp.status_mut() = status;
+    resp
+}
+
+fn path_and_query(req: &Request<Body>) -> String {
+    req.uri()
+        .path_and_query()
+        .map_or("/", |p| p.as_str())
+        .to_string()
+}
+
+async fn forward(
+    state: &State,
+    client: &reqwest::Client,
+    target: &Url,
+    record: Option<&Path>,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let seq = state.seq.fetch_add(1, Ordering::Relaxed);
+    let path = path_and_query(&req);
+    let url = shadow::shadow_url(&target.join(&path)?, target);
+    let (parts, body) = req.into_parts();
+    let req_body = hyper::body::to_bytes(body).await?;
+    let mut headers = parts.headers;
+    proxy::strip_hop_by_hop(&mut headers);
+    // reqwest derives these from the URL and body
+    headers.remove(header::HOST);
+    headers.remove(header::CONTENT_LENGTH);
+
+    let started = Instant::now();
+    let upstream = client
+        .request(parts.method.clone(), url.clone())
+        .headers(headers.clone())
+        .body(req_body.clone())
+        .send()
+        .await?;
+    let exchange = Exchange::read(upstream, started).await?;
+
+    {
+        let _guard = state.print.lock().unwrap();
+        println!(
+            "{} {} {}",
+            format!("#{}", seq).bold(),
+            parts.method.to_string().cyan().bold(),
+            url
+        );
+        proxy::print_message(&headers, &req_body);
+        println!(
+            "{} {:?} {} {}",
+            format!("#{}", seq).bold(),
+            exchange.version,
+            exchange.status.to_string().blue(),
+            proxy::format_elapsed(exchange.timing.total).dimmed()
+        );
+        proxy::print_message(&exchange.headers, &exchange.body);
+    }
+
+    if let Some(dir) = record {
+        let recording = Recording {
+            method: parts.method.to_string(),
+            body: String::from_utf8_lossy(&req_body).into_owned(),
+            response: har::response(&exchange),
+            path,
+        };
+        let file = dir.join(file_name(seq, &recording.method, &recording.path));
+        std::fs::write(&file, serde_json::to_string_pretty(&recording)? + "\n")
+            .with_context(|| format!("Failed to write {}", file.display()))?;
+    }
+
+    let mut resp_headers = exchange.headers;
+    proxy::strip_hop_by_hop(&mut resp_headers);
+    let mut resp = Response::new(Body::from(exchange.body));
+    *resp.status_mut() = exchange.status;
+    *resp.headers_mut() = resp_headers;
+    Ok(resp)
+}
+
+async fn replay(
+    state: &State,
+    recordings: &[Recording],
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let seq = state.seq.fetch_add(1, Ordering::Relaxed);
+    let method = req.method().to_string();
+    let path = path_and_query(&req);
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    let body = String::from_utf8_lossy(&body);
+    let Some(recording) = lookup(recordings, &method, &path, &body) else {
+        return Ok(error(
+            StatusCode::NOT_FOUND,
+            &format!("No recording for {} {}", method, path),
+        ));
+    };
+    let recorded = &recording.response;
+    let content = &recorded.content;
+    let text = content.text.clone().unwrap_or_default();
+    let bytes = match content.encoding.as_deref() {
+        Some("base64") => STANDARD
+            .decode(text)
+            .map_err(|e| anyhow!(format!("Failed to decode recorded body: {}", e)))?,
+        _ => text.into_bytes(),
+    };
+    let mut headers = HeaderMap::new();
+    for h in &recorded.headers {
+        // the body was stored decoded
+        if ["content-length", "content-encoding"].contains(&h.name.to_ascii_lowercase().as_str()) {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(h.name.as_bytes()),
+            HeaderValue::from_str(&h.value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    proxy::strip_hop_by_hop(&mut headers);
+    {
+        let _guard = state.print.lock().unwrap();
+        println!(
+            "{} {} {} {}",
+            format!("#{}", seq).bold(),
+            method.cyan().bold(),
+            path,
+            format!("=> {} (replayed)", recorded.status).dimmed()
+        );
+    }
+    let mut resp = Response::new(Body::from(bytes));
+    *resp.status_mut() = StatusCode::from_u16(recorded.status)?;
+    *resp.headers_mut() = headers;
+    Ok(resp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(method: &str, path: &str, body: &str, status: u16) -> Recording {
+        Recording {
+            method: method.into(),
+            path: path.into(),
+            body: body.into(),
+            response: har::HarResponse {
+                status,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn lookup_works() {
+        let recordings = [
+            recording("GET", "/users?page=1", "", 200),
+            recording("POST", "/users", "{\"a\":1}", 201),
+            recording("POST", "/users", "{\"a\":2}", 409),
+            recording("GET", "/users?page=1", "", 304),
+        ];
+        let status = |m, p, b| lookup(&recordings, m, p, b).map(|r| r.response.status);
+        assert_eq!(status("GET", "/users?page=1", ""), Some(304));
+        assert_eq!(status("POST", "/users", "{\"a\":1}"), Some(201));
+        // the latest one when no body matches
+        assert_eq!(status("POST", "/users", "{}"), Some(409));
+        assert_eq!(status("GET", "/users", ""), None);
+        assert_eq!(
+            file_name(3, "GET", "/v1/users?page=1"),
+            "0003-get-v1_users.json"
+        );
+        assert_eq!(file_name(12, "POST", "/"), "0012-post-root.json");
+    }
+
+    #[test]
+    fn last_seq_works() {
+        let dir = std::env::temp_dir().join("httpie-mock-last-seq");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(last_seq(&dir).unwrap(), 0);
+        // 0001 and 0002 were deleted, numbering must not reuse 0003
+        for name in ["0003-get-a.json", "0004-post-b.json", "notes.txt"] {
+            std::fs::write(dir.join(name), "{}").unwrap();
+        }
+        assert_eq!(last_seq(&dir).unwrap(), 4);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
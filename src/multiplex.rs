@@ -0,0 +1,463 @@
+use std::{
+    future::poll_fn,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use h2::client::SendRequest;
+use hyper::{body::Bytes, header::HeaderMap, Method, Request};
+use mime::Mime;
+use reqwest::Url;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader},
+    net::TcpStream,
+    task::JoinSet,
+};
+
+use crate::{body, http2, stats::format_duration, Http2Opts};
+
+/// A line typed in the interactive session.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// `[count] METHOD target [body]`: `count` concurrent streams
+    Request {
+        count: usize,
+        method: Method,
+        target: String,
+        body: Option<String>,
+    },
+    Ping,
+    Settings,
+    Help,
+}
+
+const HELP: &str = "\
+Commands:
+  [count] METHOD path [body]   open `count` streams at once, e.g. `5 GET /slow`
+  ping                         measure the round trip of a PING frame
+  settings                     show stream limits and active streams
+  end of input (Ctrl-D)        wait for open streams, then close the connection";
+
+impl std::str::FromStr for Command {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "ping" => return Ok(Self::Ping),
+            "settings" => return Ok(Self::Settings),
+            "help" | "?" => return Ok(Self::Help),
+            _ => {}
+        }
+        let mut rest = s;
+        let mut word = || {
+            let trimmed = rest.trim_start();
+            let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+            let (w, r) = trimmed.split_at(end);
+            rest = r;
+            w
+        };
+        let mut first = word();
+        let count = match first.parse::<usize>() {
+            Ok(0) => return Err(anyhow!("Failed to parse {}: count must be positive", s)),
+            Ok(n) => {
+                first = word();
+                n
+            }
+            Err(_) => 1,
+        };
+        let method = first
+            .to_ascii_uppercase()
+            .parse()
+            .map_err(|_| anyhow!(format!("Failed to parse {}: unknown method {}", s, first)))?;
+        let target = word().to_string();
+        if target.is_empty() {
+            return Err(anyhow!(format!("Failed to parse {}: missing path", s)));
+        }
+        let body = Some(rest.trim())
+            .filter(|b| !b.is_empty())
+            .map(String::from);
+        Ok(Self::Request {
+            count,
+            method,
+            target,
+            body,
+        })
+    }
+}
+
+/// Open one HTTP/2 connection to `url`'s origin and run requests typed on
+/// stdin over it, printing stream ids and timings as frames arrive.
+pub async fn session(
+    url: &str,
+    headers: &HeaderMap,
+    print_bodies: bool,
+    settings: &Http2Opts,
+) -> Result<()> {
+    let base: Url = url.parse()?;
+    let host = base
+        .host_str()
+        .ok_or_else(|| anyhow!(format!("Failed to connect: no host in {}", url)))?
+        .to_string();
+    let port = base.port_or_known_default().unwrap_or(443);
+    let tcp = TcpStream::connect((host.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to connect to {}:{}", host, port))?;
+    tcp.set_nodelay(true)?;
+    match base.scheme() {
+        // cleartext HTTP/2 with prior knowledge
+        "http" => run(tcp, base, headers, print_bodies, settings).await,
+        "https" => {
+            let connector = native_tls::TlsConnector::builder()
+                .request_alpns(&["h2"])
+                .build()?;
+            let tls = tokio_native_tls::TlsConnector::from(connector)
+                .connect(&host, tcp)
+                .await
+                .with_context(|| format!("Failed to set up TLS with {}", host))?;
+            let alpn = tls.get_ref().negotiated_alpn()?;
+            if alpn.as_deref() != Some(b"h2") {
+                return Err(anyhow!(format!(
+                    "Failed to negotiate HTTP/2 with {} (ALPN: {})",
+                    host,
+                    alpn.map(|p| String::from_utf8_lossy(&p).into_owned())
+                        .unwrap_or_else(|| "none".into())
+                )));
+            }
+            run(tls, base, headers, print_bodies, settings).await
+        }
+        other => Err(anyhow!(format!(
+            "Failed to connect: unsupported scheme {}",
+            other
+        ))),
+    }
+}
+
+async fn run<T>(
+    io: T,
+    base: Url,
+    headers: &HeaderMap,
+    print_bodies: bool,
+    settings: &Http2Opts,
+) -> Result<()>
+where
+    T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (client, mut conn) = http2::builder(settings)
+        .handshake(io)
+        .await
+        .context("Failed to start HTTP/2")?;
+    let mut ping_pong = conn.ping_pong();
+    eprintln!(
+        "{} {} over HTTP/2; `help` lists commands",
+        "Connected to".bold(),
+        base.origin().ascii_serialization()
+    );
+
+    let print = Arc::new(Mutex::new(()));
+    let mut client = Some(client);
+    let mut streams = JoinSet::new();
+    let mut ping_sent: Option<Instant> = None;
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    // without --h2-ping-interval the timer is never polled
+    let period = settings
+        .h2_ping_interval
+        .unwrap_or(Duration::from_secs(3600))
+        .max(Duration::from_millis(1));
+    let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    loop {
+        tokio::select! {
+            line = stdin.next_line(), if stdin_open => {
+                let Some(line) = line? else {
+                    // no more input: stop opening streams and let the open ones finish
+                    stdin_open = false;
+                    client = None;
+                    if streams.is_empty() {
+                        break;
+                    }
+                    continue;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let Some(send) = client.as_ref() else { continue };
+                match line.parse::<Command>() {
+                    Ok(Command::Help) => eprintln!("{}", HELP),
+                    Ok(Command::Settings) => eprintln!(
+                        "server allows {} concurrent streams, we allow {}; {} in flight",
+                        limit(conn.max_concurrent_send_streams()),
+                        limit(conn.max_concurrent_recv_streams()),
+                        streams.len()
+                    ),
+                    Ok(Command::Ping) => match ping_pong.as_mut() {
+                        Some(pp) if ping_sent.is_none() => {
+                            pp.send_ping(h2::Ping::opaque())?;
+                            ping_sent = Some(Instant::now());
+                        }
+                        Some(_) => eprintln!("A PING is already in flight"),
+                        None => eprintln!("PING is unavailable on this connection"),
+                    },
+                    Ok(Command::Request { count, method, target, body }) => {
+                        let url = match resolve(&base, &target) {
+                            Ok(url) => url,
+                            Err(e) => {
+                                eprintln!("{}", e.to_string().red());
+                                continue;
+                            }
+                        };
+                        for _ in 0..count {
+                            let exchange = Exchange {
+                                method: method.clone(),
+                                url: url.clone(),
+                                headers: headers.clone(),
+                                body: body.clone().map(Bytes::from),
+                                print_bodies,
+                                print: print.clone(),
+                            };
+                            streams.spawn(exchange.run(send.clone()));
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e.to_string().red()),
+                }
+            }
+            _ = keepalive.tick(), if settings.h2_ping_interval.is_some() => {
+                if let (Some(pp), None) = (ping_pong.as_mut(), ping_sent) {
+                    pp.send_ping(h2::Ping::opaque())?;
+                    ping_sent = Some(Instant::now());
+                }
+            }
+            pong = poll_fn(|cx| ping_pong.as_mut().unwrap().poll_pong(cx)), if ping_sent.is_some() => {
+                pong?;
+                let rtt = ping_sent.take().unwrap().elapsed();
+                println!("{} round trip {}", "PONG".cyan().bold(), format_duration(rtt));
+            }
+            Some(_) = streams.join_next(), if !streams.is_empty() => {
+                if !stdin_open && streams.is_empty() {
+                    break;
+                }
+            }
+            result = &mut conn => {
+                match result {
+                    Ok(()) => eprintln!("{}", "Server closed the connection".yellow()),
+                    Err(e) => eprintln!("{}", describe_close(&e).yellow()),
+                }
+                // streams still open were cut off with the connection
+                while streams.join_next().await.is_some() {}
+                return Ok(());
+            }
+        }
+    }
+    // all handles are gone, so the connection winds down with a GOAWAY
+    drop(client);
+    if tokio::time::timeout(Duration::from_secs(2), conn)
+        .await
+        .is_err()
+    {
+        eprintln!("Connection did not close in time");
+    }
+    Ok(())
+}
+
+fn limit(n: usize) -> String {
+    if n == usize::MAX {
+        "unlimited".into()
+    } else {
+        n.to_string()
+    }
+}
+
+fn describe_close(e: &h2::Error) -> String {
+    match e.reason() {
+        Some(reason) if e.is_go_away() && e.is_remote() => {
+            format!("Server sent GOAWAY ({:?})", reason)
+        }
+        Some(reason) if e.is_go_away() => format!("Connection closed with GOAWAY ({:?})", reason),
+        _ => format!("Connection failed: {}", e),
+    }
+}
+
+/// `target` as a URL on the connection's origin.
+fn resolve(base: &Url, target: &str) -> Result<Url> {
+    let url = base
+        .join(target)
+        .with_context(|| format!("Failed to parse target {}", target))?;
+    if url.origin() != base.origin() {
+        return Err(anyhow!(format!(
+            "This connection only serves {}",
+            base.origin().ascii_serialization()
+        )));
+    }
+    Ok(url)
+}
+
+/// One request and response on its own stream.
+struct Exchange {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body: Option<Bytes>,
+    print_bodies: bool,
+    print: Arc<Mutex<()>>,
+}
+
+impl Exchange {
+    async fn run(self, send: SendRequest<Bytes>) {
+        let path = match self.url.query() {
+            Some(q) => format!("{}?{}", self.url.path(), q),
+            None => self.url.path().to_string(),
+        };
+        if let Err((id, e)) = self.exchange(send, &path).await {
+            let id = id
+                .map(|id| format!("[{}]", id))
+                .unwrap_or_else(|| "[-]".into());
+            let reason = match e.downcast_ref::<h2::Error>() {
+                Some(h2e) if h2e.is_go_away() => format!(
+                    "cut off by GOAWAY ({:?})",
+                    h2e.reason().unwrap_or(h2::Reason::NO_ERROR)
+                ),
+                Some(h2e) if h2e.is_reset() => format!(
+                    "stream reset ({:?})",
+                    h2e.reason().unwrap_or(h2::Reason::NO_ERROR)
+                ),
+                _ => e.to_string(),
+            };
+            println!(
+                "{} {} {}: {}",
+                id.cyan().bold(),
+                self.method,
+                path,
+                reason.red()
+            );
+        }
+    }
+
+    async fn exchange(
+        &self,
+        send: SendRequest<Bytes>,
+        path: &str,
+    ) -> std::result::Result<(), (Option<u32>, anyhow::Error)> {
+        let queued = Instant::now();
+        // waits while the server's concurrent stream limit is reached
+        let mut send = send.ready().await.map_err(|e| (None, e.into()))?;
+        let queued = queued.elapsed();
+
+        let mut req = Request::builder()
+            .method(self.method.clone())
+            .uri(self.url.as_str())
+            .body(())
+            .map_err(|e| (None, e.into()))?;
+        *req.headers_mut() = self.headers.clone();
+        let start = Instant::now();
+        let (resp, mut stream) = send
+            .send_request(req, self.body.is_none())
+            .map_err(|e| (None, e.into()))?;
+        let id = resp.stream_id().as_u32();
+        let fail = |e: h2::Error| (Some(id), anyhow::Error::from(e));
+        if let Some(body) = &self.body {
+            stream.send_data(body.clone(), true).map_err(fail)?;
+        }
+        println!(
+            "{} {} {}{}",
+            format!("[{}]", id).cyan().bold(),
+            self.method.to_string().bold(),
+            path,
+            if queued > Duration::from_millis(1) {
+                format!(" (queued {})", format_duration(queued))
+                    .dimmed()
+                    .to_string()
+            } else {
+                String::new()
+            }
+        );
+
+        let resp = resp.await.map_err(fail)?;
+        let headers_at = start.elapsed();
+        println!(
+            "{} {} headers after {}",
+            format!("[{}]", id).cyan().bold(),
+            resp.status().to_string().blue(),
+            format_duration(headers_at)
+        );
+        let (parts, mut recv) = resp.into_parts();
+        let mut frames = 0;
+        let mut bytes = Vec::new();
+        while let Some(chunk) = recv.data().await {
+            let chunk = chunk.map_err(fail)?;
+            frames += 1;
+            bytes.extend_from_slice(&chunk);
+            // hand the window back so the server can keep sending
+            recv.flow_control()
+                .release_capacity(chunk.len())
+                .map_err(fail)?;
+        }
+        let trailers = recv.trailers().await.map_err(fail)?;
+        let _guard = self.print.lock().unwrap();
+        println!(
+            "{} done: {} bytes in {} DATA frames, {} total{}",
+            format!("[{}]", id).cyan().bold(),
+            bytes.len(),
+            frames,
+            format_duration(start.elapsed()),
+            trailers
+                .map(|t| format!(", {} trailers", t.len()))
+                .unwrap_or_default()
+        );
+        if self.print_bodies && !bytes.is_empty() {
+            let mime: Option<Mime> = parts
+                .headers
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok()?.parse().ok());
+            crate::print_body(mime.clone(), &body::decode_text(&bytes, mime.as_ref()));
+            println!();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_works() {
+        assert_eq!("ping".parse::<Command>().unwrap(), Command::Ping);
+        assert_eq!(
+            "get /a".parse::<Command>().unwrap(),
+            Command::Request {
+                count: 1,
+                method: Method::GET,
+                target: "/a".into(),
+                body: None,
+            }
+        );
+        assert_eq!(
+            " 5 POST /items {\"a\": 1} ".parse::<Command>().unwrap(),
+            Command::Request {
+                count: 5,
+                method: Method::POST,
+                target: "/items".into(),
+                body: Some("{\"a\": 1}".into()),
+            }
+        );
+        assert!("0 GET /".parse::<Command>().is_err());
+        assert!("GET".parse::<Command>().is_err());
+        assert!("G@T /".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn resolve_works() {
+        let base: Url = "https://example.com/api/".parse().unwrap();
+        assert_eq!(
+            resolve(&base, "users?x=1").unwrap().as_str(),
+            "https://example.com/api/users?x=1"
+        );
+        assert_eq!(
+            resolve(&base, "/health").unwrap().as_str(),
+            "https://example.com/health"
+        );
+        assert!(resolve(&base, "https://other.com/").is_err());
+    }
+}
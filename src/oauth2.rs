@@ -0,0 +1,357 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{self, Auth},
+    config, secrets,
+};
+
+/// A token this close to expiring is fetched again instead of used.
+const MARGIN: u64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Flow {
+    /// The client's own id and secret, for machine to machine APIs
+    #[default]
+    ClientCredentials,
+    /// Sign in on another device with a code, for tokens on behalf of a user
+    /// (RFC 8628)
+    DeviceCode,
+}
+
+/// Where a token comes from, kept with it so that `--session` can fetch a
+/// new one when it expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grant {
+    pub flow: Flow,
+    pub token_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_url: Option<String>,
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// What ~/.httpie/sessions/<name>.json holds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub access_token: String,
+    pub token_type: String,
+    /// seconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub grant: Grant,
+}
+
+impl Session {
+    fn expired(&self, now: u64) -> bool {
+        self.expires_at.is_some_and(|at| at <= now + MARGIN)
+    }
+}
+
+impl Auth for Session {
+    fn header(&self) -> Option<String> {
+        // servers expect `Bearer` whatever case the token endpoint used
+        let kind = if self.token_type.eq_ignore_ascii_case("bearer") {
+            "Bearer"
+        } else {
+            &self.token_type
+        };
+        Some(format!("{} {}", kind, self.access_token))
+    }
+}
+
+/// A token endpoint response, or its error (RFC 6749 section 5).
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    token_type: Option<String>,
+    expires_in: Option<u64>,
+    refresh_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: Option<u64>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn dir() -> PathBuf {
+    config::default_path().with_file_name("sessions")
+}
+
+fn path_in(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        || name.starts_with('.')
+    {
+        return Err(anyhow!(format!(
+            "Failed to use session {}: names are letters, digits, `-`, `_` and `.`",
+            name
+        )));
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+fn save_in(dir: &Path, name: &str, session: &Session) -> Result<()> {
+    let path = path_in(dir, name)?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    // the tokens are credentials
+    secrets::write_private(&path, &(serde_json::to_string_pretty(session)? + "\n"))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn load_in(dir: &Path, name: &str) -> Result<Session> {
+    let path = path_in(dir, name)?;
+    let text = std::fs::read_to_string(&path).with_context(|| {
+        format!(
+            "Failed to read session {}: fetch a token first with `httpie oauth2 --session {}`",
+            name, name
+        )
+    })?;
+    serde_json::from_str(&text)
+        .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+/// Turn a token endpoint response into a session, or its error into ours.
+fn session_from(resp: TokenResponse, grant: &Grant) -> Result<Session> {
+    if let Some(error) = resp.error {
+        let detail = resp
+            .error_description
+            .map(|d| format!(" ({})", d))
+            .unwrap_or_default();
+        return Err(anyhow!(format!(
+            "Failed to get a token: {}{}",
+            error, detail
+        )));
+    }
+    let access_token = resp
+        .access_token
+        .ok_or_else(|| anyhow!("Failed to get a token: the response has no access_token"))?;
+    Ok(Session {
+        access_token,
+        token_type: resp.token_type.unwrap_or_else(|| "Bearer".into()),
+        expires_at: resp.expires_in.map(|s| now() + s),
+        refresh_token: resp.refresh_token,
+        grant: grant.clone(),
+    })
+}
+
+async fn token_request(
+    client: &Client,
+    grant: &Grant,
+    form: &[(&str, &str)],
+) -> Result<TokenResponse> {
+    let mut req = client.post(&grant.token_url).form(form);
+    // client_secret_basic, which every server has to support
+    if let Some(secret) = &grant.client_secret {
+        req = req.basic_auth(&grant.client_id, Some(secret));
+    }
+    // the token endpoint has the shared rate limit of its host too
+    let resp = auth::send(client, req.build()?, None)
+        .await
+        .with_context(|| format!("Failed to reach {}", grant.token_url))?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    serde_json::from_str(&text).map_err(|_| {
+        anyhow!(format!(
+            "Failed to get a token: {} answered {} with {}",
+            grant.token_url,
+            status,
+            text.trim()
+        ))
+    })
+}
+
+async fn client_credentials(client: &Client, grant: &Grant) -> Result<Session> {
+    let mut form = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = &grant.scope {
+        form.push(("scope", scope));
+    }
+    if grant.client_secret.is_none() {
+        form.push(("client_id", &grant.client_id));
+    }
+    session_from(token_request(client, grant, &form).await?, grant)
+}
+
+async fn device_code(client: &Client, grant: &Grant) -> Result<Session> {
+    let device_url = grant
+        .device_url
+        .as_deref()
+        .ok_or_else(|| anyhow!("Failed to start the device flow: give --device-url"))?;
+    let mut form = vec![("client_id", grant.client_id.as_str())];
+    if let Some(scope) = &grant.scope {
+        form.push(("scope", scope));
+    }
+    let req = client.post(device_url).form(&form).build()?;
+    let resp = auth::send(client, req, None)
+        .await
+        .with_context(|| format!("Failed to reach {}", device_url))?;
+    if !resp.status().is_success() {
+        return Err(anyhow!(format!(
+            "Failed to start the device flow: {} answered {} with {}",
+            device_url,
+            resp.status(),
+            resp.text().await.unwrap_or_default().trim()
+        )));
+    }
+    let device: DeviceResponse = resp
+        .json()
+        .await
+        .context("Failed to parse the device authorization response")?;
+    eprintln!(
+        "Open {} and enter the code {}",
+        device.verification_uri.bold(),
+        device.user_code.bold()
+    );
+    if let Some(complete) = &device.verification_uri_complete {
+        eprintln!("{}", format!("or go straight to {}", complete).dimmed());
+    }
+
+    let mut interval = device.interval.unwrap_or(5);
+    let deadline = now() + device.expires_in;
+    let form = [
+        ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+        ("device_code", device.device_code.as_str()),
+        ("client_id", grant.client_id.as_str()),
+    ];
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        let resp = token_request(client, grant, &form).await?;
+        match resp.error.as_deref() {
+            Some("authorization_pending") => {}
+            Some("slow_down") => interval += 5,
+            _ => return session_from(resp, grant),
+        }
+        if now() >= deadline {
+            return Err(anyhow!(
+                "Failed to get a token: the code expired before it was entered"
+            ));
+        }
+    }
+}
+
+/// Get a token the way `grant` says and keep it as session `name`.
+pub async fn acquire(client: &Client, grant: Grant, name: &str) -> Result<()> {
+    path_in(&dir(), name)?;
+    let session = match grant.flow {
+        Flow::ClientCredentials => client_credentials(client, &grant).await?,
+        Flow::DeviceCode => device_code(client, &grant).await?,
+    };
+    save_in(&dir(), name, &session)?;
+    let expiry = match session.expires_at {
+        Some(at) => format!(", expires in {}s", at.saturating_sub(now())),
+        None => String::new(),
+    };
+    println!(
+        "{} Token saved to session {}{}",
+        "✓".green(),
+        name.bold(),
+        expiry
+    );
+    Ok(())
+}
+
+/// Session `name`, with a new token when the saved one has expired: from the
+/// refresh token if there is one, or the client credentials again.
+pub async fn session(client: &Client, name: &str) -> Result<Session> {
+    let session = load_in(&dir(), name)?;
+    if !session.expired(now()) {
+        return Ok(session);
+    }
+    let grant = session.grant.clone();
+    let renewed = match (&session.refresh_token, grant.flow) {
+        (Some(refresh), _) => {
+            let form = [
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh.as_str()),
+                ("client_id", grant.client_id.as_str()),
+            ];
+            let mut renewed = session_from(token_request(client, &grant, &form).await?, &grant)?;
+            // servers may keep the refresh token the same and not send it
+            renewed.refresh_token = renewed.refresh_token.or(session.refresh_token);
+            renewed
+        }
+        (None, Flow::ClientCredentials) => client_credentials(client, &grant).await?,
+        (None, Flow::DeviceCode) => {
+            return Err(anyhow!(format!(
+                "Failed to use session {}: the token expired, sign in again with `httpie oauth2 --session {}`",
+                name, name
+            )))
+        }
+    };
+    save_in(&dir(), name, &renewed)?;
+    Ok(renewed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_works() {
+        let grant = Grant {
+            flow: Flow::ClientCredentials,
+            token_url: "http://localhost/token".into(),
+            device_url: None,
+            client_id: "id".into(),
+            client_secret: Some("secret".into()),
+            scope: None,
+        };
+        let resp: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"t0k","token_type":"bearer","expires_in":3600}"#,
+        )
+        .unwrap();
+        let session = session_from(resp, &grant).unwrap();
+        assert_eq!(session.header().unwrap(), "Bearer t0k");
+        assert!(!session.expired(now()));
+        assert!(session.expired(now() + 3600));
+
+        let dir = std::env::temp_dir().join(format!("httpie-sessions-{}", std::process::id()));
+        save_in(&dir, "staging", &session).unwrap();
+        assert_eq!(load_in(&dir, "staging").unwrap().access_token, "t0k");
+        let meta = std::fs::metadata(path_in(&dir, "staging").unwrap()).unwrap();
+        assert_eq!(
+            std::os::unix::fs::PermissionsExt::mode(&meta.permissions()) & 0o777,
+            0o600
+        );
+        assert!(load_in(&dir, "../x").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let error: TokenResponse =
+            serde_json::from_str(r#"{"error":"invalid_client","error_description":"bad secret"}"#)
+                .unwrap();
+        let message = session_from(error, &grant).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Failed to get a token: invalid_client (bad secret)"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    io::Write,
+    sync::{Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tokio::runtime::RuntimeFlavor;
+
+/// Set by `--max-render-rate`: bytes a second written to stdout at most.
+static RATE: OnceLock<u64> = OnceLock::new();
+
+/// When writing started, and the bytes written since.
+static WRITTEN: Mutex<Option<(Instant, u64)>> = Mutex::new(None);
+
+pub fn limit_rate(bytes_per_sec: u64) {
+    RATE.set(bytes_per_sec).ok();
+}
+
+/// Book `n` more bytes written at `rate`, and how long to wait before
+/// writing more to stay under it.
+fn due(n: usize, rate: u64) -> Duration {
+    let mut written = WRITTEN.lock().unwrap();
+    let (started, total) = written.get_or_insert_with(|| (Instant::now(), 0));
+    *total += n as u64;
+    let at = Duration::from_secs_f64(*total as f64 / rate as f64);
+    at.saturating_sub(started.elapsed())
+}
+
+/// Write `s` to stdout as it is taken, so a terminal that is slow to read
+/// holds up whatever produces the output rather than letting it pile up;
+/// at most `--max-render-rate` a second when given. Output cut off by a
+/// closed pipe is dropped.
+pub fn write(s: &str) {
+    let Some(&rate) = RATE.get() else {
+        std::io::stdout().lock().write_all(s.as_bytes()).ok();
+        return;
+    };
+    // the waits would hold up the other tasks of the worker thread, so
+    // those move to other workers for the while
+    match tokio::runtime::Handle::try_current() {
+        Ok(h) if h.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| write_paced(s, rate))
+        }
+        _ => write_paced(s, rate),
+    }
+}
+
+fn write_paced(s: &str, rate: u64) {
+    let mut stdout = std::io::stdout().lock();
+    // a tenth of a second's worth at a time, to keep the pace even
+    let piece = (rate / 10).clamp(1, 64 * 1024) as usize;
+    for piece in s.as_bytes().chunks(piece) {
+        if stdout
+            .write_all(piece)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            return;
+        }
+        thread::sleep(due(piece.len(), rate));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_works() {
+        // 1000 bytes at 10 KB/s are due after 100 ms, less what has passed
+        let wait = due(1000, 10_000);
+        assert!(wait <= Duration::from_millis(100));
+        assert!(wait > Duration::from_millis(50));
+        let wait = due(1000, 10_000);
+        assert!(wait > Duration::from_millis(150));
+    }
+}
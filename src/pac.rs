@@ -0,0 +1,328 @@
+use std::{
+    net::{IpAddr, ToSocketAddrs, UdpSocket},
+    sync::mpsc,
+    thread,
+};
+
+use anyhow::{anyhow, Context as _, Result};
+use boa_engine::{js_string, Context, JsResult, JsValue, NativeFunction, Source};
+use reqwest::Url;
+
+/// The helper functions every PAC script may call, except the two that need
+/// the network (`dnsResolve`, `myIpAddress`), which are native.
+const PRELUDE: &str = r#"
+function dnsDomainIs(host, domain) {
+  return host.length >= domain.length &&
+    host.substring(host.length - domain.length) == domain;
+}
+function dnsDomainLevels(host) { return host.split('.').length - 1; }
+function isPlainHostName(host) { return host.indexOf('.') == -1; }
+function localHostOrDomainIs(host, hostdom) {
+  return host == hostdom || hostdom.lastIndexOf(host + '.', 0) == 0;
+}
+function isResolvable(host) { return dnsResolve(host) != null; }
+function __addr(ip) {
+  var b = ip.split('.');
+  return ((b[0] & 0xff) << 24) | ((b[1] & 0xff) << 16) | ((b[2] & 0xff) << 8) | (b[3] & 0xff);
+}
+function isInNet(host, pattern, mask) {
+  var ip = /^\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}$/.test(host) ? host : dnsResolve(host);
+  if (ip == null) return false;
+  var m = __addr(mask);
+  return (__addr(ip) & m) == (__addr(pattern) & m);
+}
+function shExpMatch(str, pattern) {
+  var re = pattern.replace(/[.+^${}()|[\]\\]/g, '\\$&').replace(/\*/g, '.*').replace(/\?/g, '.');
+  return new RegExp('^' + re + '$').test(str);
+}
+function __args(args) {
+  var a = Array.prototype.slice.call(args);
+  var gmt = a[a.length - 1] == 'GMT';
+  if (gmt) a.pop();
+  var now = new Date();
+  var get = function (f) { return gmt ? now['getUTC' + f]() : now['get' + f](); };
+  return { a: a, get: get };
+}
+function __within(lo, v, hi) { return lo <= hi ? lo <= v && v <= hi : v >= lo || v <= hi; }
+var __days = { SUN: 0, MON: 1, TUE: 2, WED: 3, THU: 4, FRI: 5, SAT: 6 };
+var __months = { JAN: 0, FEB: 1, MAR: 2, APR: 3, MAY: 4, JUN: 5, JUL: 6, AUG: 7, SEP: 8, OCT: 9, NOV: 10, DEC: 11 };
+function weekdayRange() {
+  var x = __args(arguments), lo = __days[x.a[0]];
+  var hi = x.a.length > 1 ? __days[x.a[1]] : lo;
+  if (lo === undefined || hi === undefined) return false;
+  return __within(lo, x.get('Day'), hi);
+}
+function timeRange() {
+  var x = __args(arguments), a = x.a;
+  var now = x.get('Hours') * 3600 + x.get('Minutes') * 60 + x.get('Seconds');
+  switch (a.length) {
+    case 1: return x.get('Hours') == a[0];
+    case 2: return __within(a[0] * 3600, now, a[1] * 3600 + 3599);
+    case 4: return __within(a[0] * 3600 + a[1] * 60, now, a[2] * 3600 + a[3] * 60 + 59);
+    case 6: return __within(a[0] * 3600 + a[1] * 60 + a[2], now, a[3] * 3600 + a[4] * 60 + a[5]);
+    default: return false;
+  }
+}
+function dateRange() {
+  var x = __args(arguments), a = x.a;
+  if (a.length == 0 || a.length > 6 || (a.length > 1 && a.length % 2)) return false;
+  // a bound is some of day, month and year; compare them as yyyymmdd
+  var key = function (parts) {
+    var k = { y: 0, m: 0, d: 0 };
+    parts.forEach(function (p) {
+      if (typeof p == 'string') k.m = __months[p];
+      else if (p > 31) k.y = p;
+      else k.d = p;
+    });
+    return k;
+  };
+  var fields = function (parts) {
+    return parts.map(function (p) { return typeof p == 'string' ? 'm' : p > 31 ? 'y' : 'd'; });
+  };
+  var half = a.length == 1 ? 1 : a.length / 2;
+  var lo = key(a.slice(0, half)), hi = a.length == 1 ? lo : key(a.slice(half));
+  var used = fields(a.slice(0, half));
+  var cur = { y: x.get('FullYear'), m: x.get('Month'), d: x.get('Date') };
+  var n = function (k) {
+    return (used.indexOf('y') >= 0 ? k.y : 0) * 10000 +
+      (used.indexOf('m') >= 0 ? k.m : 0) * 100 + (used.indexOf('d') >= 0 ? k.d : 0);
+  };
+  return used.indexOf('y') >= 0 ? n(lo) <= n(cur) && n(cur) <= n(hi) : __within(n(lo), n(cur), n(hi));
+}
+"#;
+
+/// A proxy auto-config script. boa contexts cannot leave their thread, so the
+/// script is evaluated once on a thread of its own that answers lookups.
+#[derive(Debug, Clone)]
+pub struct Pac {
+    lookups: mpsc::Sender<(Url, mpsc::Sender<Result<String>>)>,
+}
+
+/// One entry of a `FindProxyForURL` result.
+#[derive(Debug, PartialEq)]
+pub enum Choice {
+    Direct,
+    Proxy(Url),
+}
+
+impl Pac {
+    /// Fetch the script when `src` is an http(s) URL, otherwise read it from
+    /// the file.
+    pub async fn load(src: &str) -> Result<Self> {
+        let script = if src.starts_with("http://") || src.starts_with("https://") {
+            let resp = reqwest::Client::builder()
+                .no_proxy()
+                .build()?
+                .get(src)
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(anyhow!(format!(
+                    "Failed to fetch {}: {}",
+                    src,
+                    resp.status()
+                )));
+            }
+            resp.text().await?
+        } else {
+            std::fs::read_to_string(src).with_context(|| format!("Failed to read {}", src))?
+        };
+        Self::new(&script)
+    }
+
+    /// Start evaluating `script`, failing on syntax errors now rather than on
+    /// the first request.
+    pub fn new(script: &str) -> Result<Self> {
+        let script = script.to_string();
+        let (lookups, rx) = mpsc::channel::<(Url, mpsc::Sender<Result<String>>)>();
+        let (ready_tx, ready) = mpsc::channel();
+        thread::Builder::new().name("pac".into()).spawn(move || {
+            let mut ctx = match context(&script) {
+                Ok(ctx) => {
+                    ready_tx.send(Ok(())).ok();
+                    ctx
+                }
+                Err(e) => {
+                    ready_tx.send(Err(e)).ok();
+                    return;
+                }
+            };
+            for (url, reply) in rx {
+                reply.send(call(&mut ctx, &url)).ok();
+            }
+        })?;
+        ready
+            .recv()
+            .map_err(|_| anyhow!("Failed to evaluate PAC script"))??;
+        Ok(Self { lookups })
+    }
+
+    /// The raw `FindProxyForURL` result for `url`, e.g. `PROXY p:8080; DIRECT`.
+    pub fn find_proxy(&self, url: &Url) -> Result<String> {
+        let (reply, result) = mpsc::channel();
+        self.lookups
+            .send((url.clone(), reply))
+            .map_err(|_| anyhow!("Failed to run FindProxyForURL: the PAC thread stopped"))?;
+        result
+            .recv()
+            .map_err(|_| anyhow!("Failed to run FindProxyForURL: the PAC thread stopped"))?
+    }
+
+    /// The proxy to use for `url`, `None` to connect directly. Only the first
+    /// usable entry is honored since there is no failover between proxies.
+    /// reqwest picks proxies per connection, so scripts only ever see the
+    /// scheme, host and port of the URL.
+    pub fn proxy_for(&self, url: &Url) -> Option<Url> {
+        match self.find_proxy(url) {
+            Ok(result) => match parse_result(&result).into_iter().next() {
+                Some(Choice::Proxy(proxy)) => Some(proxy),
+                _ => None,
+            },
+            Err(e) => {
+                eprintln!("{}; connecting directly", e);
+                None
+            }
+        }
+    }
+}
+
+/// A context with the PAC helpers and `script` evaluated.
+fn context(script: &str) -> Result<Context> {
+    let mut ctx = Context::default();
+    let js_err = |e: boa_engine::JsError| anyhow!(format!("Failed to evaluate PAC script: {}", e));
+    ctx.register_global_callable(
+        js_string!("dnsResolve"),
+        1,
+        NativeFunction::from_fn_ptr(dns_resolve),
+    )
+    .map_err(js_err)?;
+    ctx.register_global_callable(
+        js_string!("myIpAddress"),
+        0,
+        NativeFunction::from_fn_ptr(my_ip_address),
+    )
+    .map_err(js_err)?;
+    ctx.eval(Source::from_bytes(PRELUDE)).map_err(js_err)?;
+    ctx.eval(Source::from_bytes(script.as_bytes()))
+        .map_err(js_err)?;
+    Ok(ctx)
+}
+
+/// Call `FindProxyForURL` for `url` in a context made by [`context`].
+fn call(ctx: &mut Context, url: &Url) -> Result<String> {
+    // JSON string literals are valid JavaScript string literals
+    let call = format!(
+        "FindProxyForURL({}, {})",
+        serde_json::to_string(url.as_str())?,
+        serde_json::to_string(url.host_str().unwrap_or(""))?
+    );
+    let result = ctx
+        .eval(Source::from_bytes(call.as_bytes()))
+        .map_err(|e| anyhow!(format!("Failed to run FindProxyForURL: {}", e)))?;
+    Ok(result
+        .to_string(ctx)
+        .map_err(|e| anyhow!(format!("Failed to run FindProxyForURL: {}", e)))?
+        .to_std_string_escaped())
+}
+
+/// Entries of a `FindProxyForURL` result, skipping ones we cannot use.
+pub fn parse_result(s: &str) -> Vec<Choice> {
+    s.split(';')
+        .filter_map(|entry| {
+            let mut words = entry.split_whitespace();
+            let kind = words.next()?.to_ascii_uppercase();
+            let scheme = match kind.as_str() {
+                "DIRECT" => return Some(Choice::Direct),
+                "PROXY" | "HTTP" => "http",
+                "HTTPS" => "https",
+                "SOCKS" | "SOCKS5" => "socks5",
+                _ => return None,
+            };
+            let url = format!("{}://{}", scheme, words.next()?).parse().ok()?;
+            Some(Choice::Proxy(url))
+        })
+        .collect()
+}
+
+fn dns_resolve(_: &JsValue, args: &[JsValue], ctx: &mut Context) -> JsResult<JsValue> {
+    let host = match args.first() {
+        Some(v) => v.to_string(ctx)?.to_std_string_escaped(),
+        None => return Ok(JsValue::null()),
+    };
+    let ip = (host.as_str(), 0)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.find(|a| a.is_ipv4()));
+    Ok(match ip {
+        Some(addr) => js_string!(addr.ip().to_string()).into(),
+        None => JsValue::null(),
+    })
+}
+
+fn my_ip_address(_: &JsValue, _: &[JsValue], _: &mut Context) -> JsResult<JsValue> {
+    // connecting a UDP socket sends nothing but picks the outgoing interface
+    let ip = UdpSocket::bind("0.0.0.0:0")
+        .and_then(|s| s.connect("8.8.8.8:80").map(|_| s))
+        .and_then(|s| s.local_addr())
+        .map(|a| a.ip())
+        .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+    Ok(js_string!(ip.to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_result_works() {
+        assert_eq!(
+            parse_result("PROXY p.corp:8080; SOCKS s.corp:1080;DIRECT; BOGUS x"),
+            vec![
+                Choice::Proxy("http://p.corp:8080".parse().unwrap()),
+                Choice::Proxy("socks5://s.corp:1080".parse().unwrap()),
+                Choice::Direct,
+            ]
+        );
+        assert_eq!(parse_result(""), vec![]);
+    }
+
+    #[test]
+    fn find_proxy_works() {
+        let pac = Pac::new(
+            r#"
+            function FindProxyForURL(url, host) {
+              if (isPlainHostName(host) || dnsDomainIs(host, ".intranet")) return "DIRECT";
+              if (isInNet(host, "10.0.0.0", "255.0.0.0")) return "PROXY inner:3128";
+              if (shExpMatch(url, "https://*.example.com/*")) return "HTTPS secure:443";
+              return "PROXY outer:8080; DIRECT";
+            }
+            "#,
+        )
+        .unwrap();
+        let find = |u: &str| pac.find_proxy(&u.parse().unwrap()).unwrap();
+        assert_eq!(find("http://wiki/"), "DIRECT");
+        assert_eq!(find("http://hr.intranet/x"), "DIRECT");
+        assert_eq!(find("http://10.1.2.3/"), "PROXY inner:3128");
+        assert_eq!(find("https://api.example.com/v1"), "HTTPS secure:443");
+        assert_eq!(find("https://example.org/"), "PROXY outer:8080; DIRECT");
+        assert_eq!(
+            pac.proxy_for(&"https://example.org/".parse().unwrap()),
+            Some("http://outer:8080".parse().unwrap())
+        );
+        assert_eq!(pac.proxy_for(&"http://wiki/".parse().unwrap()), None);
+
+        // the script is evaluated once and its state kept between lookups
+        let pac = Pac::new(
+            "var n = 0; function FindProxyForURL(url, host) { n++; return 'PROXY p:' + n; }",
+        )
+        .unwrap();
+        let url = "http://a/".parse().unwrap();
+        assert_eq!(pac.find_proxy(&url).unwrap(), "PROXY p:1");
+        assert_eq!(pac.clone().find_proxy(&url).unwrap(), "PROXY p:2");
+    }
+
+    #[test]
+    fn invalid_script_fails() {
+        assert!(Pac::new("function FindProxyForURL(").is_err());
+    }
+}
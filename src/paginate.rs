@@ -0,0 +1,92 @@
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::jsonpath;
+
+/// The `rel="next"` target of a Link header (RFC 8288), resolved against
+/// the URL of the page it came with, as GitHub and many others paginate.
+pub fn next_link(link: &str, base: &Url) -> Option<Url> {
+    let mut rest = link;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>')?;
+        let target = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        // parameters run up to the next link
+        let params = &rest[..rest.find('<').unwrap_or(rest.len())];
+        let next = params.split(';').any(|p| {
+            p.split_once('=').is_some_and(|(k, v)| {
+                k.trim().eq_ignore_ascii_case("rel")
+                    && v.trim()
+                        .trim_end_matches(',')
+                        .trim()
+                        .trim_matches('"')
+                        .split_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("next"))
+            })
+        });
+        if next {
+            return base.join(target).ok();
+        }
+    }
+    None
+}
+
+/// The next page named by the field at `path` of a JSON body, for APIs that
+/// put it there instead; null or absent when on the last page.
+pub fn next_field(body: &[u8], path: &jsonpath::Path, base: &Url) -> Option<Url> {
+    let json: Value = serde_json::from_slice(body).ok()?;
+    let next = path.select(&json).into_iter().next()?.as_str()?;
+    Some(next)
+        .filter(|n| !n.is_empty())
+        .and_then(|n| base.join(n).ok())
+}
+
+/// The items of every page in one array, when each page is a JSON array.
+pub fn concat(pages: &[impl AsRef<[u8]>]) -> Option<Vec<Value>> {
+    let mut items = Vec::new();
+    for page in pages {
+        match serde_json::from_slice(page.as_ref()).ok()? {
+            Value::Array(page) => items.extend(page),
+            _ => return None,
+        }
+    }
+    Some(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_works() {
+        let base: Url = "https://api.github.com/repos/a/b/issues?page=1"
+            .parse()
+            .unwrap();
+        let link = r#"<https://api.github.com/repositories/1/issues?page=2&per_page=2,3>; rel="next", <https://api.github.com/repositories/1/issues?page=9>; rel="last""#;
+        assert_eq!(
+            next_link(link, &base).unwrap().as_str(),
+            "https://api.github.com/repositories/1/issues?page=2&per_page=2,3"
+        );
+        assert_eq!(
+            next_link(r#"</issues?page=3>; rel="prev next""#, &base)
+                .unwrap()
+                .as_str(),
+            "https://api.github.com/issues?page=3"
+        );
+        assert!(next_link(r#"<https://x/?page=1>; rel="prev""#, &base).is_none());
+
+        let path: jsonpath::Path = "$.links.next".parse().unwrap();
+        let body = br#"{"links": {"next": "?page=2"}, "data": []}"#;
+        assert_eq!(
+            next_field(body, &path, &base).unwrap().as_str(),
+            "https://api.github.com/repos/a/b/issues?page=2"
+        );
+        assert!(next_field(br#"{"links": {"next": null}}"#, &path, &base).is_none());
+
+        assert_eq!(
+            concat(&[b"[1,2]".to_vec(), b"[3]".to_vec()]).unwrap(),
+            [1, 2, 3]
+        );
+        assert!(concat(&[b"[1]".to_vec(), b"{}".to_vec()]).is_none());
+    }
+}
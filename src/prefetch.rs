@@ -0,0 +1,123 @@
+use std::{collections::HashMap, net::SocketAddr, time::Instant};
+
+use colored::Colorize;
+use futures_util::StreamExt;
+use reqwest::Url;
+
+use crate::stats::format_duration;
+
+/// Lookups in flight at once; each takes a blocking thread.
+const CONCURRENCY: usize = 64;
+
+/// What [`resolve`] found: addresses for the client and errors by host.
+#[derive(Debug, Default)]
+pub struct Answers {
+    pub found: Vec<(String, Vec<SocketAddr>)>,
+    pub failed: HashMap<String, String>,
+}
+
+/// Whether requests go through a proxy from the environment, which then
+/// resolves the names itself.
+pub fn proxied() -> bool {
+    ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"]
+        .iter()
+        .any(|v| std::env::var_os(v).is_some() || std::env::var_os(v.to_lowercase()).is_some())
+}
+
+/// The host names of `urls` worth looking up, once each and in order:
+/// not IP addresses, nor hosts given with `--resolve`.
+pub fn hosts<'a>(urls: impl IntoIterator<Item = &'a str>, skip: &[String]) -> Vec<String> {
+    let mut hosts: Vec<String> = Vec::new();
+    for url in urls {
+        let Some(host) = host_of(url) else { continue };
+        if host.parse::<std::net::IpAddr>().is_err()
+            && !host.starts_with('[')
+            && !skip.contains(&host)
+            && !hosts.contains(&host)
+        {
+            hosts.push(host);
+        }
+    }
+    hosts
+}
+
+/// The host of `url`, lower case as the client compares them.
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(str::to_ascii_lowercase)
+}
+
+/// Look up every host at once, so a batch over many domains waits on DNS
+/// about as long as its slowest name rather than all of them in turn.
+pub async fn resolve(hosts: Vec<String>) -> Answers {
+    let started = Instant::now();
+    let total = hosts.len();
+    let mut lookups = futures_util::stream::iter(hosts)
+        .map(|host| async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map(|a| a.collect::<Vec<_>>());
+            (host, addrs)
+        })
+        .buffer_unordered(CONCURRENCY);
+    let mut answers = Answers::default();
+    while let Some((host, addrs)) = lookups.next().await {
+        match addrs {
+            Ok(addrs) if !addrs.is_empty() => answers.found.push((host, addrs)),
+            Ok(_) => {
+                answers.failed.insert(host, "no addresses".into());
+            }
+            Err(e) => {
+                answers.failed.insert(host, e.to_string());
+            }
+        }
+    }
+    if !answers.failed.is_empty() {
+        let mut failed: Vec<_> = answers.failed.iter().collect();
+        failed.sort();
+        for (host, e) in failed {
+            eprintln!("{} {}: {}", "✗".red().bold(), host, e);
+        }
+        eprintln!(
+            "{}",
+            format!(
+                "Failed to resolve {} of {} hosts in {}, their URLs are skipped",
+                answers.failed.len(),
+                total,
+                format_duration(started.elapsed())
+            )
+            .yellow()
+        );
+    }
+    answers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hosts_works() {
+        let urls = [
+            "https://Example.com/a",
+            "https://example.com:8443/b",
+            "http://127.0.0.1:8080/",
+            "http://[::1]/",
+            "http://pinned.test/",
+            "https://docs.rs/",
+        ];
+        assert_eq!(
+            hosts(urls, &["pinned.test".into()]),
+            ["example.com", "docs.rs"]
+        );
+    }
+
+    #[tokio::test]
+    async fn resolve_works() {
+        let answers = resolve(vec!["localhost".into(), "nonexistent.invalid".into()]).await;
+        assert_eq!(answers.found[0].0, "localhost");
+        assert!(answers.failed.contains_key("nonexistent.invalid"));
+    }
+}
@@ -0,0 +1,144 @@
+use colored::Colorize;
+use mime::Mime;
+use reqwest::StatusCode;
+use serde_json::{Map, Value};
+
+/// An RFC 7807 problem details object, as APIs describe their errors.
+#[derive(Debug, Default, PartialEq)]
+pub struct Problem {
+    pub kind: Option<String>,
+    pub title: Option<String>,
+    pub status: Option<u16>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    /// Members beyond the standard ones, by name
+    pub extensions: Map<String, Value>,
+}
+
+/// `application/problem+json`, or a vendor type ending in `+problem+json`.
+pub fn is_problem(m: &Mime) -> bool {
+    let subtype = m.subtype().as_str();
+    m.suffix() == Some(mime::JSON) && (subtype == "problem" || subtype.ends_with("+problem"))
+}
+
+/// The problem in `bytes`, when they hold a JSON object; standard members
+/// of the wrong type are kept as extensions.
+pub fn parse(bytes: &[u8]) -> Option<Problem> {
+    let Value::Object(members) = serde_json::from_slice(bytes).ok()? else {
+        return None;
+    };
+    let mut problem = Problem::default();
+    for (name, value) in members {
+        match (name.as_str(), value) {
+            ("type", Value::String(s)) => problem.kind = Some(s),
+            ("title", Value::String(s)) => problem.title = Some(s),
+            ("detail", Value::String(s)) => problem.detail = Some(s),
+            ("instance", Value::String(s)) => problem.instance = Some(s),
+            ("status", Value::Number(n)) if n.as_u64().is_some_and(|n| n < 1000) => {
+                problem.status = n.as_u64().map(|n| n as u16)
+            }
+            (_, value) => {
+                problem.extensions.insert(name, value);
+            }
+        }
+    }
+    Some(problem)
+}
+
+/// `problem` as a card: status and title, the detail, then every other
+/// member. `status` is that of the response, shown too when the problem
+/// says otherwise.
+pub fn render(problem: &Problem, status: StatusCode) -> String {
+    let bar = "│".red();
+    let code = problem
+        .status
+        .and_then(|s| StatusCode::from_u16(s).ok())
+        .unwrap_or(status);
+    let mut head = code.as_u16().to_string();
+    if let Some(reason) = code.canonical_reason() {
+        head = format!("{} {}", head, reason);
+    }
+    if let Some(title) = &problem.title {
+        head = format!("{} · {}", head, title);
+    }
+    let mut out = format!("{} {}\n", "╭".red(), head.red().bold());
+    if code != status {
+        out += &format!(
+            "{} {}\n",
+            bar,
+            format!("(sent as HTTP {})", status.as_u16()).dimmed()
+        );
+    }
+    if let Some(detail) = &problem.detail {
+        for line in detail.lines() {
+            out += &format!("{} {}\n", bar, line);
+        }
+    }
+    // about:blank is the default, which says no more than the status
+    let kind = problem.kind.as_deref().filter(|k| *k != "about:blank");
+    let mut rows: Vec<(&str, String)> = Vec::new();
+    rows.extend(kind.map(|k| ("type", k.to_string())));
+    rows.extend(problem.instance.as_ref().map(|i| ("instance", i.clone())));
+    for (name, value) in problem.extensions.iter() {
+        let value = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        rows.push((name, value));
+    }
+    if !rows.is_empty() {
+        if problem.detail.is_some() {
+            out += &format!("{}\n", bar);
+        }
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in rows {
+            let name = format!("{:<width$}", name, width = width);
+            out += &format!("{} {}  {}\n", bar, name.cyan(), value);
+        }
+    }
+    out + &format!("{}\n", "╰".red())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problem_works() {
+        assert!(is_problem(&"application/problem+json".parse().unwrap()));
+        assert!(is_problem(
+            &"application/vnd.acme+problem+json; charset=utf-8"
+                .parse()
+                .unwrap()
+        ));
+        assert!(!is_problem(&"application/json".parse().unwrap()));
+        assert!(parse(b"[1]").is_none());
+
+        let problem = parse(
+            br#"{
+                "type": "https://example.com/probs/out-of-credit",
+                "title": "You do not have enough credit.",
+                "status": 403,
+                "detail": "Your current balance is 30, but that costs 50.",
+                "instance": "/account/12345/msgs/abc",
+                "balance": 30,
+                "accounts": ["/account/12345", "/account/67890"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(problem.status, Some(403));
+        assert_eq!(problem.extensions["balance"], 30);
+        let card = render(&problem, StatusCode::BAD_REQUEST);
+        assert!(card.contains("403 Forbidden · You do not have enough credit."));
+        assert!(card.contains("(sent as HTTP 400)"));
+        assert!(card.contains("Your current balance is 30, but that costs 50."));
+        assert!(card.contains("https://example.com/probs/out-of-credit"));
+        assert!(card.contains(r#"["/account/12345","/account/67890"]"#));
+
+        let blank = parse(br#"{"type": "about:blank", "status": "404"}"#).unwrap();
+        assert_eq!(blank.extensions["status"], "404");
+        let card = render(&blank, StatusCode::NOT_FOUND);
+        assert!(card.contains("404 Not Found"));
+        assert!(!card.contains("about:blank"));
+    }
+}
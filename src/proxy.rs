@@ -0,0 +1,415 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use hyper::{
+    header::{self, HeaderMap},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use mime::Mime;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair, KeyUsagePurpose};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    rustls::{
+        self,
+        pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer},
+    },
+    TlsAcceptor,
+};
+
+use crate::{body, secrets};
+
+/// Headers that describe a single connection and must not be forwarded.
+const HOP_BY_HOP: [&str; 9] = [
+    "connection",
+    "proxy-connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Certificate authority used to mint a certificate for each intercepted host.
+pub struct Ca {
+    issuer: Issuer<'static, KeyPair>,
+    configs: Mutex<HashMap<String, Arc<rustls::ServerConfig>>>,
+}
+
+fn ca_params() -> CertificateParams {
+    let mut params = CertificateParams::default();
+    params.distinguished_name = rcgen::DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "Httpie debug proxy CA");
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    params
+}
+
+impl Ca {
+    /// Load the CA from `dir`, generating `ca.pem` and `ca-key.pem` there on
+    /// first use. Clients must trust `ca.pem` to accept intercepted traffic.
+    pub fn load_or_create(dir: &Path) -> Result<Self> {
+        let cert_path = dir.join("ca.pem");
+        let key_path = dir.join("ca-key.pem");
+        let (key, new) = match std::fs::read_to_string(&key_path) {
+            Ok(pem) => (
+                KeyPair::from_pem(&pem).map_err(|e| {
+                    anyhow!(format!("Failed to load {}: {}", key_path.display(), e))
+                })?,
+                false,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = KeyPair::generate()?;
+                std::fs::create_dir_all(dir)
+                    .with_context(|| format!("Failed to create {}", dir.display()))?;
+                secrets::write_private(&key_path, &key.serialize_pem())
+                    .with_context(|| format!("Failed to write {}", key_path.display()))?;
+                (key, true)
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", key_path.display()))
+            }
+        };
+        // a certificate left from another key would not match it
+        if new || !cert_path.exists() {
+            let cert = ca_params().self_signed(&key)?;
+            std::fs::write(&cert_path, cert.pem())
+                .with_context(|| format!("Failed to write {}", cert_path.display()))?;
+        }
+        Ok(Self {
+            // leaf certificates only need the issuer's name and key, which
+            // match the stored certificate
+            issuer: Issuer::new(ca_params(), key),
+            configs: Mutex::default(),
+        })
+    }
+
+    /// TLS configuration presenting a certificate for `host`.
+    pub fn server_config(&self, host: &str) -> Result<Arc<rustls::ServerConfig>> {
+        if let Some(config) = self.configs.lock().unwrap().get(host) {
+            return Ok(config.clone());
+        }
+        let key = KeyPair::generate()?;
+        let mut params = CertificateParams::new(vec![host.to_string()])?;
+        params.distinguished_name.push(DnType::CommonName, host);
+        let cert = params.signed_by(&key, &self.issuer)?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert.der().clone()],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.serialize_der())),
+            )?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let config = Arc::new(config);
+        self.configs
+            .lock()
+            .unwrap()
+            .insert(host.to_string(), config.clone());
+        Ok(config)
+    }
+}
+
+/// `~/.httpie/ca`, where the CA lives unless `--ca-dir` says otherwise.
+pub fn default_ca_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".httpie")
+        .join("ca")
+}
+
+struct State {
+    client: reqwest::Client,
+    ca: Option<Ca>,
+    seq: AtomicUsize,
+    /// keeps the logs of concurrent exchanges from interleaving
+    print: Mutex<()>,
+}
+
+/// Run a forwarding proxy on `addr`, printing every exchange. CONNECT
+/// tunnels are decrypted when a CA is given and passed through otherwise.
+pub async fn serve(addr: SocketAddr, ca: Option<Ca>, insecure: bool) -> Result<()> {
+    let client = reqwest::Client::builder()
+        .no_proxy()
+        .danger_accept_invalid_certs(insecure)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let intercept = ca.is_some();
+    let state = Arc::new(State {
+        client,
+        ca,
+        seq: AtomicUsize::new(1),
+        print: Mutex::new(()),
+    });
+    let make = make_service_fn(move |_| {
+        let state = state.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(state.clone(), req, None))) }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|e| anyhow!(format!("Failed to listen on {}: {}", addr, e)))?
+        .serve(make);
+    println!(
+        "{} http://{}{}",
+        "Proxy listening on".bold(),
+        addr,
+        if intercept { " (intercepting TLS)" } else { "" }
+    );
+    Ok(server.await?)
+}
+
+/// Handle a request to the proxy, or one inside an intercepted tunnel to
+/// `tunnel` (the CONNECT authority).
+async fn handle(
+    state: Arc<State>,
+    req: Request<Body>,
+    tunnel: Option<Arc<str>>,
+) -> Result<Response<Body>, Infallible> {
+    if req.method() == Method::CONNECT {
+        return Ok(connect(state, req));
+    }
+    let url = match &tunnel {
+        Some(authority) => format!(
+            "https://{}{}",
+            authority,
+            req.uri()
+                .path_and_query()
+                .map(|p| p.as_str())
+                .unwrap_or("/")
+        ),
+        None if req.uri().scheme().is_some() => req.uri().to_string(),
+        None => {
+            return Ok(error(
+                StatusCode::BAD_REQUEST,
+                "This is a proxy, requests need an absolute URL",
+            ))
+        }
+    };
+    Ok(forward(&state, req, url)
+        .await
+        .unwrap_or_else(|e| error(StatusCode::BAD_GATEWAY, &e.to_string())))
+}
+
+fn error(status: StatusCode, msg: &str) -> Response<Body> {
+    eprintln!("{} {}", status.to_string().red(), msg);
+    let mut resp = Response::new(Body::from(format!("{}\n", msg)));
+    *resp.status_mut() = status;
+    resp
+}
+
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    for name in HOP_BY_HOP {
+        headers.remove(name);
+    }
+}
+
+async fn forward(state: &State, req: Request<Body>, url: String) -> Result<Response<Body>> {
+    let seq = state.seq.fetch_add(1, Ordering::Relaxed);
+    let (parts, body) = req.into_parts();
+    let req_body = hyper::body::to_bytes(body).await?;
+    let mut headers = parts.headers;
+    strip_hop_by_hop(&mut headers);
+    // reqwest derives these from the URL and body
+    headers.remove(header::HOST);
+    headers.remove(header::CONTENT_LENGTH);
+
+    let start = Instant::now();
+    let upstream = state
+        .client
+        .request(parts.method.clone(), &url)
+        .headers(headers.clone())
+        .body(req_body.clone())
+        .send()
+        .await?;
+    let status = upstream.status();
+    let version = upstream.version();
+    let mut resp_headers = upstream.headers().clone();
+    let resp_body = upstream.bytes().await?;
+    let elapsed = start.elapsed();
+
+    {
+        let _guard = state.print.lock().unwrap();
+        println!(
+            "{} {} {}",
+            format!("#{}", seq).bold(),
+            parts.method.to_string().cyan().bold(),
+            url
+        );
+        print_message(&headers, &req_body);
+        println!(
+            "{} {:?} {} {}",
+            format!("#{}", seq).bold(),
+            version,
+            status.to_string().blue(),
+            format_elapsed(elapsed).dimmed()
+        );
+        print_message(&resp_headers, &resp_body);
+    }
+
+    strip_hop_by_hop(&mut resp_headers);
+    let mut resp = Response::new(Body::from(resp_body));
+    *resp.status_mut() = status;
+    *resp.headers_mut() = resp_headers;
+    Ok(resp)
+}
+
+pub fn format_elapsed(d: Duration) -> String {
+    format!("{}ms", d.as_millis())
+}
+
+pub fn print_message(headers: &HeaderMap, bytes: &[u8]) {
+    crate::print_header_table(headers, false);
+    println!();
+    if bytes.is_empty() {
+        return;
+    }
+    let mime: Option<Mime> = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok()?.parse().ok());
+    if mime.as_ref().is_some_and(is_binary) {
+        println!(
+            "{}\n",
+            format!("[{} bytes of binary data]", bytes.len()).dimmed()
+        );
+        return;
+    }
+    let gzipped = headers
+        .get(header::CONTENT_ENCODING)
+        .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"gzip"));
+    let decoded = if gzipped {
+        body::gunzip(bytes).unwrap_or_else(|_| bytes.to_vec())
+    } else {
+        bytes.to_vec()
+    };
+    let text = body::decode_text(&decoded, mime.as_ref());
+    crate::print_body(mime, &text);
+    println!();
+}
+
+fn is_binary(m: &Mime) -> bool {
+    matches!(m.type_().as_str(), "image" | "audio" | "video" | "font")
+        || matches!(
+            m.subtype().as_str(),
+            "octet-stream" | "zip" | "pdf" | "gzip"
+        )
+}
+
+/// Answer a CONNECT and take over the connection once it is upgraded.
+fn connect(state: Arc<State>, req: Request<Body>) -> Response<Body> {
+    let Some(authority) = req.uri().authority().map(|a| a.to_string()) else {
+        return error(StatusCode::BAD_REQUEST, "CONNECT needs host:port");
+    };
+    tokio::spawn(async move {
+        let result = match hyper::upgrade::on(req).await {
+            Ok(upgraded) => match &state.ca {
+                Some(ca) => intercept(state.clone(), ca, upgraded, authority.clone()).await,
+                None => tunnel(upgraded, &authority).await,
+            },
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            eprintln!("{} {}: {}", "CONNECT".red(), authority, e);
+        }
+    });
+    Response::new(Body::empty())
+}
+
+async fn tunnel(mut upgraded: hyper::upgrade::Upgraded, authority: &str) -> Result<()> {
+    let mut upstream = TcpStream::connect(authority).await?;
+    let (up, down) = tokio::io::copy_bidirectional(&mut upgraded, &mut upstream).await?;
+    println!(
+        "{} {} {}",
+        "CONNECT".cyan().bold(),
+        authority,
+        format!("(tunneled, {} bytes up, {} down)", up, down).dimmed()
+    );
+    Ok(())
+}
+
+async fn intercept(
+    state: Arc<State>,
+    ca: &Ca,
+    upgraded: hyper::upgrade::Upgraded,
+    authority: String,
+) -> Result<()> {
+    let host = authority
+        .rsplit_once(':')
+        .map(|(h, _)| h)
+        .unwrap_or(&authority)
+        .trim_matches(['[', ']']);
+    let acceptor = TlsAcceptor::from(ca.server_config(host)?);
+    let tls = acceptor.accept(upgraded).await?;
+    let authority: Arc<str> = authority.into();
+    let service = service_fn(move |req| handle(state.clone(), req, Some(authority.clone())));
+    hyper::server::conn::Http::new()
+        .http1_only(true)
+        .serve_connection(tls, service)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_hop_by_hop_works() {
+        let mut headers = HeaderMap::new();
+        headers.insert("connection", "keep-alive".parse().unwrap());
+        headers.insert("proxy-authorization", "Basic eA==".parse().unwrap());
+        headers.insert("accept", "*/*".parse().unwrap());
+        strip_hop_by_hop(&mut headers);
+        assert_eq!(headers.len(), 1);
+        assert!(headers.contains_key("accept"));
+    }
+
+    #[test]
+    fn ca_works() {
+        let dir = std::env::temp_dir().join(format!("httpie-ca-test-{}", std::process::id()));
+        let ca = Ca::load_or_create(&dir).unwrap();
+        assert!(dir.join("ca.pem").exists());
+        let config = ca.server_config("example.com").unwrap();
+        assert!(Arc::ptr_eq(
+            &config,
+            &ca.server_config("example.com").unwrap()
+        ));
+        // reloading keeps the key the clients already trust
+        let key = std::fs::read_to_string(dir.join("ca-key.pem")).unwrap();
+        Ca::load_or_create(&dir).unwrap();
+        assert_eq!(
+            key,
+            std::fs::read_to_string(dir.join("ca-key.pem")).unwrap()
+        );
+        let mode = |name| {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::metadata(dir.join(name))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777
+        };
+        assert_eq!(mode("ca-key.pem"), 0o600);
+        // a new key comes with a new certificate
+        let cert = std::fs::read_to_string(dir.join("ca.pem")).unwrap();
+        std::fs::remove_file(dir.join("ca-key.pem")).unwrap();
+        Ca::load_or_create(&dir).unwrap();
+        assert_ne!(cert, std::fs::read_to_string(dir.join("ca.pem")).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use reqwest::Url;
+use serde_json::Value;
+
+use crate::template;
+
+/// How a JSON array value is written into the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ArrayFormat {
+    /// `ids=1&ids=2&ids=3`
+    #[default]
+    Repeat,
+    /// `ids=1,2,3`
+    Comma,
+    /// `ids[]=1&ids[]=2&ids[]=3`
+    Brackets,
+}
+
+/// A query parameter given on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryItem {
+    /// `key==value`, sent as is
+    Text(String, String),
+    /// `key:=json`, where an array expands to several values
+    Json(String, Value),
+}
+
+/// Whether `s` reads as a query item rather than a URL: a plain key before
+/// the first `==` or `:=`.
+pub fn is_item(s: &str) -> bool {
+    let end = [s.find("=="), s.find(":=")].into_iter().flatten().min();
+    end.is_some_and(|i| {
+        i > 0
+            && s[..i]
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-.[]".contains(c))
+    })
+}
+
+/// Parse `key==value` or `key:=json`, resolving `{{VAR}}` placeholders.
+pub fn parse_item(s: &str) -> Result<QueryItem> {
+    let s = template::render(s)?;
+    // the earliest separator wins, so `a==b:=c` is text
+    let text = s.find("==");
+    let json = s.find(":=");
+    let item = match (text, json) {
+        (Some(i), j) if j.is_none_or(|j| i < j) => {
+            QueryItem::Text(s[..i].to_string(), s[i + 2..].to_string())
+        }
+        (_, Some(j)) => {
+            let value = serde_json::from_str(&s[j + 2..]).map_err(|e| {
+                anyhow!(format!("Failed to parse {}: invalid JSON value: {}", s, e))
+            })?;
+            QueryItem::Json(s[..j].to_string(), value)
+        }
+        _ => {
+            return Err(anyhow!(format!(
+                "Failed to parse {}: expected `key==value` or `key:=[1,2]`",
+                s
+            )))
+        }
+    };
+    let (QueryItem::Text(k, _) | QueryItem::Json(k, _)) = &item;
+    if k.is_empty() {
+        return Err(anyhow!(format!("Failed to parse {}: missing key", s)));
+    }
+    Ok(item)
+}
+
+/// A JSON value as query text: strings unquoted, null empty, anything nested
+/// as compact JSON.
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// The `(key, value)` pairs for `items`, arrays written as `format` says.
+pub fn pairs(items: &[QueryItem], format: ArrayFormat) -> Result<Vec<(String, String)>> {
+    let mut pairs = Vec::new();
+    for item in items {
+        match item {
+            QueryItem::Text(k, v) => pairs.push((k.clone(), v.clone())),
+            QueryItem::Json(k, Value::Object(_)) => {
+                return Err(anyhow!(format!(
+                    "Failed to add query parameter {}: objects cannot be sent in a query string",
+                    k
+                )))
+            }
+            QueryItem::Json(k, Value::Array(values)) => match format {
+                ArrayFormat::Repeat => {
+                    pairs.extend(values.iter().map(|v| (k.clone(), scalar(v))));
+                }
+                ArrayFormat::Comma => {
+                    let joined: Vec<String> = values.iter().map(scalar).collect();
+                    pairs.push((k.clone(), joined.join(",")));
+                }
+                ArrayFormat::Brackets => {
+                    let key = format!("{}[]", k);
+                    pairs.extend(values.iter().map(|v| (key.clone(), scalar(v))));
+                }
+            },
+            QueryItem::Json(k, v) => pairs.push((k.clone(), scalar(v))),
+        }
+    }
+    Ok(pairs)
+}
+
+/// `url` with the query items appended to whatever query it already has.
+pub fn append(url: &str, items: &[QueryItem], format: ArrayFormat) -> Result<String> {
+    if items.is_empty() {
+        return Ok(url.to_string());
+    }
+    let mut url: Url = url.parse()?;
+    url.query_pairs_mut().extend_pairs(pairs(items, format)?);
+    Ok(url.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_item_works() {
+        assert_eq!(
+            parse_item("q==a:=b").unwrap(),
+            QueryItem::Text("q".into(), "a:=b".into())
+        );
+        assert_eq!(
+            parse_item("ids:=[1,2]").unwrap(),
+            QueryItem::Json("ids".into(), serde_json::json!([1, 2]))
+        );
+        assert!(parse_item("ids:=[1,").is_err());
+        assert!(parse_item("==x").is_err());
+        assert!(parse_item("a=b").is_err());
+        assert!(is_item("ids[]:=[1]"));
+        assert!(!is_item("http://a.b/?x==y"));
+        assert!(!is_item(":3000/a"));
+    }
+
+    #[test]
+    fn append_works() {
+        let items = [
+            parse_item("ids:=[1,\"two\",true]").unwrap(),
+            parse_item("q==a b").unwrap(),
+        ];
+        let url = "http://localhost/s?page=2";
+        assert_eq!(
+            append(url, &items, ArrayFormat::Repeat).unwrap(),
+            "http://localhost/s?page=2&ids=1&ids=two&ids=true&q=a+b"
+        );
+        assert_eq!(
+            append(url, &items, ArrayFormat::Comma).unwrap(),
+            "http://localhost/s?page=2&ids=1%2Ctwo%2Ctrue&q=a+b"
+        );
+        assert_eq!(
+            append(url, &items, ArrayFormat::Brackets).unwrap(),
+            "http://localhost/s?page=2&ids%5B%5D=1&ids%5B%5D=two&ids%5B%5D=true&q=a+b"
+        );
+        assert_eq!(append(url, &[], ArrayFormat::Repeat).unwrap(), url);
+        let object = [parse_item("f:={\"a\":1}").unwrap()];
+        assert!(append(url, &object, ArrayFormat::Repeat).is_err());
+    }
+}
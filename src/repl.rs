@@ -0,0 +1,264 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Method,
+};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use crate::{curl::split_words, parse_templated_body_item, parse_url, query, send, Opts};
+
+const HELP: &str = "\
+get|post|put|patch|delete|head|options [path|url] [items...]
+    send a request; paths are relative to the base URL, items are
+    `key==value` or `key:=[..]` query parameters and `key=value`,
+    `key@file` or `@file` body items as for `post`
+set Name: value    send this header with every request
+unset Name         stop sending it
+headers            list the sticky headers
+base [url]         show or change the base URL
+help               this text
+exit               leave (Ctrl-D works too)";
+
+/// A line typed at the prompt.
+#[derive(Debug, PartialEq)]
+enum Command {
+    Request {
+        method: Method,
+        target: Option<String>,
+        items: Vec<String>,
+    },
+    Set(String, String),
+    Unset(String),
+    Headers,
+    Base(Option<String>),
+    Help,
+    Exit,
+}
+
+fn parse_line(line: &str) -> Result<Option<Command>> {
+    let line = line.trim();
+    let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let command = match word.to_ascii_lowercase().as_str() {
+        "" => return Ok(None),
+        "set" => {
+            let (name, value) = rest
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Failed to parse set: expected `set Name: value`"))?;
+            Command::Set(name.trim().to_string(), value.trim().to_string())
+        }
+        "unset" if !rest.is_empty() => Command::Unset(rest.to_string()),
+        "headers" => Command::Headers,
+        "base" => Command::Base((!rest.is_empty()).then(|| rest.to_string())),
+        "help" | "?" => Command::Help,
+        "exit" | "quit" => Command::Exit,
+        "get" | "post" | "put" | "patch" | "delete" | "head" | "options" => {
+            let mut words = split_words(rest)?.into_iter().peekable();
+            let target = words.next_if(|w| is_target(w));
+            Command::Request {
+                method: word.to_ascii_uppercase().parse()?,
+                target,
+                items: words.collect(),
+            }
+        }
+        _ => {
+            return Err(anyhow!(format!(
+                "Unknown command {}, type `help` for the list",
+                word
+            )))
+        }
+    };
+    Ok(Some(command))
+}
+
+/// Whether `word` names what to request rather than being an item.
+fn is_target(word: &str) -> bool {
+    word.starts_with(['/', ':'])
+        || word.contains("://")
+        || !(word.contains(['=', '@']) || query::is_item(word))
+}
+
+/// The session state kept between lines.
+struct Session {
+    base: Option<String>,
+    headers: HeaderMap,
+}
+
+impl Session {
+    /// `target` made absolute against the base URL.
+    fn resolve(&self, target: Option<&str>) -> Result<String> {
+        match (target, &self.base) {
+            (Some(t), Some(base)) if !t.contains("://") && !t.starts_with(':') => Ok(format!(
+                "{}/{}",
+                base.trim_end_matches('/'),
+                t.trim_start_matches('/')
+            )),
+            (Some(t), _) => parse_url(t),
+            (None, Some(base)) => Ok(base.clone()),
+            (None, None) => Err(anyhow!("Failed to send: no URL given and no base URL set")),
+        }
+    }
+
+    async fn request(
+        &self,
+        client: &Client,
+        method: Method,
+        target: Option<&str>,
+        items: &[String],
+        opts: &Opts,
+    ) -> Result<()> {
+        let mut query = Vec::new();
+        let mut body = Vec::new();
+        for item in items {
+            if query::is_item(item) {
+                query.push(query::parse_item(item)?);
+            } else {
+                body.push(parse_templated_body_item(item)?);
+            }
+        }
+        let url = query::append(
+            &self.resolve(target)?,
+            &query,
+            query::ArrayFormat::default(),
+        )?;
+        let req = client.request(method, url).headers(self.headers.clone());
+        send(client, req, &body, opts).await
+    }
+
+    async fn handle(&mut self, command: Command, client: &Client, opts: &Opts) -> Result<()> {
+        match command {
+            Command::Request {
+                method,
+                target,
+                items,
+            } => {
+                self.request(client, method, target.as_deref(), &items, opts)
+                    .await?;
+                // bodies need not end with a newline, keep the prompt apart
+                println!();
+            }
+            Command::Set(name, value) => {
+                let name: HeaderName = name.parse()?;
+                let value: HeaderValue = crate::template::render(&value)?.parse()?;
+                self.headers.insert(name, value);
+            }
+            Command::Unset(name) => {
+                if self.headers.remove(name.as_str()).is_none() {
+                    println!("{} is not set", name);
+                }
+            }
+            Command::Headers => crate::print_header_table(&self.headers, true),
+            Command::Base(None) => match &self.base {
+                Some(base) => println!("{}", base),
+                None => println!("No base URL, give full URLs or set one with `base <url>`"),
+            },
+            Command::Base(Some(url)) => self.base = Some(parse_url(&url)?),
+            Command::Help => println!("{}", HELP),
+            Command::Exit => {}
+        }
+        Ok(())
+    }
+}
+
+fn history_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".httpie")
+        .join("repl_history")
+}
+
+/// Read commands until `exit` or end of input. Errors are printed and the
+/// session goes on.
+pub async fn run(client: Client, base: Option<String>, opts: &Opts) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let history = history_path();
+    // there is no history yet on first use
+    editor.load_history(&history).ok();
+    let mut session = Session {
+        base,
+        headers: HeaderMap::new(),
+    };
+    let prompt = "httpie> ".green().bold().to_string();
+    loop {
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        editor.add_history_entry(line.as_str())?;
+        match parse_line(&line) {
+            Ok(Some(Command::Exit)) => break,
+            Ok(Some(command)) => {
+                if let Err(e) = session.handle(command, &client, opts).await {
+                    eprintln!("{} {}", "Error:".red().bold(), e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("{} {}", "Error:".red().bold(), e),
+        }
+    }
+    if let Some(dir) = history.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    editor.save_history(&history)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_line_works() {
+        assert_eq!(
+            parse_line("post /items name=x ids:=[1,2]").unwrap(),
+            Some(Command::Request {
+                method: Method::POST,
+                target: Some("/items".into()),
+                items: vec!["name=x".into(), "ids:=[1,2]".into()],
+            })
+        );
+        assert_eq!(
+            parse_line("GET q==1").unwrap(),
+            Some(Command::Request {
+                method: Method::GET,
+                target: None,
+                items: vec!["q==1".into()],
+            })
+        );
+        assert_eq!(
+            parse_line("set Authorization: Bearer a:b").unwrap(),
+            Some(Command::Set("Authorization".into(), "Bearer a:b".into()))
+        );
+        assert_eq!(parse_line("  ").unwrap(), None);
+        assert!(parse_line("fetch /x").is_err());
+        assert!(parse_line("set Authorization").is_err());
+    }
+
+    #[test]
+    fn resolve_works() {
+        let session = Session {
+            base: Some("http://localhost:3000/v1/".into()),
+            headers: HeaderMap::new(),
+        };
+        assert_eq!(
+            session.resolve(Some("/users")).unwrap(),
+            "http://localhost:3000/v1/users"
+        );
+        assert_eq!(session.resolve(None).unwrap(), "http://localhost:3000/v1/");
+        assert_eq!(
+            session.resolve(Some("https://example.com/a")).unwrap(),
+            "https://example.com/a"
+        );
+        let bare = Session {
+            base: None,
+            headers: HeaderMap::new(),
+        };
+        assert!(bare.resolve(None).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    fs::{OpenOptions, Permissions},
+    io::Write,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{config, template};
+
+/// Where a secret lives, as written in the `[secrets]` table of the config.
+#[derive(Debug, PartialEq)]
+enum Source {
+    /// `vault:PATH#FIELD`, read with the `vault` CLI and its login
+    Vault { path: String, field: String },
+    /// `sops:FILE` for the whole decrypted file, or `sops:FILE#a.b` for one
+    /// value of a YAML or JSON file
+    Sops { file: PathBuf, key: Option<String> },
+    /// `env-file:FILE#KEY`, a `.env` file kept out of version control; the
+    /// secret's own name when there is no `#KEY`
+    EnvFile { file: PathBuf, key: Option<String> },
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = s.split_once(':').unwrap_or(("", s));
+        let (target, key) = match rest.rsplit_once('#') {
+            Some((target, key)) => (target, Some(key.to_string())),
+            None => (rest, None),
+        };
+        match kind {
+            "vault" => Ok(Self::Vault {
+                path: target.into(),
+                field: key.ok_or_else(|| anyhow!("give the field as vault:PATH#FIELD"))?,
+            }),
+            "sops" => Ok(Self::Sops {
+                file: target.into(),
+                key,
+            }),
+            "env-file" => Ok(Self::EnvFile {
+                file: target.into(),
+                key,
+            }),
+            _ => Err(anyhow!(format!(
+                "unknown source {}, expected vault:, sops: or env-file:",
+                s
+            ))),
+        }
+    }
+}
+
+/// The output of `program args`, trimmed of the line break it ends with.
+fn run(program: &str, args: &[&str]) -> Result<String> {
+    let out = Command::new(program)
+        .args(args)
+        .output()
+        .with_context(|| format!("{} could not be run, is it installed?", program))?;
+    if !out.status.success() {
+        return Err(anyhow!(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&out.stderr).trim()
+        )));
+    }
+    let mut value = String::from_utf8(out.stdout)
+        .map_err(|_| anyhow!(format!("{} printed something that is not text", program)))?;
+    if value.ends_with('\n') {
+        value.pop();
+    }
+    Ok(value)
+}
+
+impl Source {
+    fn fetch(&self, name: &str) -> Result<String> {
+        match self {
+            Self::Vault { path, field } => {
+                run("vault", &["kv", "get", &format!("-field={}", field), path])
+            }
+            Self::Sops { file, key } => {
+                let file = file.to_string_lossy();
+                match key {
+                    Some(key) => {
+                        // `a.b` becomes sops' `["a"]["b"]`
+                        let extract: String =
+                            key.split('.').map(|k| format!("[\"{}\"]", k)).collect();
+                        run("sops", &["--decrypt", "--extract", &extract, &file])
+                    }
+                    None => run("sops", &["--decrypt", &file]),
+                }
+            }
+            Self::EnvFile { file, key } => {
+                let s = std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                let vars = template::parse_env_file(&s)?;
+                let key = key.as_deref().unwrap_or(name);
+                vars.get(key)
+                    .cloned()
+                    .ok_or_else(|| anyhow!(format!("{} has no {}", file.display(), key)))
+            }
+        }
+    }
+}
+
+/// Secrets resolved so far, by name, to fetch each once and to know what to
+/// keep out of files.
+static RESOLVED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// The value of `name` when the config declares it a secret.
+pub fn resolve(name: &str) -> Option<Result<String>> {
+    let spec = config::current().secrets.get(name)?;
+    if let Some((_, value)) = RESOLVED.lock().unwrap().iter().find(|(n, _)| n == name) {
+        return Some(Ok(value.clone()));
+    }
+    let value = spec
+        .parse::<Source>()
+        .and_then(|source| source.fetch(name))
+        .map_err(|e| anyhow!(format!("Failed to resolve secret {}: {}", name, e)));
+    if let Ok(value) = &value {
+        RESOLVED
+            .lock()
+            .unwrap()
+            .push((name.to_string(), value.clone()));
+    }
+    Some(value)
+}
+
+/// Write `contents` to `path` readable by the user alone, as keys and tokens
+/// should be; a file already there is made so before it is overwritten.
+pub fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    file.set_len(0)?;
+    file.write_all(contents.as_bytes())
+}
+
+/// `s` with every resolved secret put back as its `{{NAME}}` placeholder,
+/// for anything written to disk. JSON-escaped values are found too.
+pub fn redact(s: &str) -> String {
+    let resolved = RESOLVED.lock().unwrap();
+    let mut out = s.to_string();
+    for (name, value) in resolved.iter().filter(|(_, v)| !v.is_empty()) {
+        let placeholder = format!("{{{{{}}}}}", name);
+        let escaped = serde_json::to_string(value).unwrap_or_default();
+        let escaped = &escaped[1..escaped.len() - 1];
+        out = out
+            .replace(value, &placeholder)
+            .replace(escaped, &placeholder);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_works() {
+        assert_eq!(
+            "vault:secret/staging#token".parse::<Source>().unwrap(),
+            Source::Vault {
+                path: "secret/staging".into(),
+                field: "token".into()
+            }
+        );
+        assert!("vault:secret/staging".parse::<Source>().is_err());
+        assert!("keychain:x".parse::<Source>().is_err());
+
+        let file = std::env::temp_dir().join(format!("httpie-secrets-{}.env", std::process::id()));
+        std::fs::write(&file, "API_TOKEN=s3cr3t\nOTHER='a\"b'\n").unwrap();
+        let source: Source = format!("env-file:{}", file.display()).parse().unwrap();
+        assert_eq!(source.fetch("API_TOKEN").unwrap(), "s3cr3t");
+        let source: Source = format!("env-file:{}#OTHER", file.display())
+            .parse()
+            .unwrap();
+        assert_eq!(source.fetch("API_TOKEN").unwrap(), "a\"b");
+        std::fs::remove_file(&file).unwrap();
+
+        RESOLVED
+            .lock()
+            .unwrap()
+            .push(("QUOTED".into(), "a\"b".into()));
+        assert_eq!(
+            redact(r#"{"auth": "a\"b", "raw": a"b}"#),
+            r#"{"auth": "{{QUOTED}}", "raw": {{QUOTED}}}"#
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use mime::Mime;
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, Request, Response, StatusCode, Url, Version,
+};
+
+use crate::{
+    auth::{self, Auth},
+    body, diff,
+    stats::format_duration,
+};
+
+/// `primary` moved onto the shadow endpoint: its origin, with the shadow's
+/// path (if any) as a prefix.
+pub fn shadow_url(primary: &Url, shadow: &Url) -> Url {
+    let mut url = shadow.clone();
+    let path = shadow.path().trim_end_matches('/').to_string() + primary.path();
+    url.set_path(&path);
+    url.set_query(primary.query());
+    url
+}
+
+/// A copy of `req` addressed to the shadow endpoint, signed for it again.
+pub fn mirror(req: &Request, shadow: &Url, auth: Option<&dyn Auth>) -> Result<Request> {
+    let mut copy = req.try_clone().ok_or_else(|| {
+        anyhow!("Failed to mirror request: streamed file bodies cannot be sent twice")
+    })?;
+    *copy.url_mut() = shadow_url(req.url(), shadow);
+    if let Some(auth) = auth {
+        auth.apply(&mut copy)?;
+    }
+    Ok(copy)
+}
+
+/// A response read in full.
+pub struct Outcome {
+    pub url: Url,
+    pub status: StatusCode,
+    pub version: Version,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    pub elapsed: Duration,
+}
+
+impl Outcome {
+    /// Send `req`, prepared for `auth` already.
+    pub async fn fetch(client: &Client, req: Request, auth: Option<&dyn Auth>) -> Result<Self> {
+        let url = req.url().clone();
+        let start = Instant::now();
+        let resp = auth::send(client, req, auth).await?;
+        let status = resp.status();
+        let version = resp.version();
+        let headers = resp.headers().clone();
+        let body = resp.bytes().await?.to_vec();
+        Ok(Self {
+            url,
+            status,
+            version,
+            headers,
+            body,
+            elapsed: start.elapsed(),
+        })
+    }
+
+    /// Back into a response, for the regular printing.
+    pub fn to_response(&self) -> Response {
+        let mut resp = hyper::Response::new(self.body.clone());
+        *resp.status_mut() = self.status;
+        *resp.version_mut() = self.version;
+        *resp.headers_mut() = self.headers.clone();
+        resp.into()
+    }
+
+    /// Status and body, with JSON pretty printed so that diffs are per value.
+    fn comparable(&self) -> String {
+        let mime: Option<Mime> = self
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok()?.parse().ok());
+        let mut text = body::decode_text(&self.body, mime.as_ref()).into_owned();
+        if mime.as_ref().and_then(crate::syntax_for) == Some("json") {
+            text = jsonxf::pretty_print(&text).unwrap_or(text);
+        }
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        format!("{}\n\n{}", self.status, text)
+    }
+
+    fn summary(&self) -> String {
+        format!("{} in {}", self.status, format_duration(self.elapsed))
+    }
+}
+
+/// Print how the shadow response compares to the primary one.
+pub fn report(primary: &Outcome, shadow: Result<Outcome>) {
+    println!();
+    let shadow = match shadow {
+        Ok(s) => s,
+        Err(e) => {
+            println!("{} {}", "Shadow failed:".red().bold(), e);
+            return;
+        }
+    };
+    println!(
+        "{} {} (primary {})",
+        "Shadow:".bold(),
+        shadow.summary(),
+        primary.summary()
+    );
+    match diff::unified(
+        &primary.comparable(),
+        &shadow.comparable(),
+        primary.url.as_str(),
+        shadow.url.as_str(),
+    ) {
+        Some(d) => diff::print_unified(&d),
+        None => println!("{}", "Shadow response matches the primary".green()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_url_works() {
+        let primary: Url = "https://api.example.com/v1/users?page=2".parse().unwrap();
+        let shadow = |s: &str| shadow_url(&primary, &s.parse().unwrap()).to_string();
+        assert_eq!(
+            shadow("http://staging:8080"),
+            "http://staging:8080/v1/users?page=2"
+        );
+        assert_eq!(
+            shadow("http://staging:8080/next/"),
+            "http://staging:8080/next/v1/users?page=2"
+        );
+    }
+
+    #[test]
+    fn comparable_works() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        let outcome = Outcome {
+            url: "http://a/".parse().unwrap(),
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers,
+            body: br#"{"a":1}"#.to_vec(),
+            elapsed: Duration::ZERO,
+        };
+        assert_eq!(outcome.comparable(), "200 OK\n\n{\n  \"a\": 1\n}\n");
+    }
+}
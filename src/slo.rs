@@ -0,0 +1,147 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use reqwest::{Client, Request};
+
+use crate::{
+    auth::Auth,
+    bench,
+    stats::{self, format_duration},
+};
+
+/// Samples taken when an objective does not say.
+const DEFAULT_SAMPLES: usize = 20;
+
+/// A latency objective, e.g. `p95=300ms@20`: the 95th percentile of 20
+/// sequential requests must be at most 300ms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Objective {
+    pub percentile: f64,
+    pub target: Duration,
+    pub samples: usize,
+}
+
+impl FromStr for Objective {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(format!(
+                "Failed to parse SLO {}: expected e.g. p95=300ms@20",
+                s
+            ))
+        };
+        let (p, rest) = s.split_once('=').ok_or_else(invalid)?;
+        let percentile: f64 = p
+            .strip_prefix(['p', 'P'])
+            .and_then(|p| p.parse().ok())
+            .filter(|p| (0.0..=100.0).contains(p))
+            .ok_or_else(invalid)?;
+        let (target, samples) = match rest.split_once('@') {
+            Some((target, n)) => (target, n.parse().map_err(|_| invalid())?),
+            None => (rest, DEFAULT_SAMPLES),
+        };
+        if samples == 0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            percentile,
+            target: crate::parse_duration(target)?,
+            samples,
+        })
+    }
+}
+
+/// Send `req` as many times as the objectives need, one after another,
+/// print how each one fared and fail if any is missed or a request fails.
+pub async fn check(
+    client: Client,
+    auth: Option<Arc<dyn Auth>>,
+    req: Request,
+    objectives: &[Objective],
+) -> Result<()> {
+    let samples = objectives.iter().map(|o| o.samples).max().unwrap_or(0);
+    let report = bench::run(client, auth, req, samples, 1, None, None).await?;
+    let failed: usize = report.errors
+        + report
+            .statuses
+            .iter()
+            .filter(|(status, _)| **status >= 400)
+            .map(|(_, n)| n)
+            .sum::<usize>();
+    let mut missed = Vec::new();
+    for o in objectives {
+        // each objective looks at its own first samples
+        let mut taken = report.latencies[..o.samples.min(report.latencies.len())].to_vec();
+        taken.sort();
+        if taken.is_empty() {
+            missed.push(format!("p{} had no successful samples", o.percentile));
+            continue;
+        }
+        let actual = stats::percentile(&taken, o.percentile);
+        let ok = actual <= o.target;
+        println!(
+            "{} p{} <= {} over {} samples: {}",
+            if ok {
+                "PASS".green().bold()
+            } else {
+                "FAIL".red().bold()
+            },
+            o.percentile,
+            format_duration(o.target),
+            taken.len(),
+            format_duration(actual)
+        );
+        if !ok {
+            missed.push(format!(
+                "p{} was {}, over {}",
+                o.percentile,
+                format_duration(actual),
+                format_duration(o.target)
+            ));
+        }
+    }
+    if let Some(s) = stats::summarize(&report.latencies) {
+        println!(
+            "{:<14}min {}  mean {}  max {}",
+            "Latency:",
+            format_duration(s.min),
+            format_duration(s.mean),
+            format_duration(s.max)
+        );
+    }
+    if failed > 0 {
+        missed.push(format!("{} of {} requests failed", failed, samples));
+    }
+    if !missed.is_empty() {
+        return Err(anyhow!(format!(
+            "Failed to meet the SLO: {}",
+            missed.join("; ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_objective_works() {
+        assert_eq!(
+            "p95=300ms@20".parse::<Objective>().unwrap(),
+            Objective {
+                percentile: 95.0,
+                target: Duration::from_millis(300),
+                samples: 20,
+            }
+        );
+        let o: Objective = "p99.9=1.5s".parse().unwrap();
+        assert_eq!((o.percentile, o.samples), (99.9, DEFAULT_SAMPLES));
+        assert!("95=300ms".parse::<Objective>().is_err());
+        assert!("p101=1s".parse::<Objective>().is_err());
+        assert!("p95=300ms@0".parse::<Objective>().is_err());
+        assert!("p95=fast".parse::<Objective>().is_err());
+    }
+}
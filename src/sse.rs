@@ -0,0 +1,177 @@
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use reqwest::{header, Client};
+
+use crate::{
+    auth::{self, Auth},
+    transcript::{self, Frame, Recorder},
+};
+
+/// An event of a `text/event-stream` body.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Event {
+    pub event: Option<String>,
+    pub id: Option<String>,
+    pub data: String,
+}
+
+/// Incremental parser for the server-sent events wire format.
+#[derive(Debug, Default)]
+pub struct Parser {
+    buf: String,
+    current: Event,
+    has_data: bool,
+}
+
+impl Parser {
+    /// Feed a chunk of the stream, returning the events it completed.
+    pub fn push(&mut self, chunk: &str) -> Vec<Event> {
+        self.buf.push_str(chunk);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.find('\n') {
+            let line: String = self.buf.drain(..=pos).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if self.has_data {
+                    events.push(std::mem::take(&mut self.current));
+                }
+                self.current = Event::default();
+                self.has_data = false;
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.current.event = Some(value.into()),
+                "id" => self.current.id = Some(value.into()),
+                "data" => {
+                    if self.has_data {
+                        self.current.data.push('\n');
+                    }
+                    self.current.data.push_str(value);
+                    self.has_data = true;
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// The text at the start of `pending`, taken out of it, with invalid bytes
+/// replaced; a sequence cut off at the end, as a chunk may be, is left for
+/// the next chunk to complete.
+fn take_text(pending: &mut Vec<u8>) -> String {
+    let mut text = String::new();
+    let mut at = 0;
+    loop {
+        match std::str::from_utf8(&pending[at..]) {
+            Ok(s) => {
+                text += s;
+                at = pending.len();
+                break;
+            }
+            Err(e) => {
+                let valid = at + e.valid_up_to();
+                // valid up to there, so this cannot fail
+                text += std::str::from_utf8(&pending[at..valid]).unwrap_or_default();
+                match e.error_len() {
+                    Some(n) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        at = valid + n;
+                    }
+                    None => {
+                        at = valid;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    pending.drain(..at);
+    text
+}
+
+/// Subscribe to an event stream and print events as they arrive, optionally
+/// recording them to a transcript.
+pub async fn stream(
+    client: Client,
+    auth: Option<&dyn Auth>,
+    url: &str,
+    mut recorder: Option<Recorder>,
+) -> Result<()> {
+    let req = client
+        .get(url)
+        .header(header::ACCEPT, "text/event-stream")
+        .build()?;
+    let mut resp = auth::sign_and_send(&client, req, auth).await?;
+    if !resp.status().is_success() {
+        return Err(anyhow!("Failed to subscribe: {}", resp.status()));
+    }
+    let start = Instant::now();
+    let mut parser = Parser::default();
+    let mut pending = Vec::new();
+    while let Some(chunk) = resp.chunk().await? {
+        pending.extend_from_slice(&chunk);
+        for event in parser.push(&take_text(&mut pending)) {
+            let frame = Frame::Event {
+                event: event.event,
+                id: event.id,
+                data: event.data,
+            };
+            let elapsed = start.elapsed();
+            transcript::render(&frame, elapsed);
+            if let Some(r) = recorder.as_mut() {
+                r.record(&frame, elapsed)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parser_works() {
+        let mut p = Parser::default();
+        assert_eq!(p.push(": keepalive\nevent: tick\nid: 1\nda"), vec![]);
+        assert_eq!(
+            p.push("ta: a\ndata: b\n\ndata:c\r\n\r\n"),
+            vec![
+                Event {
+                    event: Some("tick".into()),
+                    id: Some("1".into()),
+                    data: "a\nb".into(),
+                },
+                Event {
+                    data: "c".into(),
+                    ..Default::default()
+                },
+            ]
+        );
+        // events without data are dropped
+        assert_eq!(p.push("event: empty\n\n"), vec![]);
+    }
+
+    #[test]
+    fn take_text_works() {
+        let mut pending = b"data: a\xff\n\ndata: b\n\n\xe2\x82".to_vec();
+        let text = take_text(&mut pending);
+        assert_eq!(text, "data: a\u{fffd}\n\ndata: b\n\n");
+        // the cut off euro sign waits for the rest
+        assert_eq!(pending, b"\xe2\x82");
+        pending.push(0xac);
+        assert_eq!(take_text(&mut pending), "€");
+        assert!(pending.is_empty());
+
+        let mut p = Parser::default();
+        let events = p.push(&take_text(&mut b"\xff\ndata: b\n\n".to_vec()));
+        assert_eq!(events[0].data, "b");
+    }
+}
@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+/// Latency distribution of a set of samples.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Summary {
+    pub count: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Summarize `samples`, or `None` when there are none.
+pub fn summarize(samples: &[Duration]) -> Option<Summary> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort();
+    let total: Duration = sorted.iter().sum();
+    Some(Summary {
+        count: sorted.len(),
+        min: sorted[0],
+        mean: total / sorted.len() as u32,
+        p50: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+        max: sorted[sorted.len() - 1],
+    })
+}
+
+/// Nearest-rank percentile of already sorted, non-empty samples.
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Human friendly duration, e.g. `850µs`, `12.3ms` or `1.20s`.
+pub fn format_duration(d: Duration) -> String {
+    let micros = d.as_micros();
+    if micros < 1000 {
+        format!("{}µs", micros)
+    } else if micros < 1_000_000 {
+        format!("{:.1}ms", micros as f64 / 1000.0)
+    } else {
+        format!("{:.2}s", d.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(v: u64) -> Duration {
+        Duration::from_millis(v)
+    }
+
+    #[test]
+    fn summarize_works() {
+        assert_eq!(summarize(&[]), None);
+        let samples: Vec<_> = (1..=100).rev().map(ms).collect();
+        let s = summarize(&samples).unwrap();
+        assert_eq!(s.count, 100);
+        assert_eq!((s.min, s.max), (ms(1), ms(100)));
+        assert_eq!(s.mean, Duration::from_micros(50_500));
+        assert_eq!((s.p50, s.p95, s.p99), (ms(50), ms(95), ms(99)));
+    }
+
+    #[test]
+    fn percentile_works() {
+        let sorted = [ms(10), ms(20), ms(30)];
+        assert_eq!(percentile(&sorted, 0.0), ms(10));
+        assert_eq!(percentile(&sorted, 50.0), ms(20));
+        assert_eq!(percentile(&sorted, 100.0), ms(30));
+    }
+
+    #[test]
+    fn format_duration_works() {
+        assert_eq!(format_duration(Duration::from_micros(850)), "850µs");
+        assert_eq!(format_duration(Duration::from_micros(12_345)), "12.3ms");
+        assert_eq!(format_duration(ms(1200)), "1.20s");
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use mime::Mime;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::{config, exchange::Exchange, har};
+
+/// Hashes are shown this long; any unique prefix finds a body again.
+pub const SHORT: usize = 12;
+
+/// What is kept next to a body, `<hash>.json` beside the `<hash>` file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Meta {
+    pub url: String,
+    pub status: u16,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    /// when it was received, ISO 8601
+    pub received: String,
+}
+
+pub struct Stored {
+    pub hash: String,
+    pub meta: Meta,
+    pub body: Vec<u8>,
+}
+
+impl Stored {
+    pub fn mime(&self) -> Option<Mime> {
+        self.meta.content_type.as_deref()?.parse().ok()
+    }
+}
+
+fn dir() -> PathBuf {
+    config::default_path().with_file_name("store")
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    digest::digest(&digest::SHA256, bytes)
+        .as_ref()
+        .iter()
+        .fold(String::new(), |mut s, b| {
+            write!(s, "{:02x}", b).ok();
+            s
+        })
+}
+
+/// Keep `body`, the decoded body of `exchange`, in the store and return its
+/// hash. The same body received again is stored once.
+pub fn put(exchange: &Exchange, body: &[u8]) -> Result<String> {
+    put_in(&dir(), exchange, body)
+}
+
+fn put_in(dir: &Path, exchange: &Exchange, body: &[u8]) -> Result<String> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let hash = sha256(body);
+    let path = dir.join(&hash);
+    if !path.exists() {
+        std::fs::write(&path, body)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    let meta = Meta {
+        url: exchange
+            .url
+            .as_ref()
+            .map(|u| u.to_string())
+            .unwrap_or_default(),
+        status: exchange.status.as_u16(),
+        content_type: exchange
+            .headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+        received: har::iso8601(SystemTime::now()),
+    };
+    let meta_path = path.with_extension("json");
+    std::fs::write(&meta_path, serde_json::to_string_pretty(&meta)? + "\n")
+        .with_context(|| format!("Failed to write {}", meta_path.display()))?;
+    Ok(hash)
+}
+
+/// The stored body whose hash starts with `prefix`.
+pub fn get(prefix: &str) -> Result<Stored> {
+    get_in(&dir(), prefix)
+}
+
+fn get_in(dir: &Path, prefix: &str) -> Result<Stored> {
+    let prefix = prefix.to_ascii_lowercase();
+    if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!(format!("Failed to find {}: not a hash", prefix)));
+    }
+    let mut found: Vec<String> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|e| e.ok()?.file_name().into_string().ok())
+        .filter(|name| !name.contains('.') && name.starts_with(&prefix))
+        .collect();
+    let hash = match found.len() {
+        0 => {
+            return Err(anyhow!(format!(
+                "Failed to find {}: no stored body has that hash",
+                prefix
+            )))
+        }
+        1 => found.remove(0),
+        n => {
+            return Err(anyhow!(format!(
+                "Failed to find {}: {} stored bodies start with it, give more of the hash",
+                prefix, n
+            )))
+        }
+    };
+    let path = dir.join(&hash);
+    let body =
+        std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let meta_path = path.with_extension("json");
+    let meta = std::fs::read_to_string(&meta_path)
+        .with_context(|| format!("Failed to read {}", meta_path.display()))?;
+    let meta = serde_json::from_str(&meta)
+        .map_err(|e| anyhow!(format!("Failed to parse {}: {}", meta_path.display(), e)))?;
+    Ok(Stored { hash, meta, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode, Version};
+    use std::time::Duration;
+
+    #[test]
+    fn put_and_get_work() {
+        let dir = std::env::temp_dir().join(format!("httpie-store-{}", std::process::id()));
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", "application/json".parse().unwrap());
+        let exchange = Exchange {
+            url: Some("http://localhost/a".parse().unwrap()),
+            status: StatusCode::OK,
+            version: Version::HTTP_11,
+            headers,
+            body: Default::default(),
+            timing: crate::exchange::Timing {
+                headers: Duration::ZERO,
+                total: Duration::ZERO,
+            },
+            redirects: Vec::new(),
+        };
+        let hash = put_in(&dir, &exchange, b"{}").unwrap();
+        assert_eq!(hash, sha256(b"{}"));
+        let stored = get_in(&dir, &hash[..SHORT]).unwrap();
+        assert_eq!(stored.body, b"{}");
+        assert_eq!(stored.meta.url, "http://localhost/a");
+        assert_eq!(stored.mime(), Some(mime::APPLICATION_JSON));
+        assert!(get_in(&dir, "ffff").is_err());
+        assert!(get_in(&dir, "../x").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::secrets;
+
+/// Variables from `--env-file`, loaded before the command line is parsed.
+static ENV_FILE: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// The `--env-file` argument, found ahead of clap so that value parsers can
+/// already resolve placeholders.
+pub fn env_file_arg(args: &[String]) -> Option<PathBuf> {
+    crate::flag_value(args, "--env-file").map(PathBuf::from)
+}
+
+pub fn init(env_file: Option<&Path>) -> Result<()> {
+    let vars = match env_file {
+        Some(path) => {
+            let s = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            parse_env_file(&s).map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e)))?
+        }
+        None => HashMap::new(),
+    };
+    ENV_FILE.set(vars).ok();
+    Ok(())
+}
+
+/// `KEY=value` lines of a `.env` file. Values may be single quoted (taken
+/// literally) or double quoted (with `\n`, `\"` and `\\` escapes); `#` starts
+/// a comment outside quotes.
+pub fn parse_env_file(s: &str) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for (n, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!(format!("line {}: expected KEY=value", n + 1)))?;
+        let key = key.trim();
+        if !is_name(key) {
+            return Err(anyhow!(format!("line {}: invalid name {}", n + 1, key)));
+        }
+        let value = value.trim();
+        let value = if let Some(v) = value.strip_prefix('\'') {
+            v.strip_suffix('\'')
+                .ok_or_else(|| anyhow!(format!("line {}: unterminated quote", n + 1)))?
+                .to_string()
+        } else if let Some(v) = value.strip_prefix('"') {
+            let v = v
+                .strip_suffix('"')
+                .ok_or_else(|| anyhow!(format!("line {}: unterminated quote", n + 1)))?;
+            v.replace("\\n", "\n")
+                .replace("\\\"", "\"")
+                .replace("\\\\", "\\")
+        } else {
+            match value.find(" #") {
+                Some(i) => value[..i].trim_end().to_string(),
+                None => value.to_string(),
+            }
+        };
+        vars.insert(key.to_string(), value);
+    }
+    Ok(vars)
+}
+
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Replace `{{VAR}}` placeholders using `lookup`, failing with the names of
+/// all the variables it could not resolve. Braces around anything that is
+/// not a variable name are left alone.
+pub fn expand(s: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut missing: Vec<&str> = Vec::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        out.push_str(&rest[..start]);
+        let end = start + 2 + len + 2;
+        match is_name(name).then(|| lookup(name)) {
+            Some(Some(value)) => out.push_str(&value),
+            Some(None) => {
+                if !missing.contains(&name) {
+                    missing.push(name);
+                }
+            }
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    if !missing.is_empty() {
+        return Err(anyhow!(format!(
+            "Unresolved variables in {}: {}",
+            s,
+            missing.join(", ")
+        )));
+    }
+    Ok(out)
+}
+
+/// Expand placeholders from the config's secrets, then the environment, then
+/// the `--env-file`. Set environment variables take precedence over the
+/// file, as with other `.env` tooling.
+pub fn render(s: &str) -> Result<String> {
+    let failed = RefCell::new(None);
+    let rendered = expand(s, |name| match secrets::resolve(name) {
+        Some(Ok(value)) => Some(value),
+        Some(Err(e)) => {
+            failed.borrow_mut().get_or_insert(e);
+            None
+        }
+        None => std::env::var(name)
+            .ok()
+            .or_else(|| ENV_FILE.get()?.get(name).cloned()),
+    });
+    match failed.into_inner() {
+        Some(e) => Err(e),
+        None => rendered,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_works() {
+        let vars = HashMap::from([("HOST", "api.local"), ("TOKEN", "s3cr3t")]);
+        let lookup = |n: &str| vars.get(n).map(|v| v.to_string());
+        assert_eq!(
+            expand("https://{{HOST}}/x?t={{ TOKEN }}", lookup).unwrap(),
+            "https://api.local/x?t=s3cr3t"
+        );
+        assert_eq!(expand("{{not a var}} {{", lookup).unwrap(), "{{not a var}} {{");
+        let err = expand("{{A}}/{{HOST}}/{{B}}/{{A}}", lookup).unwrap_err();
+        assert_eq!(err.to_string(), "Unresolved variables in {{A}}/{{HOST}}/{{B}}/{{A}}: A, B");
+    }
+
+    #[test]
+    fn parse_env_file_works() {
+        let vars = parse_env_file(
+            "# comment\nexport A=1\nB = two words # note\nC='lit # \\n'\nD=\"x\\ny\"\n\n",
+        )
+        .unwrap();
+        assert_eq!(vars["A"], "1");
+        assert_eq!(vars["B"], "two words");
+        assert_eq!(vars["C"], "lit # \\n");
+        assert_eq!(vars["D"], "x\ny");
+        assert!(parse_env_file("NOPE").is_err());
+        assert!(parse_env_file("1X=a").is_err());
+        assert!(parse_env_file("A=\"open").is_err());
+    }
+
+    #[test]
+    fn env_file_arg_works() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            env_file_arg(&args(&["httpie", "get", "--env-file", ".env", "u"])),
+            Some(".env".into())
+        );
+        assert_eq!(env_file_arg(&args(&["httpie", "--env-file=a.env"])), Some("a.env".into()));
+        assert_eq!(env_file_arg(&args(&["httpie", "--", "--env-file", "x"])), None);
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+use crate::{body::hexdump, output, print_synctect};
+
+/// Something received on a streaming connection.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Frame {
+    /// A server-sent event
+    Event {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        event: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        data: String,
+    },
+    /// A WebSocket text message
+    Text {
+        data: String,
+        #[serde(default, skip_serializing_if = "is_false")]
+        outgoing: bool,
+    },
+    /// A WebSocket binary message, base64 encoded
+    Binary {
+        data: String,
+        #[serde(default, skip_serializing_if = "is_false")]
+        outgoing: bool,
+    },
+    /// The WebSocket connection was closed
+    Close {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        code: Option<u16>,
+        #[serde(default)]
+        reason: String,
+    },
+}
+
+fn is_false(b: &bool) -> bool {
+    !b
+}
+
+fn arrow(outgoing: bool) -> colored::ColoredString {
+    if outgoing {
+        "->".green()
+    } else {
+        "<-".cyan()
+    }
+}
+
+/// One transcript line: a frame and when it arrived, in milliseconds since
+/// the connection was opened.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    t: u64,
+    #[serde(flatten)]
+    frame: Frame,
+}
+
+/// Writes frames to a JSON Lines transcript as they arrive.
+pub struct Recorder {
+    out: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self {
+            out: BufWriter::new(file),
+        })
+    }
+
+    pub fn record(&mut self, frame: &Frame, elapsed: Duration) -> Result<()> {
+        let entry = Entry {
+            t: elapsed.as_millis() as u64,
+            frame: frame.clone(),
+        };
+        serde_json::to_writer(&mut self.out, &entry)?;
+        self.out.write_all(b"\n")?;
+        // keep the transcript usable when the stream is interrupted
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Print a frame with its arrival time; JSON payloads are highlighted.
+pub fn render(frame: &Frame, elapsed: Duration) {
+    let ts = format!("[+{:.3}s]", elapsed.as_secs_f64()).dimmed();
+    match frame {
+        Frame::Event { event, id, data } => {
+            let mut head = format!(
+                "{} {}",
+                ts,
+                event.as_deref().unwrap_or("message").cyan().bold()
+            );
+            if let Some(id) = id {
+                head += &format!(" id={}", id);
+            }
+            println!("{}", head);
+            print_data(data);
+        }
+        Frame::Text { data, outgoing } => {
+            println!("{} {} {}", ts, arrow(*outgoing), "text".bold());
+            print_data(data);
+        }
+        Frame::Binary { data, outgoing } => {
+            let bytes = STANDARD.decode(data).unwrap_or_default();
+            println!(
+                "{} {} {} ({} bytes)",
+                ts,
+                arrow(*outgoing),
+                "binary".bold(),
+                bytes.len()
+            );
+            print!("{}", hexdump(&bytes));
+        }
+        Frame::Close { code, reason } => {
+            let code = code.map(|c| c.to_string()).unwrap_or_default();
+            println!("{} {} {} {}", ts, "closed".yellow().bold(), code, reason);
+        }
+    }
+}
+
+fn print_data(data: &str) {
+    match serde_json::from_str::<serde_json::Value>(data) {
+        Ok(v) if v.is_object() || v.is_array() => {
+            print_synctect(&serde_json::to_string_pretty(&v).unwrap(), "json");
+            println!();
+        }
+        _ => output::write(&format!("{}\n", data)),
+    }
+}
+
+/// Re-render a transcript, `speed` times faster than it was recorded; a
+/// speed of zero prints everything at once.
+pub async fn replay(path: &Path, speed: f64) -> Result<()> {
+    if speed < 0.0 {
+        return Err(anyhow!("Speed must not be negative"));
+    }
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut last = 0;
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("Failed to parse {} line {}", path.display(), n + 1))?;
+        if speed > 0.0 {
+            let wait = entry.t.saturating_sub(last) as f64 / speed;
+            tokio::time::sleep(Duration::from_secs_f64(wait / 1000.0)).await;
+        }
+        last = entry.t;
+        render(&entry.frame, Duration::from_millis(entry.t));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_format_works() {
+        let entry = Entry {
+            t: 1500,
+            frame: Frame::Event {
+                event: Some("tick".into()),
+                id: None,
+                data: "{}".into(),
+            },
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            line,
+            r#"{"t":1500,"type":"event","event":"tick","data":"{}"}"#
+        );
+        assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), entry);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{self, HeaderMap},
+    Client, Response, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::{
+    auth::{self, Auth},
+    upload::{file_size, progress_bar},
+};
+
+const TUS_VERSION: &str = "1.0.0";
+
+/// Upload URL created by the server, remembered so an interrupted upload can
+/// be resumed instead of starting over.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct TusState {
+    endpoint: String,
+    file_len: u64,
+    location: String,
+}
+
+impl TusState {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => Ok(Some(serde_json::from_str(&s).with_context(|| {
+                format!("Failed to parse upload state {}", path.display())
+            })?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write upload state {}", path.display()))
+    }
+
+    /// Whether this is the state of uploading `file_len` bytes to `endpoint`.
+    fn is_for(&self, endpoint: &Url, file_len: u64) -> bool {
+        self.endpoint == endpoint.as_str() && self.file_len == file_len
+    }
+}
+
+/// Default state file location: next to the uploaded file.
+pub fn default_state_path(file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.tus-state", file))
+}
+
+/// Upload `file` to the tus `endpoint` in `chunk_size` PATCH requests,
+/// resuming a previous upload recorded in `state_path` when possible.
+/// Returns the response to the final PATCH.
+pub async fn upload(
+    client: Client,
+    auth: Option<&dyn Auth>,
+    endpoint: Url,
+    file: &str,
+    chunk_size: u64,
+    state_path: &Path,
+) -> Result<Response> {
+    if chunk_size == 0 {
+        return Err(anyhow!("Chunk size must be greater than zero"));
+    }
+    let file_len = file_size(file).await?;
+
+    let mut resumed = None;
+    if let Some(state) = TusState::load(state_path)? {
+        if state.is_for(&endpoint, file_len) {
+            let location: Url = state.location.parse()?;
+            // the server may have expired the upload; start over in that case
+            if let Some(offset) = fetch_offset(&client, auth, &location).await? {
+                if offset > file_len {
+                    return Err(anyhow!(
+                        "Server has {} bytes of upload {}, but {} is only {} bytes",
+                        offset,
+                        location,
+                        file,
+                        file_len
+                    ));
+                }
+                eprintln!("Resuming upload {} at byte {}", location, offset);
+                resumed = Some((location, offset));
+            }
+        }
+    }
+    let (location, mut offset) = match resumed {
+        Some(r) => r,
+        None => {
+            let location = create(&client, auth, &endpoint, file, file_len).await?;
+            let state = TusState {
+                endpoint: endpoint.to_string(),
+                file_len,
+                location: location.to_string(),
+            };
+            state.save(state_path)?;
+            (location, 0)
+        }
+    };
+
+    let pb = progress_bar(file_len);
+    pb.set_position(offset);
+    let mut f = File::open(file)
+        .await
+        .with_context(|| format!("Failed to open {}", file))?;
+    let mut last = None;
+    while offset < file_len || last.is_none() {
+        let len = chunk_size.min(file_len - offset);
+        f.seek(SeekFrom::Start(offset)).await?;
+        let mut buf = vec![0; len as usize];
+        f.read_exact(&mut buf).await?;
+
+        let req = client
+            .patch(location.clone())
+            .header("Tus-Resumable", TUS_VERSION)
+            .header("Upload-Offset", offset)
+            .header(header::CONTENT_TYPE, "application/offset+octet-stream")
+            .body(buf)
+            .build()?;
+        let resp = auth::sign_and_send(&client, req, auth).await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!(
+                "Failed to upload chunk at byte {}: {}",
+                offset,
+                resp.status()
+            ));
+        }
+        let next = upload_offset(resp.headers())
+            .ok_or_else(|| anyhow!("Server response has no Upload-Offset"))?;
+        if next != offset + len {
+            return Err(anyhow!(
+                "Server acknowledged offset {}, expected {}",
+                next,
+                offset + len
+            ));
+        }
+        offset = next;
+        pb.set_position(offset);
+        last = Some(resp);
+    }
+    pb.finish();
+
+    std::fs::remove_file(state_path)?;
+    eprintln!("Uploaded to {}", location);
+    last.ok_or_else(|| anyhow!("Failed to upload {}: no chunk was sent", file))
+}
+
+/// Creation extension: POST the upload length and metadata, get the upload URL.
+async fn create(
+    client: &Client,
+    auth: Option<&dyn Auth>,
+    endpoint: &Url,
+    file: &str,
+    file_len: u64,
+) -> Result<Url> {
+    let name = Path::new(file)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| file.to_string());
+    let req = client
+        .post(endpoint.clone())
+        .header("Tus-Resumable", TUS_VERSION)
+        .header("Upload-Length", file_len)
+        .header(
+            "Upload-Metadata",
+            format!("filename {}", STANDARD.encode(name)),
+        )
+        .build()?;
+    let resp = auth::sign_and_send(client, req, auth).await?;
+    if resp.status() != StatusCode::CREATED {
+        return Err(anyhow!("Failed to create upload: {}", resp.status()));
+    }
+    let location = resp
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| anyhow!("Server response has no Location"))?;
+    // the location is commonly relative to the endpoint
+    Ok(endpoint.join(location)?)
+}
+
+/// Ask the server how much of the upload it already has. `None` means the
+/// upload is gone and has to be created again.
+async fn fetch_offset(
+    client: &Client,
+    auth: Option<&dyn Auth>,
+    location: &Url,
+) -> Result<Option<u64>> {
+    let req = client
+        .head(location.clone())
+        .header("Tus-Resumable", TUS_VERSION)
+        .build()?;
+    let resp = auth::sign_and_send(client, req, auth).await?;
+    match resp.status() {
+        StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::FORBIDDEN => Ok(None),
+        s if s.is_success() => Ok(upload_offset(resp.headers())),
+        s => Err(anyhow!("Failed to fetch upload offset: {}", s)),
+    }
+}
+
+fn upload_offset(headers: &HeaderMap) -> Option<u64> {
+    headers.get("Upload-Offset")?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upload_offset_works() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(upload_offset(&headers), None);
+        headers.insert("Upload-Offset", "1024".parse().unwrap());
+        assert_eq!(upload_offset(&headers), Some(1024));
+        headers.insert("Upload-Offset", "-1".parse().unwrap());
+        assert_eq!(upload_offset(&headers), None);
+    }
+
+    #[test]
+    fn tus_state_works() {
+        let path = std::env::temp_dir().join(format!("tus-state-{}", std::process::id()));
+        assert_eq!(TusState::load(&path).unwrap(), None);
+
+        let endpoint: Url = "http://localhost/files/".parse().unwrap();
+        let state = TusState {
+            endpoint: endpoint.to_string(),
+            file_len: 10,
+            location: "http://localhost/files/abc".to_string(),
+        };
+        state.save(&path).unwrap();
+        let loaded = TusState::load(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, state);
+        assert!(loaded.is_for(&endpoint, 10));
+        // a changed file or another endpoint starts over
+        assert!(!loaded.is_for(&endpoint, 11));
+        assert!(!loaded.is_for(&"http://localhost/other/".parse().unwrap(), 10));
+    }
+}
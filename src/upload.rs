@@ -0,0 +1,68 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{multipart::Part, Body};
+use tokio::fs::File;
+use tokio_util::io::ReaderStream;
+
+/// Files are streamed from disk in chunks of this size, so memory use stays
+/// bounded no matter how large the upload is.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Set by `--ci`, where progress bars only clutter the log.
+static HIDE_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+pub fn hide_progress() {
+    HIDE_PROGRESS.store(true, Ordering::Relaxed);
+}
+
+/// Upload progress bar drawn on stderr, showing bytes sent and throughput.
+pub fn progress_bar(total: u64) -> ProgressBar {
+    if HIDE_PROGRESS.load(Ordering::Relaxed) {
+        return ProgressBar::hidden();
+    }
+    let pb = ProgressBar::new(total);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner} [{elapsed_precise}] [{bar:40}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+        )
+        .unwrap()
+        .progress_chars("=> "),
+    );
+    pb
+}
+
+/// Size of the file at `path`, used to size the progress bar and to send an
+/// explicit Content-Length.
+pub async fn file_size(path: &str) -> Result<u64> {
+    let meta = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path))?;
+    Ok(meta.len())
+}
+
+/// Open `path` as a streaming request body, advancing `pb` as chunks are read.
+pub async fn file_body(path: &str, pb: &ProgressBar) -> Result<Body> {
+    let file = File::open(path)
+        .await
+        .with_context(|| format!("Failed to open {}", path))?;
+    let reader = pb.wrap_async_read(file);
+    Ok(Body::wrap_stream(ReaderStream::with_capacity(
+        reader, CHUNK_SIZE,
+    )))
+}
+
+/// Multipart part streaming the file at `path`, named after its file name.
+pub async fn file_part(path: &str, pb: &ProgressBar) -> Result<Part> {
+    let len = file_size(path).await?;
+    let body = file_body(path, pb).await?;
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    Ok(Part::stream_with_length(body, len).file_name(name))
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, Command};
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+/// How often each subcommand and flag was used, kept in
+/// `~/.httpie/usage.json` when the config says `usage_stats = true`. It is
+/// only ever read by `httpie usage`; nothing is sent anywhere.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Counts {
+    pub runs: u64,
+    pub subcommands: BTreeMap<String, u64>,
+    pub flags: BTreeMap<String, u64>,
+}
+
+pub fn default_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".httpie")
+        .join("usage.json")
+}
+
+/// How a flag is written, `--long` where it has a long form.
+fn flag_name(arg: &clap::Arg) -> Option<String> {
+    if arg.is_positional() {
+        return None;
+    }
+    arg.get_long()
+        .map(|l| format!("--{}", l))
+        .or_else(|| arg.get_short().map(|s| format!("-{}", s)))
+}
+
+/// The flags of `cmd` given on the command line in `matches`; globals are
+/// left to the parent command.
+fn given(cmd: &Command, matches: &ArgMatches, with_globals: bool) -> Vec<String> {
+    cmd.get_arguments()
+        .filter(|a| with_globals || !a.is_global_set())
+        .filter(|a| matches.value_source(a.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .filter_map(flag_name)
+        .collect()
+}
+
+/// Flags that can go with the subcommands used so far, minus those used.
+fn never_used(counts: &Counts, cmd: &Command) -> Vec<String> {
+    let used_subcommands = cmd
+        .get_subcommands()
+        .filter(|s| counts.subcommands.contains_key(s.get_name()));
+    cmd.get_arguments()
+        .chain(used_subcommands.flat_map(|s| s.get_arguments().filter(|a| !a.is_global_set())))
+        .filter_map(flag_name)
+        .filter(|f| !["--help", "--version"].contains(&f.as_str()) && !counts.flags.contains_key(f))
+        .fold(Vec::new(), |mut flags, f| {
+            if !flags.contains(&f) {
+                flags.push(f);
+            }
+            flags
+        })
+}
+
+impl Counts {
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s)
+                .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Count one run of `cmd` as parsed into `matches`.
+    pub fn add(&mut self, cmd: &Command, matches: &ArgMatches) {
+        self.runs += 1;
+        let mut flags = given(cmd, matches, true);
+        if let Some((name, sub_matches)) = matches.subcommand() {
+            *self.subcommands.entry(name.to_string()).or_default() += 1;
+            if let Some(sub) = cmd.find_subcommand(name) {
+                flags.extend(given(sub, sub_matches, false));
+            }
+        }
+        for flag in flags {
+            *self.flags.entry(flag).or_default() += 1;
+        }
+    }
+}
+
+/// Count this run in the file at `path`.
+pub fn record(path: &Path, cmd: &Command, matches: &ArgMatches) -> Result<()> {
+    let mut counts = Counts::load(path)?;
+    counts.add(cmd, matches);
+    counts.save(path)
+}
+
+/// Most used first, then by name.
+fn by_count(counts: &BTreeMap<String, u64>) -> Vec<(&String, &u64)> {
+    let mut sorted: Vec<_> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    sorted
+}
+
+/// Print what the file at `path` has counted, and the flags of the
+/// subcommands used that never were.
+pub fn show(path: &Path, cmd: &Command, enabled: bool) -> Result<()> {
+    let counts = Counts::load(path)?;
+    if !enabled {
+        eprintln!(
+            "{}",
+            "Usage statistics are off; set `usage_stats = true` in ~/.httpie/config.toml \
+             to count subcommands and flags in ~/.httpie/usage.json"
+                .yellow()
+        );
+    }
+    if counts.runs == 0 {
+        println!("Nothing counted yet");
+        return Ok(());
+    }
+    println!("{} runs counted in {}", counts.runs, path.display());
+    for (title, section) in [
+        ("Subcommands", &counts.subcommands),
+        ("Flags", &counts.flags),
+    ] {
+        println!("\n{}", title.bold());
+        for (name, n) in by_count(section) {
+            println!("  {:<28}{}", name, n);
+        }
+    }
+    let unused = never_used(&counts, cmd);
+    if !unused.is_empty() {
+        println!("\n{}", "Never used".bold());
+        println!("  {}", unused.join(" ").dimmed());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_works() {
+        let cmd = crate::command();
+        let mut counts = Counts::default();
+        for args in [
+            ["httpie", "get", "--json-output", "http://localhost"].as_slice(),
+            &[
+                "httpie",
+                "--ci",
+                "get",
+                "--parallel",
+                "2",
+                "http://localhost",
+            ],
+            &["httpie", "post", "--sorted", "http://localhost", "a=1"],
+        ] {
+            let matches = cmd.clone().try_get_matches_from(args).unwrap();
+            counts.add(&cmd, &matches);
+        }
+        assert_eq!(counts.runs, 3);
+        assert_eq!(by_count(&counts.subcommands)[0], (&"get".to_string(), &2));
+        assert_eq!(counts.flags["--json-output"], 1);
+        assert_eq!(counts.flags["--ci"], 1);
+        assert_eq!(counts.flags["--parallel"], 1);
+        // defaults do not count
+        assert!(!counts.flags.contains_key("--max-pages"));
+
+        let unused = never_used(&counts, &cmd);
+        assert!(unused.contains(&"--max-pages".to_string()));
+        assert!(!unused.contains(&"--sorted".to_string()));
+        assert!(!unused.contains(&"--help".to_string()));
+        // only the flags of subcommands used
+        assert!(!unused.contains(&"--concurrency".to_string()));
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::ConditionalOpts;
+
+/// The validators last received for a URL.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Validators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let get = |name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        Self {
+            etag: get(header::ETAG),
+            last_modified: get(header::LAST_MODIFIED),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Validators per URL, kept in a JSON file for `--cached`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Cache(BTreeMap<String, Validators>);
+
+pub fn default_cache_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".httpie")
+        .join("validators.json")
+}
+
+fn key(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.into()
+}
+
+impl Cache {
+    /// Load the cache at `path`, or an empty one when it does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(s) => serde_json::from_str(&s)
+                .map_err(|e| anyhow!(format!("Failed to parse {}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    pub fn get(&self, url: &Url) -> Option<&Validators> {
+        self.0.get(&key(url))
+    }
+
+    /// Remember the validators of a response; returns whether anything changed.
+    pub fn record(&mut self, url: &Url, headers: &HeaderMap) -> bool {
+        let mut fresh = Validators::from_headers(headers);
+        if fresh.is_empty() {
+            return false;
+        }
+        let entry = self.0.entry(key(url)).or_default();
+        // a 304 may repeat only some of them
+        if fresh.etag.is_none() {
+            fresh.etag = entry.etag.clone();
+        }
+        if fresh.last_modified.is_none() {
+            fresh.last_modified = entry.last_modified.clone();
+        }
+        let changed = *entry != fresh;
+        *entry = fresh;
+        changed
+    }
+}
+
+/// `If-Modified-Since` input: an HTTP date, or a file whose modification time
+/// is used (like `curl -z`).
+pub fn parse_since(s: &str) -> Result<SystemTime> {
+    let path = Path::new(s);
+    if path.exists() {
+        return path
+            .metadata()
+            .and_then(|m| m.modified())
+            .with_context(|| format!("Failed to read the modification time of {}", s));
+    }
+    httpdate::parse_http_date(s).map_err(|_| {
+        anyhow!(format!(
+            "Failed to parse date {}: expected e.g. `Wed, 21 Oct 2015 07:28:00 GMT` or a file",
+            s
+        ))
+    })
+}
+
+/// An entity tag as sent in `If-None-Match`, quoted unless it already is.
+pub fn quote_etag(s: &str) -> String {
+    let s = s.trim();
+    if s == "*" || s.starts_with('"') || s.starts_with("W/\"") || s.contains(',') {
+        s.to_string()
+    } else {
+        format!("\"{}\"", s)
+    }
+}
+
+/// Set the conditional headers: explicit flags win over cached validators.
+pub fn apply(
+    headers: &mut HeaderMap,
+    opts: &ConditionalOpts,
+    cached: Option<&Validators>,
+) -> Result<()> {
+    let etag = opts
+        .if_none_match
+        .as_deref()
+        .map(quote_etag)
+        .or_else(|| cached?.etag.clone());
+    let since = opts
+        .if_modified_since
+        .map(httpdate::fmt_http_date)
+        .or_else(|| cached?.last_modified.clone());
+    if let Some(etag) = etag {
+        headers.insert(header::IF_NONE_MATCH, etag.parse()?);
+    }
+    if let Some(since) = since {
+        headers.insert(header::IF_MODIFIED_SINCE, since.parse()?);
+    }
+    Ok(())
+}
+
+/// Explain a response to a conditional request.
+pub fn report(status: StatusCode, sent: &HeaderMap) {
+    let conditions: Vec<String> = [
+        ("If-None-Match", header::IF_NONE_MATCH),
+        ("If-Modified-Since", header::IF_MODIFIED_SINCE),
+    ]
+    .into_iter()
+    .filter_map(|(label, name)| {
+        let v = sent.get(name)?.to_str().ok()?;
+        Some(format!("{}: {}", label, v))
+    })
+    .collect();
+    if conditions.is_empty() {
+        return;
+    }
+    let conditions = conditions.join(", ");
+    if status == StatusCode::NOT_MODIFIED {
+        eprintln!(
+            "{} the cached copy is still valid ({})",
+            "304 Not Modified:".green().bold(),
+            conditions
+        );
+    } else if status.is_success() {
+        eprintln!(
+            "{} the resource changed or the validators were ignored ({})",
+            "Full response:".yellow().bold(),
+            conditions
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_etag_works() {
+        assert_eq!(quote_etag("abc"), "\"abc\"");
+        assert_eq!(quote_etag("\"abc\""), "\"abc\"");
+        assert_eq!(quote_etag("W/\"abc\""), "W/\"abc\"");
+        assert_eq!(quote_etag("*"), "*");
+    }
+
+    #[test]
+    fn cache_works() {
+        let url: Url = "http://localhost/a#top".parse().unwrap();
+        let mut cache = Cache::default();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ETAG, "\"v1\"".parse().unwrap());
+        headers.insert(
+            header::LAST_MODIFIED,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert!(cache.record(&url, &headers));
+        assert!(!cache.record(&url, &headers));
+        // a 304 carrying only the ETag keeps Last-Modified
+        let mut etag_only = HeaderMap::new();
+        etag_only.insert(header::ETAG, "\"v2\"".parse().unwrap());
+        assert!(cache.record(&url, &etag_only));
+        let v = cache.get(&"http://localhost/a".parse().unwrap()).unwrap();
+        assert_eq!(v.etag.as_deref(), Some("\"v2\""));
+        assert!(v.last_modified.is_some());
+
+        let opts = ConditionalOpts {
+            if_none_match: Some("mine".into()),
+            ..Default::default()
+        };
+        let mut sent = HeaderMap::new();
+        apply(&mut sent, &opts, Some(v)).unwrap();
+        assert_eq!(sent[header::IF_NONE_MATCH], "\"mine\"");
+        assert_eq!(
+            sent[header::IF_MODIFIED_SINCE],
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+    }
+
+    #[test]
+    fn parse_since_works() {
+        assert!(parse_since("Wed, 21 Oct 2015 07:28:00 GMT").is_ok());
+        assert!(parse_since("Cargo.toml").is_ok());
+        assert!(parse_since("yesterday").is_err());
+    }
+}
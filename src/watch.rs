@@ -0,0 +1,188 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use colored::Colorize;
+use reqwest::{header, Client};
+
+use crate::{
+    auth::{self, Auth},
+    compression,
+    exchange::Exchange,
+    stats::format_duration,
+};
+
+/// How many recent iterations make up the baseline.
+const WINDOW: usize = 20;
+/// Iterations seen before any is flagged.
+const WARMUP: usize = 5;
+/// How many spreads from the mean is sharp.
+const SHARP: f64 = 3.0;
+
+/// Shannon entropy in bits per byte: near 0 for repetitive bodies, near 8
+/// for compressed or random ones.
+pub fn entropy(bytes: &[u8]) -> f64 {
+    if bytes.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &b in bytes {
+        counts[usize::from(b)] += 1;
+    }
+    let len = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Rolling mean and spread of one measure. The spread has a floor, so that
+/// bodies which hardly vary do not make every small change look sharp.
+#[derive(Debug, Default)]
+struct Series {
+    values: VecDeque<f64>,
+}
+
+impl Series {
+    fn push(&mut self, value: f64) {
+        if self.values.len() == WINDOW {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// Mean and spread when `value` is sharply off them.
+    fn deviation(&self, value: f64, floor: impl Fn(f64) -> f64) -> Option<(f64, f64)> {
+        let n = self.values.len() as f64;
+        let mean = self.values.iter().sum::<f64>() / n;
+        let var = self.values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+        let spread = var.sqrt().max(floor(mean));
+        ((value - mean).abs() > SHARP * spread).then_some((mean, var.sqrt()))
+    }
+}
+
+/// Sizes and entropies of the bodies seen so far.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    sizes: Series,
+    entropies: Series,
+}
+
+impl Baseline {
+    /// What is off about a body of `size` bytes and `entropy`, if anything.
+    /// Bodies that look normal join the baseline; flagged ones stay out of
+    /// it, so a run of error pages does not become the new normal.
+    pub fn check(&mut self, size: usize, entropy: f64) -> Vec<String> {
+        let size = size as f64;
+        let mut flags = Vec::new();
+        if self.sizes.values.len() >= WARMUP {
+            // a tenth of the size, or 16 bytes
+            if let Some((mean, sd)) = self.sizes.deviation(size, |m| (m / 10.0).max(16.0)) {
+                let what = if size < mean { "smaller" } else { "larger" };
+                flags.push(format!(
+                    "body {} than usual: {} bytes, baseline {:.0}±{:.0}",
+                    what, size, mean, sd
+                ));
+            }
+            if let Some((mean, sd)) = self.entropies.deviation(entropy, |_| 0.25) {
+                flags.push(format!(
+                    "entropy {:.2} bits/byte, baseline {:.2}±{:.2}",
+                    entropy, mean, sd
+                ));
+            }
+        }
+        if flags.is_empty() {
+            self.sizes.push(size);
+            self.entropies.push(entropy);
+        }
+        flags
+    }
+}
+
+/// One line for a response: iteration, status, size, entropy and time.
+fn line(n: usize, exchange: &Exchange, size: usize, entropy: f64) -> String {
+    let status = exchange.status.to_string();
+    let status = if exchange.status.is_success() {
+        status.green()
+    } else {
+        status.red()
+    };
+    format!(
+        "{} {} {} bytes, {:.2} bits/byte, {}",
+        format!("#{}", n).dimmed(),
+        status,
+        size,
+        entropy,
+        format_duration(exchange.timing.total)
+    )
+}
+
+/// Fetch `url` every `interval` until interrupted, one line per response,
+/// flagging bodies whose size or entropy is sharply off the recent ones even
+/// while the status stays the same.
+pub async fn run(
+    client: &Client,
+    url: &str,
+    interval: Duration,
+    auth: Option<&dyn Auth>,
+) -> Result<()> {
+    let mut baseline = Baseline::default();
+    for n in 1.. {
+        let req = client.get(url).build()?;
+        let started = Instant::now();
+        let fetched = match auth::sign_and_send(client, req, auth).await {
+            Ok(resp) => Exchange::read(resp, started).await,
+            Err(e) => Err(e),
+        };
+        match fetched {
+            Ok(exchange) => {
+                let codings = exchange
+                    .headers
+                    .get(header::CONTENT_ENCODING)
+                    .and_then(|v| v.to_str().ok())
+                    .map(compression::codings)
+                    .unwrap_or_default();
+                let body = compression::decode(&codings, exchange.body.clone())
+                    .unwrap_or_else(|_| exchange.body.clone());
+                let entropy = entropy(&body);
+                println!("{}", line(n, &exchange, body.len(), entropy));
+                for flag in baseline.check(body.len(), entropy) {
+                    println!("  {} {}", "⚠".yellow().bold(), flag.yellow());
+                }
+            }
+            Err(e) => println!("{} {} {}", format!("#{}", n).dimmed(), "error".red(), e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baseline_works() {
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+
+        let mut baseline = Baseline::default();
+        for size in [1000, 1010, 990, 1005, 995] {
+            assert!(baseline.check(size, 4.5).is_empty());
+        }
+        // small wobbles are fine
+        assert!(baseline.check(1040, 4.6).is_empty());
+        // an error page served with a 200
+        let flags = baseline.check(120, 3.1);
+        assert_eq!(flags.len(), 2);
+        assert!(flags[0].starts_with("body smaller than usual: 120 bytes"));
+        // and it stays out of the baseline
+        assert_eq!(baseline.check(121, 3.1).len(), 2);
+    }
+}
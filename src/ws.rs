@@ -0,0 +1,82 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::{HeaderName, HeaderValue},
+    Message,
+};
+
+use crate::transcript::{self, Frame, Recorder};
+
+/// Connect to a `ws://` or `wss://` URL, send each stdin line as a text
+/// message and print incoming frames until either side closes.
+pub async fn connect(
+    url: &str,
+    headers: &reqwest::header::HeaderMap,
+    mut recorder: Option<Recorder>,
+) -> Result<()> {
+    let mut req = url
+        .into_client_request()
+        .with_context(|| format!("Failed to parse WebSocket URL {}", url))?;
+    // the handshake is a plain HTTP request, so the usual headers apply
+    for (name, value) in headers.iter() {
+        req.headers_mut().insert(
+            HeaderName::from_bytes(name.as_str().as_bytes())?,
+            HeaderValue::from_bytes(value.as_bytes())?,
+        );
+    }
+    let (mut socket, resp) = tokio_tungstenite::connect_async(req)
+        .await
+        .with_context(|| format!("Failed to connect to {}", url))?;
+    eprintln!("Connected: {}", resp.status());
+
+    let start = Instant::now();
+    let mut emit = |frame: Frame, elapsed: Duration| -> Result<()> {
+        transcript::render(&frame, elapsed);
+        if let Some(r) = recorder.as_mut() {
+            r.record(&frame, elapsed)?;
+        }
+        Ok(())
+    };
+
+    let mut stdin = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdin_open = true;
+    loop {
+        tokio::select! {
+            line = stdin.next_line(), if stdin_open => match line? {
+                Some(line) => {
+                    socket.send(Message::text(line.clone())).await?;
+                    emit(Frame::Text { data: line, outgoing: true }, start.elapsed())?;
+                }
+                None => {
+                    // end of input: close politely and wait for the server's close
+                    stdin_open = false;
+                    socket.close(None).await?;
+                }
+            },
+            msg = socket.next() => {
+                let Some(msg) = msg else { break };
+                let frame = match msg? {
+                    Message::Text(text) => Frame::Text { data: text.to_string(), outgoing: false },
+                    Message::Binary(bytes) => Frame::Binary { data: STANDARD.encode(&bytes), outgoing: false },
+                    Message::Close(close) => Frame::Close {
+                        code: close.as_ref().map(|c| c.code.into()),
+                        reason: close.map(|c| c.reason.to_string()).unwrap_or_default(),
+                    },
+                    // pings are answered automatically
+                    _ => continue,
+                };
+                let closed = matches!(frame, Frame::Close { .. });
+                emit(frame, start.elapsed())?;
+                if closed {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}